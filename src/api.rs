@@ -0,0 +1,310 @@
+//! Typed library interface to humpty.
+//!
+//! The CLI subcommands (`fit`, `csv`, `exp`) are thin layers over these functions,
+//! services can call them directly with in-memory data.
+//! Quantile and ensemble forecasts (`forecast`), the per hump decomposition (`components`) and the running
+//! residual statistics and alarms (`residuals`) live in their own modules and are re-exported here.
+//!
+//! ```no_run
+//! use humpty::api::{Series, FitConfig, fit, forecast};
+//! let series = Series::from_values( vec![1., 2., 4., 8., 12., 14., 15.] );
+//! let result = fit( &series, &FitConfig::default().humps(1).samples(50) ).unwrap();
+//! let best = result.best().unwrap();
+//! let ahead = forecast( &best.fitted_model, &[7., 8., 9.] );
+//! ```
+
+use std::cmp::Ordering;
+
+use nalgebra::DVector;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{HumptyError, Result};
+use crate::main_mod_dm_fit::{FitSummary, model_fit};
+use crate::models::ModelAffine::{AffineAdditive, FitDiagnostics};
+use crate::models::ModelTanh::ModelTanh;
+use crate::models::ParameterizedModel;
+use crate::transform::ValueTransform;
+use crate::viz_lib::invert_tanh;
+
+pub use crate::components::*;
+pub use crate::forecast::*;
+pub use crate::residuals::*;
+
+/// an observed series, `time[k]` is the time stamp of `value[k]`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Series {
+    pub time: Vec<f64>,
+    pub value: Vec<f64>,
+}
+
+impl Series {
+    pub fn new(time: Vec<f64>, value: Vec<f64>) -> Result<Self> {
+        if time.len() != value.len() {
+            return Err(HumptyError::parse(
+                "series",
+                format!("{} time stamps for {} values", time.len(), value.len()),
+            ));
+        }
+        Ok(Self { time, value })
+    }
+
+    /// values observed at t = 0, 1, 2, ...
+    pub fn from_values(value: Vec<f64>) -> Self {
+        let time = (0..value.len()).map(|x| x as f64).collect();
+        Self { time, value }
+    }
+
+    pub fn len(&self) -> usize {
+        self.value.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.value.is_empty()
+    }
+}
+
+/// settings for `fit`, built up from the default, e.g. `FitConfig::default().humps(4).samples(200).reports(10)`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FitConfig {
+    pub humps: usize,
+    pub samples: usize,
+    pub reports: usize,
+    pub keep_failed: bool,
+}
+
+impl Default for FitConfig {
+    fn default() -> Self {
+        Self { humps: 3, samples: 1000, reports: 1, keep_failed: false }
+    }
+}
+
+impl FitConfig {
+    /// number of tanh components in the model.
+    pub fn humps(mut self, humps: usize) -> Self {
+        self.humps = humps;
+        self
+    }
+    /// number of random restarts.
+    pub fn samples(mut self, samples: usize) -> Self {
+        self.samples = samples;
+        self
+    }
+    /// number of best fits kept in the result.
+    pub fn reports(mut self, reports: usize) -> Self {
+        self.reports = reports;
+        self
+    }
+    /// keep restarts where the solver failed or diverged.
+    pub fn keep_failed(mut self, keep_failed: bool) -> Self {
+        self.keep_failed = keep_failed;
+        self
+    }
+}
+
+/// the ranked fits (best first) and the solver summary over all restarts.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FitResult {
+    pub fits: Vec<model_fit<ModelTanh>>,
+    pub summary: FitSummary,
+}
+
+impl FitResult {
+    pub fn best(&self) -> Option<&model_fit<ModelTanh>> {
+        self.fits.first()
+    }
+}
+
+/// residual, residual per point, fitted model, initial model and solver diagnostics of one restart.
+type Restart = (f64, f64, AffineAdditive<ModelTanh>, AffineAdditive<ModelTanh>, FitDiagnostics);
+
+/// fit `config.humps` tanh components plus a constant to the series from `config.samples` random starts.
+pub fn fit(series: &Series, config: &FitConfig) -> Result<FitResult> {
+    let tspan = DVector::from_vec(series.time.clone());
+    let dspan = DVector::from_vec(series.value.clone());
+    let mut list: Vec<Restart> = (0..config.samples)
+        .into_par_iter()
+        .map(|_| {
+            let mut m2 = AffineAdditive::<ModelTanh>::random_model_given_humps(config.humps, &rand::thread_rng());
+            let m2init = m2.clone();
+            let mut diagnostics = m2.curve_fit(&tspan, &dspan)?;
+            let (rsumsq, rsumsq_pp, _, _) = m2.residual_mat(&tspan, &dspan);
+            if diagnostics.success && !rsumsq.is_finite() {
+                diagnostics.success = false;
+                diagnostics.termination = format!("Diverged (non-finite residual after {})", diagnostics.termination);
+            }
+            Ok((rsumsq, rsumsq_pp, m2, m2init, diagnostics))
+        })
+        .collect::<Result<_>>()?;
+
+    let summary = FitSummary::from_diagnostics(list.iter().map(|x| &x.4));
+    if !config.keep_failed {
+        list.retain(|x| x.4.success);
+        if list.is_empty() && config.reports > 0 {
+            return Err(HumptyError::Solver(format!(
+                "all {} restarts failed, use --keep-failed to keep them anyway",
+                config.samples
+            )));
+        }
+    }
+
+    // non-finite residuals (diverged restarts kept with keep_failed) rank last.
+    list.sort_by(|a, b| match (a.0.is_finite(), b.0.is_finite()) {
+        (true, true) => a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal),
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => Ordering::Equal,
+    });
+
+    let fits = list
+        .into_iter()
+        .take(config.reports)
+        .map(|(r, rpp, m, minit, diagnostics)| model_fit {
+            humps: config.humps,
+            fitted_model: m,
+            initial_model: minit,
+            residual_total: r,
+            residual_per_point: rpp,
+            diagnostics,
+        })
+        .collect();
+    Ok(FitResult { fits, summary })
+}
+
+/// model values at the requested times.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Forecast {
+    pub time: Vec<f64>,
+    pub value: Vec<f64>,
+}
+
+pub fn forecast(model: &AffineAdditive<ModelTanh>, times: &[f64]) -> Forecast {
+    Forecast {
+        time: times.to_vec(),
+        value: times.iter().map(|t| model.eval(*t)).collect(),
+    }
+}
+
+/// residual (data - model) summary of a model against a series.
+///     residual_total :     sqrt( sum of squared residuals )
+///     residual_per_point : residual_total / number of points
+///     running_skew :       skewness of the residuals up to and including each point
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResidualStats {
+    pub time: Vec<f64>,
+    pub model: Vec<f64>,
+    pub residuals: Vec<f64>,
+    pub squared: Vec<f64>,
+    pub residual_total: f64,
+    pub residual_per_point: f64,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub running_skew: Vec<f64>,
+}
+
+pub fn residual_stats(model: &AffineAdditive<ModelTanh>, series: &Series) -> ResidualStats {
+    let (residual_total, residual_per_point, squared, residuals) = model.residual(&series.time, &series.value);
+    let n = residuals.len().max(1) as f64;
+    let mean = residuals.iter().sum::<f64>() / n;
+    let std_dev = (residuals.iter().map(|r| (r - mean) * (r - mean)).sum::<f64>() / n).sqrt();
+    ResidualStats {
+        time: series.time.clone(),
+        model: series.time.iter().map(|t| model.eval(*t)).collect(),
+        running_skew: expanding_skew(&residuals),
+        residuals,
+        squared,
+        residual_total,
+        residual_per_point,
+        mean,
+        std_dev,
+    }
+}
+
+/// one tanh component in its several parameterisations.
+///     t0 = -β/α is the midpoint, motion_quantiles are the times of 25%, 50% and 75% motion,
+///     and the logistic form has X_0 = κ, r = 2α, K = 2κ.
+/// For a model fitted under a value transform κ is in the transformed space, `kappa_data` is κ in data units
+/// (unit range, z-score) and `factor` = e^(2κ) is the change of 1 + y over the whole hump (log1p).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HumpExplanation {
+    pub kappa: f64,
+    pub alpha: f64,
+    pub beta: f64,
+    pub t0: f64,
+    pub motion_quantiles: [f64; 3],
+    pub logistic_x0: f64,
+    pub logistic_r: f64,
+    pub logistic_k: f64,
+    pub kappa_data: Option<f64>,
+    pub factor: Option<f64>,
+}
+
+/// the humps of a model ordered by their midpoint t0, and the constant offset (also in data units under a transform).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Explanation {
+    pub humps: Vec<HumpExplanation>,
+    pub offset: f64,
+    pub offset_data: Option<f64>,
+}
+
+pub fn explain(model: &AffineAdditive<ModelTanh>) -> Explanation {
+    let mut components: Vec<ModelTanh> = model.tm.components.clone();
+    components.sort_by(|c, d| (-c.beta / c.alpha).partial_cmp(&(-d.beta / d.alpha)).unwrap_or(Ordering::Equal));
+    let affine = model.link.as_ref().and_then(|l| l.affine());
+    let log1p = model.link == Some(ValueTransform::Log1p);
+    let humps = components
+        .iter()
+        .map(|c| {
+            let mut mq = [
+                invert_tanh(0.25, c.alpha, c.beta),
+                invert_tanh(0.5, c.alpha, c.beta),
+                invert_tanh(0.75, c.alpha, c.beta),
+            ];
+            mq.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            HumpExplanation {
+                kappa: c.kappa,
+                alpha: c.alpha,
+                beta: c.beta,
+                t0: -c.beta / c.alpha,
+                motion_quantiles: mq,
+                logistic_x0: c.kappa,
+                logistic_r: 2. * c.alpha,
+                logistic_k: 2. * c.kappa,
+                kappa_data: affine.map(|(_, scale)| scale * c.kappa),
+                factor: if log1p { Some((2. * c.kappa).exp()) } else { None },
+            }
+        })
+        .collect();
+    let offset = model.km.eval(0.);
+    Explanation { humps, offset, offset_data: model.link.as_ref().map(|l| l.inverse(offset)) }
+}
+
+#[test]
+fn test_fit_recovers_single_hump() {
+    let truth = ModelTanh::new(10., 0.2, -4.);
+    let series = Series::from_values((0..40).map(|t| 3. + truth.eval(t as f64)).collect());
+    let result = fit(&series, &FitConfig::default().humps(1).samples(20)).unwrap();
+    let best = result.best().unwrap();
+    assert!(best.residual_per_point < 1e-3);
+    let e = explain(&best.fitted_model);
+    assert!((e.humps[0].t0 - 20.).abs() < 1e-2);
+    let ahead = forecast(&best.fitted_model, &[60.]);
+    assert!((ahead.value[0] - (3. + truth.eval(60.))).abs() < 1e-2);
+}
+
+#[test]
+fn test_fit_counts_failed_restarts() {
+    // a non-finite observation makes every restart fail or diverge.
+    let mut value: Vec<f64> = (0..20).map(|t| t as f64).collect();
+    value[10] = f64::NAN;
+    let series = Series::from_values(value);
+    let config = FitConfig::default().humps(1).samples(5).reports(5);
+    assert!(fit(&series, &config).is_err());
+    let result = fit(&series, &config.keep_failed(true)).unwrap();
+    assert_eq!(result.summary.samples, 5);
+    assert_eq!(result.summary.failed, 5);
+    assert_eq!(result.summary.failure_rate, 1.);
+    assert_eq!(result.fits.len(), 5);
+    assert!(result.fits.iter().all(|f| !f.diagnostics.success));
+    assert_eq!(result.summary.terminations.values().sum::<usize>(), 5);
+}
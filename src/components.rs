@@ -0,0 +1,87 @@
+//! Per hump decomposition of a fitted model over time, re-exported by `api`.
+
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::ModelAffine::AffineAdditive;
+use crate::models::ModelTanh::ModelTanh;
+use crate::models::ParameterizedModel;
+
+/// a model taken apart over time, `humps[j][k]` is hump j (ordered by t0) at `time[k]`:
+///     hump_j(t) = κ_j ( 1 + tanh( α_j t + β_j ) ),  rate_j(t) = κ_j α_j ( 1 - tanh²( α_j t + β_j ) )
+/// the baseline is the constant term and `sum` adds everything up.  Under an affine value transform the parts are
+/// scaled into data units (the shift goes to the baseline), so `sum` is the model; under log1p they stay in the
+/// additive log(1 + y) space and `data_units` is false.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Components {
+    pub time: Vec<f64>,
+    pub t0: Vec<f64>,
+    pub humps: Vec<Vec<f64>>,
+    pub baseline: Vec<f64>,
+    pub sum: Vec<f64>,
+    /// the derivative of each hump (incidence per unit of time) and of the sum.
+    pub rates: Vec<Vec<f64>>,
+    pub rate_sum: Vec<f64>,
+    pub data_units: bool,
+}
+
+pub fn components(model: &AffineAdditive<ModelTanh>, times: &[f64]) -> Components {
+    let mut humps: Vec<ModelTanh> = model.tm.components.clone();
+    humps.sort_by(|c, d| (-c.beta / c.alpha).partial_cmp(&(-d.beta / d.alpha)).unwrap_or(Ordering::Equal));
+    let (shift, scale, data_units) = match model.link.as_ref() {
+        None => (0., 1., true),
+        Some(link) => match link.affine() {
+            Some((shift, scale)) => (shift, scale, true),
+            None => (0., 1., false),
+        },
+    };
+    let values: Vec<Vec<f64>> = humps.iter().map(|c| times.iter().map(|t| scale * c.eval(*t)).collect()).collect();
+    let rates: Vec<Vec<f64>> = humps
+        .iter()
+        .map(|c| {
+            times
+                .iter()
+                .map(|t| {
+                    let th = (c.alpha * t + c.beta).tanh();
+                    scale * c.kappa * c.alpha * (1. - th * th)
+                })
+                .collect()
+        })
+        .collect();
+    let baseline: Vec<f64> = times.iter().map(|t| shift + scale * model.km.eval(*t)).collect();
+    let sum = (0..times.len()).map(|k| baseline[k] + values.iter().map(|h| h[k]).sum::<f64>()).collect();
+    let rate_sum = (0..times.len()).map(|k| rates.iter().map(|r| r[k]).sum()).collect();
+    Components {
+        time: times.to_vec(),
+        t0: humps.iter().map(|c| -c.beta / c.alpha).collect(),
+        humps: values,
+        baseline,
+        sum,
+        rates,
+        rate_sum,
+        data_units,
+    }
+}
+
+#[test]
+fn test_components_add_up() {
+    use crate::models::{ModelAdditive::ModelAdditive, ModelConstant::ModelConstant};
+    use crate::transform::ValueTransform;
+    let mut model = AffineAdditive::new(
+        ModelAdditive::new(vec![ModelTanh::new(5., 0.3, -9.), ModelTanh::new(10., 0.2, -2.)]),
+        ModelConstant::new(1.5),
+    );
+    model.link = Some(ValueTransform::ZScore { mean: 100., sd: 20. });
+    let times = [0., 10., 30., 45.];
+    let c = components(&model, &times);
+    // ordered by t0: 10 before 30.
+    assert_eq!(c.t0, vec![10., 30.]);
+    assert!(c.data_units);
+    for (k, t) in times.iter().enumerate() {
+        assert!((c.sum[k] - model.eval(*t)).abs() < 1e-9);
+        let h = 1e-5;
+        let slope = (model.eval(t + h) - model.eval(t - h)) / (2. * h);
+        assert!((c.rate_sum[k] - slope).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt;
+
+/// Errors surfaced by the humpty library.
+///
+/// The CLI maps each case to a message on stderr and an exit code (see `exit_code`),
+/// library users can match on the variants directly.
+#[derive(Debug)]
+pub enum HumptyError {
+    /// reading or writing a file failed.
+    Io { path: String, source: std::io::Error },
+    /// a file or argument could be read but not understood (csv, yaml, parameter strings, ...).
+    Parse { context: String, message: String },
+    /// the requested data column is not in the input.
+    MissingColumn { column: String, available: Vec<String> },
+    /// the package does not hold a fit at the requested index.
+    MissingModel { index: usize, available: usize },
+    /// the varpro/levmar problem could not be built or no restart converged.
+    Solver(String),
+    /// the offset/limit/strides combination cannot be served.
+    UnsupportedSlice(String),
+}
+
+pub type Result<T> = std::result::Result<T, HumptyError>;
+
+impl HumptyError {
+    pub fn io( path: impl Into<String>, source: std::io::Error ) -> Self {
+        HumptyError::Io { path: path.into(), source }
+    }
+    pub fn parse( context: impl Into<String>, message: impl fmt::Display ) -> Self {
+        HumptyError::Parse { context: context.into(), message: message.to_string() }
+    }
+
+    /// process exit code used by the CLI for this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            HumptyError::Io { .. } => 2,
+            HumptyError::Parse { .. } => 3,
+            HumptyError::MissingColumn { .. } => 4,
+            HumptyError::MissingModel { .. } => 5,
+            HumptyError::Solver(_) => 6,
+            HumptyError::UnsupportedSlice(_) => 7,
+        }
+    }
+}
+
+impl fmt::Display for HumptyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HumptyError::Io { path, source } => write!(f, "cannot access {path}: {source}"),
+            HumptyError::Parse { context, message } => write!(f, "cannot parse {context}: {message}"),
+            HumptyError::MissingColumn { column, available } => {
+                write!(f, "no column named '{column}', available columns are: {}", available.join(", "))
+            }
+            HumptyError::MissingModel { index, available } => {
+                write!(f, "no fit at index {index}, the package holds {available} fit(s)")
+            }
+            HumptyError::Solver(message) => write!(f, "solver failed: {message}"),
+            HumptyError::UnsupportedSlice(message) => write!(f, "unsupported slice: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for HumptyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HumptyError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for HumptyError {
+    fn from(source: std::io::Error) -> Self {
+        HumptyError::Io { path: String::from("-"), source }
+    }
+}
+
+impl From<polars::prelude::PolarsError> for HumptyError {
+    fn from(e: polars::prelude::PolarsError) -> Self {
+        HumptyError::parse("data frame", e)
+    }
+}
+
+impl From<serde_yaml::Error> for HumptyError {
+    fn from(e: serde_yaml::Error) -> Self {
+        HumptyError::parse("yaml", e)
+    }
+}
+
+impl From<csv::Error> for HumptyError {
+    fn from(e: csv::Error) -> Self {
+        let message = e.to_string();
+        match e.into_kind() {
+            csv::ErrorKind::Io(source) => HumptyError::from(source),
+            _ => HumptyError::parse("csv", message),
+        }
+    }
+}
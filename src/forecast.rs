@@ -0,0 +1,359 @@
+//! Quantile and ensemble forecasts of fitted models, re-exported by `api`.
+
+use std::cmp::Ordering;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::api::Series;
+use crate::error::{HumptyError, Result};
+use crate::main_mod_dm_fit::model_fit;
+use crate::models::ModelAffine::AffineAdditive;
+use crate::models::ModelTanh::ModelTanh;
+use crate::models::ParameterizedModel;
+
+/// where the spread of a quantile forecast comes from.
+///     residual :  the empirical quantiles of the fit residuals (in the fitted space of a transformed model), widened
+///                 by sqrt(ahead) beyond the end of the fitted data as for a random walk.
+///     ensemble :  the weighted quantiles over the forecasts of several fits, e.g. the top fits of a package
+///                 weighted as in `ensemble_forecast` (see `weighted_quantile`).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Spread {
+    #[default]
+    Residual,
+    Ensemble,
+}
+
+impl std::str::FromStr for Spread {
+    type Err = HumptyError;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "residual" => Ok(Spread::Residual),
+            "ensemble" => Ok(Spread::Ensemble),
+            _ => Err(HumptyError::parse("forecast spread", format!("'{s}' is not one of residual, ensemble"))),
+        }
+    }
+}
+
+/// predictive quantiles, `value[k][j]` is the `quantiles[j]` quantile at `time[k]`.
+/// `ahead[k]` counts time steps past the last fitted time (zero or less inside the fitted range).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QuantileForecast {
+    pub time: Vec<f64>,
+    pub ahead: Vec<f64>,
+    pub quantiles: Vec<f64>,
+    pub value: Vec<Vec<f64>>,
+}
+
+/// the q quantile of sorted values, linear between order statistics.
+pub fn sorted_quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    let h = q.clamp(0., 1.) * (sorted.len() - 1) as f64;
+    let (lo, hi) = (h.floor() as usize, h.ceil() as usize);
+    sorted[lo] + (h - lo as f64) * (sorted[hi] - sorted[lo])
+}
+
+fn check_quantiles(quantiles: &[f64]) -> Result<()> {
+    match quantiles.iter().find(|q| !(0. ..=1.).contains(*q)) {
+        Some(q) => Err(HumptyError::parse("forecast quantiles", format!("{q} is not between 0 and 1"))),
+        None if quantiles.is_empty() => Err(HumptyError::parse("forecast quantiles", "no quantile levels given")),
+        None => Ok(()),
+    }
+}
+
+/// the median spacing of increasing times, 1 when there is none.
+pub fn time_step(times: &[f64]) -> f64 {
+    let mut gaps: Vec<f64> = times.windows(2).map(|w| w[1] - w[0]).filter(|g| *g > 0.).collect();
+    gaps.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    if gaps.is_empty() { 1. } else { gaps[gaps.len() / 2] }
+}
+
+/// time steps past the end of `fitted` (see `time_step`).
+fn horizons(fitted: &[f64], times: &[f64]) -> Vec<f64> {
+    let step = time_step(fitted);
+    let last = fitted.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    times.iter().map(|t| (t - last) / step).collect()
+}
+
+/// the sorted finite residuals of `model` on the data it was fitted on, in the fitted space.
+fn fitted_residuals(model: &AffineAdditive<ModelTanh>, fitted: &Series) -> Result<Vec<f64>> {
+    let forward = |y: f64| model.link.as_ref().map_or(y, |l| l.forward(y));
+    let mut residuals: Vec<f64> = fitted
+        .time
+        .iter()
+        .zip(fitted.value.iter())
+        .map(|(t, y)| forward(*y) - model.eval_fitted(*t))
+        .filter(|r| r.is_finite())
+        .collect();
+    if residuals.len() < 2 {
+        return Err(HumptyError::parse("residual spread", "fewer than two fitted points with data"));
+    }
+    residuals.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    Ok(residuals)
+}
+
+/// quantile forecast from the residuals of `model` on the data it was fitted on (see `Spread::Residual`).
+pub fn residual_quantile_forecast(
+    model: &AffineAdditive<ModelTanh>,
+    fitted: &Series,
+    times: &[f64],
+    quantiles: &[f64],
+) -> Result<QuantileForecast> {
+    check_quantiles(quantiles)?;
+    let inverse = |z: f64| model.link.as_ref().map_or(z, |l| l.inverse(z));
+    let residuals = fitted_residuals(model, fitted)?;
+    let offsets: Vec<f64> = quantiles.iter().map(|q| sorted_quantile(&residuals, *q)).collect();
+    let ahead = horizons(&fitted.time, times);
+    let value = times
+        .iter()
+        .zip(ahead.iter())
+        .map(|(t, h)| {
+            let z = model.eval_fitted(*t);
+            let widen = h.max(1.).sqrt();
+            offsets.iter().map(|r| inverse(z + widen * r)).collect()
+        })
+        .collect();
+    Ok(QuantileForecast { time: times.to_vec(), ahead, quantiles: quantiles.to_vec(), value })
+}
+
+/// the q quantile of (value, weight) pairs: the values are sorted and each sits at the level of the middle of its
+/// weight, ( weight below + weight / 2 ) / total, with linear steps in between and the end values beyond.
+/// Values that are not finite or have no positive weight are left out.
+pub fn weighted_quantile(values: &[(f64, f64)], q: f64) -> f64 {
+    let mut at: Vec<(f64, f64)> = values.iter().copied().filter(|(v, w)| v.is_finite() && *w > 0.).collect();
+    if at.is_empty() {
+        return f64::NAN;
+    }
+    at.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+    let total: f64 = at.iter().map(|(_, w)| w).sum();
+    let mut below = 0.;
+    let levels: Vec<f64> = at
+        .iter()
+        .map(|(_, w)| {
+            below += w;
+            (below - w / 2.) / total
+        })
+        .collect();
+    match levels.partition_point(|p| *p < q) {
+        0 => at[0].0,
+        k if k == at.len() => at[k - 1].0,
+        k => at[k - 1].0 + (q - levels[k - 1]) / (levels[k] - levels[k - 1]) * (at[k].0 - at[k - 1].0),
+    }
+}
+
+/// weighted quantiles over the members of an ensemble (see `Spread::Ensemble`), `fitted` only places the horizon.
+pub fn ensemble_quantile_forecast(ef: &EnsembleForecast, fitted: &[f64], quantiles: &[f64]) -> Result<QuantileForecast> {
+    check_quantiles(quantiles)?;
+    if ef.members.len() < 2 {
+        return Err(HumptyError::parse("ensemble spread", format!("needs at least two fits, found {}", ef.members.len())));
+    }
+    let value = (0..ef.time.len())
+        .map(|k| {
+            let at: Vec<(f64, f64)> = ef.members.iter().zip(ef.weights.iter()).map(|(m, w)| (m[k], *w)).collect();
+            quantiles.iter().map(|q| weighted_quantile(&at, *q)).collect()
+        })
+        .collect();
+    Ok(QuantileForecast { time: ef.time.clone(), ahead: horizons(fitted, &ef.time), quantiles: quantiles.to_vec(), value })
+}
+
+/// number of residual random-walk paths behind an incidence forecast, see `residual_paths`.
+pub const RESIDUAL_PATHS: usize = 1000;
+
+/// random-walk paths of `model` at `times` (the residual spread as paths): every path starts on the model at
+/// `times[0]` and adds sqrt(steps) times a residual drawn from the fitted residuals for each interval of `times`,
+/// in the fitted space of a transformed model.  `paths[p][k]` is path p at `times[k]`, the draws come from `seed`.
+pub fn residual_paths(
+    model: &AffineAdditive<ModelTanh>,
+    fitted: &Series,
+    times: &[f64],
+    paths: usize,
+    seed: u64,
+) -> Result<Vec<Vec<f64>>> {
+    let residuals = fitted_residuals(model, fitted)?;
+    let inverse = |z: f64| model.link.as_ref().map_or(z, |l| l.inverse(z));
+    let ahead = horizons(&fitted.time, times);
+    let mut rng = StdRng::seed_from_u64(seed);
+    Ok((0..paths)
+        .map(|_| {
+            let mut walk = 0.;
+            times
+                .iter()
+                .enumerate()
+                .map(|(k, t)| {
+                    if k > 0 {
+                        walk += (ahead[k] - ahead[k - 1]).max(0.).sqrt() * residuals[rng.gen_range(0..residuals.len())];
+                    }
+                    inverse(model.eval_fitted(*t) + walk)
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// incidence quantiles from weighted cumulative paths (`paths[p][k]` is path p at time k, e.g. the members and weights
+/// of an `EnsembleForecast`, or `residual_paths` with equal weights): `value[k - 1][j]` is the `quantiles[j]` weighted
+/// quantile over the paths of paths[p][k] - paths[p][k - 1], clamped at zero so the levels stay ordered.
+/// Paths without a finite difference are left out.
+pub fn incidence_quantiles(paths: &[Vec<f64>], weights: &[f64], quantiles: &[f64]) -> Result<Vec<Vec<f64>>> {
+    check_quantiles(quantiles)?;
+    let steps = paths.iter().map(|p| p.len()).min().unwrap_or(0);
+    Ok((1..steps)
+        .map(|k| {
+            let diff: Vec<(f64, f64)> = paths.iter().zip(weights.iter()).map(|(p, w)| (p[k] - p[k - 1], *w)).collect();
+            // a NaN (no path with a difference) stays NaN.
+            quantiles.iter().map(|q| weighted_quantile(&diff, *q)).map(|v| if v < 0. { 0. } else { v }).collect()
+        })
+        .collect())
+}
+
+/// how the members of an ensemble are weighted, from their residuals on the fitted data (in the fitted space):
+///     akaike :       w ∝ exp( -ΔAIC / 2 ), AIC = n ln( RSS / n ) + 2 p with p = 3 humps + 1 parameters
+///     inverse_mse :  w ∝ 1 / MSE
+///     equal :        w = 1 / N
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Weighting {
+    #[default]
+    Akaike,
+    InverseMse,
+    Equal,
+}
+
+impl std::str::FromStr for Weighting {
+    type Err = HumptyError;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "akaike" | "aic" => Ok(Weighting::Akaike),
+            "inverse_mse" | "mse" => Ok(Weighting::InverseMse),
+            "equal" => Ok(Weighting::Equal),
+            _ => Err(HumptyError::parse("ensemble weights", format!("'{s}' is not one of akaike, inverse_mse, equal"))),
+        }
+    }
+}
+
+/// weighted combination of several fits, `members[i][k]` is fit i at `time[k]`.  The contribution of fit i is
+/// `weights[i] * members[i][k]`, the contributions add up to `mean[k]`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EnsembleForecast {
+    pub time: Vec<f64>,
+    pub weights: Vec<f64>,
+    /// sum of squared residuals of each fit on the fitted data, in the fitted space.
+    pub rss: Vec<f64>,
+    pub members: Vec<Vec<f64>>,
+    pub mean: Vec<f64>,
+    /// weighted standard deviation of the members around the mean.
+    pub spread: Vec<f64>,
+}
+
+/// normalized weights from the residual sums of squares of `n` points and the parameter counts (see `Weighting`).
+/// Fits with a non-finite residual get no weight, a fit without residual takes all of it.
+pub fn ensemble_weights(rss: &[f64], parameters: &[usize], n: usize, weighting: Weighting) -> Result<Vec<f64>> {
+    let n = n as f64;
+    let raw: Vec<f64> = match weighting {
+        Weighting::Equal => vec![1.; rss.len()],
+        Weighting::InverseMse => rss.iter().map(|r| n / r).collect(),
+        Weighting::Akaike => {
+            let aic: Vec<f64> = rss.iter().zip(parameters).map(|(r, p)| n * (r / n).ln() + 2. * *p as f64).collect();
+            let best = aic.iter().copied().fold(f64::INFINITY, f64::min);
+            // a fit without residual (AIC of -inf) takes all the weight.
+            aic.iter().map(|a| if best == f64::NEG_INFINITY { (*a == best) as u8 as f64 } else { (-(a - best) / 2.).exp() }).collect()
+        }
+    };
+    let raw: Vec<f64> = raw.iter().zip(rss).map(|(w, r)| if r.is_finite() { *w } else { 0. }).collect();
+    let raw: Vec<f64> = if raw.iter().any(|w| w.is_infinite()) {
+        raw.iter().map(|w| w.is_infinite() as u8 as f64).collect()
+    } else {
+        raw.iter().map(|w| if w.is_finite() { *w } else { 0. }).collect()
+    };
+    let total: f64 = raw.iter().sum();
+    if total <= 0. {
+        return Err(HumptyError::parse("ensemble weights", "no fit has a finite residual"));
+    }
+    Ok(raw.iter().map(|w| w / total).collect())
+}
+
+/// the weighted ensemble of `fits` at `times`, weighted by their residuals on `fitted` (see `Weighting`).
+pub fn ensemble_forecast(
+    fits: &[&model_fit<ModelTanh>],
+    fitted: &Series,
+    times: &[f64],
+    weighting: Weighting,
+) -> Result<EnsembleForecast> {
+    if fits.is_empty() {
+        return Err(HumptyError::parse("ensemble", "no fits to combine"));
+    }
+    let residuals: Vec<Vec<f64>> = fits
+        .iter()
+        .map(|f| {
+            let model = &f.fitted_model;
+            let forward = |y: f64| model.link.as_ref().map_or(y, |l| l.forward(y));
+            fitted.time.iter().zip(fitted.value.iter()).map(|(t, y)| forward(*y) - model.eval_fitted(*t)).collect()
+        })
+        .collect();
+    // a fit that cannot be evaluated at some data point (diverged) has no residual.
+    let n = residuals.iter().map(|r| r.iter().filter(|x| x.is_finite()).count()).max().unwrap_or(0);
+    let rss: Vec<f64> = residuals
+        .iter()
+        .map(|r| match r.iter().filter(|x| x.is_finite()).count() {
+            count if count < n => f64::NAN,
+            _ => r.iter().filter(|x| x.is_finite()).map(|x| x * x).sum(),
+        })
+        .collect();
+    let parameters: Vec<usize> = fits.iter().map(|f| 3 * f.fitted_model.tm.components.len() + 1).collect();
+    let weights = ensemble_weights(&rss, &parameters, n, weighting)?;
+    let members: Vec<Vec<f64>> = fits.iter().map(|f| times.iter().map(|t| f.fitted_model.eval(*t)).collect()).collect();
+    let (mean, spread) = (0..times.len())
+        .map(|k| {
+            // members without a value at a time are left out and the others renormalized.
+            let at: Vec<(f64, f64)> = weights.iter().zip(members.iter()).map(|(w, m)| (*w, m[k])).filter(|(w, v)| *w > 0. && v.is_finite()).collect();
+            let total: f64 = at.iter().map(|(w, _)| w).sum();
+            let mean = at.iter().map(|(w, v)| w * v).sum::<f64>() / total;
+            let var = at.iter().map(|(w, v)| w * (v - mean) * (v - mean)).sum::<f64>() / total;
+            (mean, var.sqrt())
+        })
+        .unzip();
+    Ok(EnsembleForecast { time: times.to_vec(), weights, rss, members, mean, spread })
+}
+
+#[test]
+fn test_residual_quantile_forecast() {
+    use crate::models::{ModelAdditive::ModelAdditive, ModelConstant::ModelConstant};
+    assert_eq!(sorted_quantile(&[1., 2., 3., 4.], 0.5), 2.5);
+    let model = AffineAdditive::new(ModelAdditive::new(vec![ModelTanh::new(10., 0.2, -4.)]), ModelConstant::new(0.));
+    // alternating residuals of +-1 around the model.
+    let time: Vec<f64> = (0..40).map(|t| t as f64).collect();
+    let value = time.iter().enumerate().map(|(k, t)| model.eval(*t) + if k % 2 == 0 { 1. } else { -1. }).collect();
+    let fitted = Series::new(time, value).unwrap();
+    let f = residual_quantile_forecast(&model, &fitted, &[20., 43.], &[0., 0.5, 1.]).unwrap();
+    assert_eq!(f.ahead, vec![-19., 4.]);
+    assert!((f.value[0][0] - (model.eval(20.) - 1.)).abs() < 1e-12);
+    assert!((f.value[0][1] - model.eval(20.)).abs() < 1e-12);
+    // two steps of widening four steps ahead.
+    assert!((f.value[1][2] - (model.eval(43.) + 2.)).abs() < 1e-12);
+    assert!(residual_quantile_forecast(&model, &fitted, &[1.], &[1.5]).is_err());
+}
+
+#[test]
+fn test_ensemble_weights() {
+    let rss = [1., 4., f64::NAN];
+    let w = ensemble_weights(&rss, &[4, 4, 4], 10, Weighting::InverseMse).unwrap();
+    assert!((w[0] - 0.8).abs() < 1e-12 && (w[1] - 0.2).abs() < 1e-12 && w[2] == 0.);
+    // equal parameters: exp( -n ln( RSS_1 / RSS_0 ) / 2 ) = ( 1 / 4 )^5 relative weight.
+    let w = ensemble_weights(&rss, &[4, 4, 4], 10, Weighting::Akaike).unwrap();
+    assert!((w[1] / w[0] - 0.25f64.powi(5)).abs() < 1e-12);
+    assert_eq!(ensemble_weights(&rss, &[4, 4, 4], 10, Weighting::Equal).unwrap(), vec![0.5, 0.5, 0.]);
+    assert_eq!(ensemble_weights(&[0., 1.], &[4, 4], 10, Weighting::InverseMse).unwrap(), vec![1., 0.]);
+    assert!(ensemble_weights(&[f64::NAN], &[4], 10, Weighting::Equal).is_err());
+    // levels at the middle of each weight: 1/8, 3/8, 5/8, 7/8 for four equal weights.
+    let v = [(4., 1.), (1., 1.), (3., 1.), (2., 1.)];
+    assert_eq!(weighted_quantile(&v, 0.5), 2.5);
+    assert_eq!(weighted_quantile(&v, 0.05), 1.);
+    assert_eq!(weighted_quantile(&[(1., 0.5), (2., 0.), (3., 0.5)], 0.5), 2.);
+    // three quarters of the weight on 1: levels 3/8 and 7/8.
+    assert_eq!(weighted_quantile(&[(1., 3.), (3., 1.)], 0.375), 1.);
+    assert_eq!(weighted_quantile(&[(1., 3.), (3., 1.)], 0.5), 1.5);
+}
@@ -0,0 +1,242 @@
+use std::io::{Cursor, Read};
+
+use chrono::NaiveDate;
+use polars::prelude::{BooleanChunked, CsvReader, DataFrame, DataType, IntoSeries, JsonFormat, JsonLineReader, JsonReader, NamedFrom, NewChunkedArray, SerReader, Series};
+use serde::{Serialize, Deserialize};
+
+use crate::error::{HumptyError, Result};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq )]
+#[serde(rename_all = "lowercase")]
+pub enum InputFormat {
+    Csv,
+    /// an array of records.
+    Json,
+    /// one record per line (json lines).
+    Ndjson,
+    Parquet,
+}
+
+impl std::str::FromStr for InputFormat {
+    type Err = HumptyError;
+    fn from_str( s : &str ) -> Result<Self> {
+        match s {
+            "csv" => Ok( InputFormat::Csv ),
+            "json" => Ok( InputFormat::Json ),
+            "ndjson" | "jsonl" => Ok( InputFormat::Ndjson ),
+            "parquet" => Ok( InputFormat::Parquet ),
+            _ => Err( HumptyError::parse( "input format", format!( "'{s}' is not one of csv, json, ndjson, parquet" ))),
+        }
+    }
+}
+
+impl InputFormat {
+    /// by file extension, otherwise (stdin, unknown extension) by the first bytes: the parquet magic, `[` for json, `{` for json lines, csv else.
+    pub fn detect( path : &str, head : &[u8] ) -> Self {
+        let extension = std::path::Path::new( path ).extension().and_then( |e| e.to_str() ).map( |e| e.to_ascii_lowercase() );
+        match extension.as_deref() {
+            Some( "csv" ) => return InputFormat::Csv,
+            Some( "parquet" | "pq" ) => return InputFormat::Parquet,
+            Some( "ndjson" | "jsonl" ) => return InputFormat::Ndjson,
+            _ => {},
+        }
+        if head.starts_with( b"PAR1" ) {
+            return InputFormat::Parquet
+        }
+        match head.iter().find( |b| ! b.is_ascii_whitespace() ) {
+            Some( b'[' ) => InputFormat::Json,
+            Some( b'{' ) => InputFormat::Ndjson,
+            _ => InputFormat::Csv,
+        }
+    }
+}
+
+/// read a table from a file or from stdin (`-`), the format is detected unless given; returns the format used
+/// and the content hash of the bytes read (`blake3:<hex>`).
+pub fn read_input( path : &str, format : Option<InputFormat> ) -> Result<(DataFrame, InputFormat, String)> {
+    let bytes = if path == "-" {
+        let mut buffer = Vec::new();
+        std::io::stdin().read_to_end( &mut buffer ).map_err( |e| HumptyError::io( "stdin", e ))?;
+        buffer
+    } else {
+        std::fs::read( path ).map_err( |e| HumptyError::io( path, e ))?
+    };
+    let format = format.unwrap_or_else( || InputFormat::detect( path, &bytes[..bytes.len().min( 64 )] ) );
+    let hash = content_hash( &bytes );
+    let cursor = Cursor::new( bytes );
+    let df = match format {
+        InputFormat::Csv => CsvReader::new( cursor ).has_header( true ).with_try_parse_dates( true ).finish(),
+        InputFormat::Json => JsonReader::new( cursor ).with_json_format( JsonFormat::Json ).finish(),
+        InputFormat::Ndjson => JsonLineReader::new( cursor ).finish(),
+        #[cfg(feature = "parquet")]
+        InputFormat::Parquet => polars::prelude::ParquetReader::new( cursor ).finish(),
+        #[cfg(not(feature = "parquet"))]
+        InputFormat::Parquet => return Err( HumptyError::parse( path, "parquet support is not built in, rebuild with --features parquet" )),
+    };
+    Ok(( df.map_err( |e| HumptyError::parse( path, e ))?, format, hash ))
+}
+
+/// the content hash recorded for an input, `blake3:<hex>` of its raw bytes.
+pub fn content_hash( bytes : &[u8] ) -> String {
+    format!( "blake3:{}", blake3::hash( bytes ).to_hex() )
+}
+
+////////////////
+///
+/// wide tables (JHU / USAFacts style) have one row per region and one column per date:
+///
+/// ```text
+/// UID,...,Admin2,Province_State,...,1/22/20,1/23/20,...
+/// ```
+///
+/// the rows matching every `select` entry (KEY=VALUE, compared on trimmed text) are summed per date column,
+/// so `Admin2=Alameda` picks a county and `Province_State=California` (or no selection) gives the total.
+/// Columns whose name is a date (M/D/YY, M/D/YYYY or YYYY-MM-DD) are melted into a `date` column and a value column.
+#[derive(Debug, Serialize, Deserialize, Clone, Default )]
+pub struct WideTable {
+    #[serde(default)]
+    pub select : Vec<String>,
+}
+
+impl WideTable {
+    pub fn new( select : Vec<String> ) -> Self {
+        Self { select }
+    }
+
+    /// KEY=VALUE pairs of the selection.
+    fn selection( &self ) -> Result<Vec<(String, String)>> {
+        self.select.iter().map( |s| match s.split_once( '=' ) {
+            Some( (k, v) ) => Ok( ( k.trim().to_string(), v.trim().to_string() ) ),
+            None => Err( HumptyError::parse( "wide table selection", format!( "'{s}' is not KEY=VALUE" ))),
+        }).collect()
+    }
+
+    /// melt the selected rows into a long frame with columns `date` and `value_column`.
+    pub fn melt( &self, df : &DataFrame, value_column : &str ) -> Result<DataFrame> {
+        let rows = matching_rows( df, &self.selection()? )?;
+        let matched = rows.iter().filter( |x| **x ).count();
+        if matched == 0 {
+            return Err( HumptyError::parse( "wide table selection", format!( "no row matches {}", self.select.join( ", " ) )))
+        }
+        eprintln!( "wide table: summing {matched} of {} rows", df.height() );
+
+        let mut dated : Vec<(NaiveDate, f64)> = Vec::new();
+        for column in df.get_columns() {
+            let Some( date ) = parse_header_date( column.name() ) else { continue };
+            if ! column.dtype().is_numeric() {
+                return Err( HumptyError::parse( format!( "column {}", column.name() ), format!( "expected counts, found {}", column.dtype() )))
+            }
+            let values = column.cast( &DataType::Float64 )?;
+            let picked : Vec<f64> = values.f64()?.into_iter().zip( rows.iter() ).filter( |(_, keep)| **keep ).filter_map( |(v, _)| v ).collect();
+            // a date with no value in any selected row stays missing.
+            let total = if picked.is_empty() { f64::NAN } else { picked.iter().sum() };
+            dated.push( ( date, total ) );
+        }
+        if dated.is_empty() {
+            return Err( HumptyError::parse( "wide table", "no column name is a date (M/D/YY, M/D/YYYY or YYYY-MM-DD)" ))
+        }
+        dated.sort_by_key( |(d, _)| *d );
+
+        let epoch = NaiveDate::from_ymd_opt( 1970, 1, 1 ).unwrap();
+        let days : Vec<i32> = dated.iter().map( |(d, _)| ( *d - epoch ).num_days() as i32 ).collect();
+        let values : Vec<f64> = dated.iter().map( |(_, v)| *v ).collect();
+        let date = Series::new( "date", days ).cast( &DataType::Date )?;
+        Ok( DataFrame::new( vec![ date.into_series(), Series::new( value_column, values ) ])? )
+    }
+}
+
+////////////////
+///
+/// long tables stack several series, one row per (group, date), e.g. `county,state,date,count`.
+/// A `GroupKey` keeps the rows whose `column` equals `value` (compared on trimmed text).
+#[derive(Debug, Serialize, Deserialize, Clone )]
+pub struct GroupKey {
+    pub column : String,
+    pub value : String,
+}
+
+impl GroupKey {
+    pub fn new( column : String, value : String ) -> Self {
+        Self { column, value }
+    }
+
+    pub fn filter( &self, df : &DataFrame ) -> Result<DataFrame> {
+        let rows = matching_rows( df, &[ ( self.column.clone(), self.value.trim().to_string() ) ] )?;
+        let mask = BooleanChunked::from_slice( "group", &rows );
+        Ok( df.filter( &mask )? )
+    }
+}
+
+/// the distinct (trimmed) values of `column` in order of first appearance, missing values are skipped.
+pub fn group_values( df : &DataFrame, column : &str ) -> Result<Vec<String>> {
+    let text = text_column( df, column )?;
+    let mut values : Vec<String> = Vec::new();
+    for x in text.utf8()?.into_iter().flatten() {
+        let x = x.trim();
+        if ! values.iter().any( |v| v == x ) {
+            values.push( x.to_string() );
+        }
+    }
+    Ok( values )
+}
+
+fn text_column( df : &DataFrame, column : &str ) -> Result<Series> {
+    let found = df.column( column ).map_err( |_| HumptyError::MissingColumn {
+        column: column.to_string(),
+        available: df.get_column_names().iter().map( |x| x.to_string() ).collect() })?;
+    Ok( found.cast( &DataType::Utf8 )? )
+}
+
+/// rows where every KEY column equals VALUE (trimmed text).
+fn matching_rows( df : &DataFrame, selection : &[(String, String)] ) -> Result<Vec<bool>> {
+    let mut rows : Vec<bool> = vec![ true; df.height() ];
+    for (key, value) in selection {
+        let text = text_column( df, key )?;
+        text.utf8()?.into_iter().zip( rows.iter_mut() ).for_each( |(x, keep)| {
+            *keep = *keep && x.is_some_and( |x| x.trim() == value );
+        });
+    }
+    Ok( rows )
+}
+
+fn parse_header_date( name : &str ) -> Option<NaiveDate> {
+    ["%m/%d/%y", "%m/%d/%Y", "%Y-%m-%d"].iter().find_map( |f| NaiveDate::parse_from_str( name.trim(), f ).ok() )
+}
+
+#[test]
+fn test_wide_table_melt() {
+    let df = DataFrame::new( vec![
+        Series::new( "Admin2", &["Alameda ", "Alpine", "Amador"] ),
+        Series::new( "1/23/20", &[Some( 2i64 ), Some( 0 ), None] ),
+        Series::new( "1/22/20", &[Some( 1i64 ), Some( 0 ), Some( 5 )] ),
+    ]).unwrap();
+    let county = WideTable::new( vec![ String::from( "Admin2=Alameda" ) ] ).melt( &df, "cases" ).unwrap();
+    let values : Vec<f64> = county.column( "cases" ).unwrap().f64().unwrap().into_no_null_iter().collect();
+    assert_eq!( values, vec![1., 2.] );
+    assert_eq!( county.column( "date" ).unwrap().dtype(), &DataType::Date );
+    let total = WideTable::default().melt( &df, "cases" ).unwrap();
+    let values : Vec<f64> = total.column( "cases" ).unwrap().f64().unwrap().into_no_null_iter().collect();
+    assert_eq!( values, vec![6., 2.] );
+    assert!( WideTable::new( vec![ String::from( "Admin2=Nowhere" ) ] ).melt( &df, "cases" ).is_err() );
+}
+
+#[test]
+fn test_group_key_filter() {
+    let df = DataFrame::new( vec![
+        Series::new( "county", &["Los Angeles County ", "Orange County", "Los Angeles County"] ),
+        Series::new( "count", &[1i64, 2, 3] ),
+    ]).unwrap();
+    assert_eq!( group_values( &df, "county" ).unwrap(), vec!["Los Angeles County", "Orange County"] );
+    let la = GroupKey::new( String::from( "county" ), String::from( "Los Angeles County" ) ).filter( &df ).unwrap();
+    assert_eq!( la.height(), 2 );
+}
+
+#[test]
+fn test_input_format_detect() {
+    assert_eq!( InputFormat::detect( "x.csv", b"{" ), InputFormat::Csv );
+    assert_eq!( InputFormat::detect( "x.pq", b"" ), InputFormat::Parquet );
+    assert_eq!( InputFormat::detect( "-", b"PAR1...." ), InputFormat::Parquet );
+    assert_eq!( InputFormat::detect( "-", b"  [{\"t\":1}]" ), InputFormat::Json );
+    assert_eq!( InputFormat::detect( "x.json", b"{\"t\":1}\n" ), InputFormat::Ndjson );
+    assert_eq!( InputFormat::detect( "-", b"date,count" ), InputFormat::Csv );
+}
@@ -3,7 +3,20 @@ pub mod main_mod_dm_fit;
 pub mod main_mod_dm_viz;   
 pub mod main_mod_dm_csv; 
 pub mod main_mod_dm_exp; 
+pub mod main_mod_dm_run;
+pub mod main_mod_dm_migrate;
+pub mod main_mod_dm_score;
+pub mod main_mod_dm_backtest;
+pub mod ingest;
+pub mod resample;
+pub mod transform;
+pub mod stats;
+pub mod forecast;
+pub mod components;
+pub mod residuals;
 pub mod models;
+pub mod api;
+pub mod error;
 pub mod viz_lib;
 pub mod viz_lib2; 
 //pub mod tests;
@@ -1,16 +1,33 @@
-use clap::{Command, Arg, value_parser, arg, ArgGroup, Subcommand};
+use clap::{Command, Arg, ArgAction, value_parser, arg, ArgGroup, Subcommand};
 use humpty::main_mod_dm_gen::{build_tanh_model_from_string, write_data};
 use humpty::main_mod_dm_fit::*; 
 use humpty::main_mod_dm_viz::*;
 use humpty::main_mod_dm_exp::*;
 use humpty::main_mod_dm_csv::*;
+use humpty::main_mod_dm_run::run_job;
+use humpty::main_mod_dm_migrate::migrate_packages;
+use humpty::main_mod_dm_score::{read_truth, score_files};
+use humpty::main_mod_dm_backtest::{backtest, RollingOrigins, Baseline};
+use humpty::api::{FitConfig, StatWindow};
+use humpty::ingest::{WideTable, InputFormat};
+use humpty::resample::{Aggregate, SeriesStep};
+use humpty::stats::{AlarmConfig, Detector};
+use humpty::transform::ValueTransform;
+use humpty::error::HumptyError;
 
 //use std::intrinsics::offset;
 use std::path::{Path, PathBuf};
 
 fn main() {
     let m: clap::ArgMatches = cli().get_matches();
+    if let Err(e) = run(&m) {
+        eprintln!("humpty: {e}");
+        std::process::exit(e.exit_code());
+    }
+}
 
+/// dispatch the parsed command line, library errors are handed back to main for reporting.
+fn run(m: &clap::ArgMatches) -> Result<(), HumptyError> {
     match m.subcommand() {
         // MODULE 1 create the HDC Brain Architecture 
         Some(("gen", m)) => { 
@@ -24,13 +41,14 @@ fn main() {
                     println!( " here with params = {params} , error ={error} steps={time_steps} output={:?}", &output );
                     // TODO ... lets improve upon this interface ...
                     //println!( " gen {output} ");
-                    let ( t, d, D , M)  = build_tanh_model_from_string( time_steps, params, error, true ); 
+                    let ( t, d, D , M)  = build_tanh_model_from_string( time_steps, params, error, true )?; 
                     let stub = output.with_extension(""); //.as_os_str().to_str().unwrap();
-                    let _= write_data( format!("{}.csv", stub.as_os_str().to_str().unwrap() ).as_str(), &t, &D ); 
+                    write_data( format!("{}.csv", stub.as_os_str().to_str().unwrap() ).as_str(), &t, &D )?; 
                     //let f = std::fs::File::open("out.yml").expect("Could not open file.");
                     //let mut scrape_config: Config = serde_yaml::from_reader(f).expect("Could not read values.");
-                    let serialized = serde_yaml::to_string(&M).unwrap();
-                    std::fs::write(format!("{}_model.yml", stub.as_os_str().to_str().unwrap() ).as_str() , serialized).unwrap();
+                    let serialized = serde_yaml::to_string(&M)?;
+                    let model_path = format!("{}_model.yml", stub.as_os_str().to_str().unwrap() );
+                    std::fs::write(model_path.as_str() , serialized).map_err(|e| HumptyError::io(model_path.clone(), e))?;
                 }
                 _ => {}
             }
@@ -42,9 +60,26 @@ fn main() {
             let samples : usize = m.get_one::<usize>("samples" ).expect("parsing humps issue").clone();
             let reports : usize = m.get_one::<usize>("reports" ).expect("parsing reports issue").clone();
             let offset: Option<i64>  = match m.get_one::<i64>("offset") {Some(O) => Some( *O ),None => None};
-            let limit: Option<usize>  = match m.get_one::<usize>( "limit"){Some(O) => Some( *O ),None => None}; 
+            let limit: Option<usize>  = limit_arg(m); 
             let strides  = match m.get_one::<usize>("strides"){Some(O) => Some( *O ),None => None};
             let data_column   = m.get_one::<String>("col").expect("data column needed" );
+            let keep_failed : bool = m.get_flag("keep_failed");
+            let time : Option<TimeAxis> = match m.get_one::<String>("date_col") {
+                Some(column) => Some( TimeAxis::new( column.clone(), m.get_one::<String>("time_unit").expect("time unit has a default").parse()?, m.get_one::<String>("origin").cloned() )),
+                None => None,
+            };
+            let select : Vec<String> = m.get_many::<String>("select").map( |v| v.cloned().collect() ).unwrap_or_default();
+            let group_by : Option<String> = m.get_one::<String>("group_by").cloned();
+            let mut steps : Vec<SeriesStep> = Vec::new();
+            if let Some(period) = m.get_one::<String>("resample") {
+                let how : Aggregate = m.get_one::<String>("agg").expect("aggregate has a default").parse()?;
+                steps.push( SeriesStep::resample( period.parse()?, m.get_one::<String>("anchor").cloned(), how, m.get_flag("keep_partial") )? );
+            }
+            if m.get_flag("diff") { steps.push( SeriesStep::Diff ); }
+            if m.get_flag("cumulate") { steps.push( SeriesStep::Cumulate ); }
+            let transform : Option<ValueTransform> = m.get_one::<String>("transform").map( |x| x.parse() ).transpose()?;
+            let format : Option<InputFormat> = m.get_one::<String>("format").map( |x| x.parse() ).transpose()?;
+            let wide : Option<WideTable> = if m.get_flag("wide") || ! select.is_empty() { Some( WideTable::new( select ) ) } else { None };
             let L : String = match limit {
                 Some(x) => format!( "{} ", x ),
                 None => String::from( "- " ),
@@ -54,76 +89,77 @@ fn main() {
                 None => String::from( "-" )
             };
             println!( " fitting model ({input}, {output}, {humps}, {samples}, limit:{L}, {S})");
-            model_curve_fitting( input, output, humps, samples, reports, data_column, offset, limit, strides ); 
+            model_curve_fitting( input, output, humps, samples, reports, data_column, offset, limit, strides, time, wide, group_by, steps, transform, format, m.get_flag("embed"), keep_failed )?; 
         },
         Some( ("viz", m )) => {
             match m.subcommand(){
                 Some(("basic", m )) =>  
                 {
                     //let data : String = m.get_one::<String>("DATA").expect( "data file not specified").clone();
-                    let models : String = m.get_one::<String>("MODEL").expect( "MODEL file not specified").clone();
+                    let models : String = model_arg(m)?;
                     let output : String = m.get_one::<String>("OUTPUT").expect( "OUTPUT file not specified").clone();
                     let top : usize = m.get_one::<usize>("top" ).expect("parsing top issue").clone();
                     let offset: Option<i64>  = match m.get_one::<i64>("offset") {Some(O) => Some( *O ),None => None};
-                    let limit: Option<usize>  = match m.get_one::<usize>( "limit"){Some(O) => Some( *O ),None => None}; 
+                    let limit: Option<usize>  = limit_arg(m); 
                     let strides  = match m.get_one::<usize>("strides"){Some(O) => Some( *O ),None => None};
                     let title : Option<String> = match m.get_one::<String>("title"){Some(O) => Some( O.clone() ),None => None};
                     let xlabel : Option<String> = match m.get_one::<String>("xlabel"){Some(O) => Some( O.clone() ),None => None};
                     let ylabel : Option<String> = match m.get_one::<String>("ylabel"){Some(O) => Some( O.clone() ),None => None};
-                    basic_visualization(  models, output, top, offset, limit, strides , partial_model::new(60, 120), title, xlabel, ylabel);
+                    basic_visualization(  models, output, top, offset, limit, strides , partial_model::new(60, 120), title, xlabel, ylabel, ensemble_arg(m)?)?;
                 },
                 Some(("intermediate", m )) => {
                     //let data : String = m.get_one::<String>("DATA").expect( "data file not specified").clone();
-                    let models : String = m.get_one::<String>("MODEL").expect( "MODEL file not specified").clone();
+                    let models : String = model_arg(m)?;
                     let output : String = m.get_one::<String>("OUTPUT").expect( "OUTPUT file not specified").clone();
                     let item : usize = m.get_one::<usize>("item" ).expect("parsing item issue").clone();
                     let pval : f64 = m.get_one::<f64>("pval" ).expect("parsing pval issue").clone();
                     let offset: Option<i64>  = match m.get_one::<i64>("offset") {Some(O) => Some( *O ),None => None};
-                    let limit: Option<usize>  = match m.get_one::<usize>( "limit"){Some(O) => Some( *O ),None => None}; 
+                    let limit: Option<usize>  = limit_arg(m); 
                     let strides  = match m.get_one::<usize>("strides"){Some(O) => Some( *O ),None => None};
                     let title : Option<String> = match m.get_one::<String>("title"){Some(O) => Some( O.clone() ),None => None};
                     let xlabel : Option<String> = match m.get_one::<String>("xlabel"){Some(O) => Some( O.clone() ),None => None};
                     let ylabel : Option<String> = match m.get_one::<String>("ylabel"){Some(O) => Some( O.clone() ),None => None};
-                    intermediate_visualization( models, output, item, pval, offset, limit, strides , title, xlabel, ylabel);
+                    intermediate_visualization( models, output, item, pval, offset, limit, strides , title, xlabel, ylabel)?;
                 },
                 Some(("residual", m )) => {
                     //let data : String = m.get_one::<String>("DATA").expect( "data file not specified").clone();
-                    let models : String = m.get_one::<String>("MODEL").expect( "MODEL file not specified").clone();
+                    let models : String = model_arg(m)?;
                     let output : String = m.get_one::<String>("OUTPUT").expect( "OUTPUT file not specified").clone();
                     let index : usize = m.get_one::<usize>("index" ).expect("parsing model at index").clone();
                     let offset: Option<i64>  = match m.get_one::<i64>("offset") {Some(O) => Some( *O ),None => None};
-                    let limit: Option<usize>  = match m.get_one::<usize>( "limit"){Some(O) => Some( *O ),None => None}; 
+                    let limit: Option<usize>  = limit_arg(m); 
                     let strides  = match m.get_one::<usize>("strides"){Some(O) => Some( *O ),None => None};
                     let title : Option<String> = match m.get_one::<String>("title"){Some(O) => Some( O.clone() ),None => None};
                     let xlabel : Option<String> = match m.get_one::<String>("xlabel"){Some(O) => Some( O.clone() ),None => None};
                     let ylabel : Option<String> = match m.get_one::<String>("ylabel"){Some(O) => Some( O.clone() ),None => None};
-                    residual_visualization(  models, output, index, offset, limit, strides, partial_model::new(60, 120), title, xlabel, ylabel);
+                    let alarms : Option<AlarmConfig> = if m.get_flag("alarms") { Some( alarm_arg(m) ) } else { None };
+                    residual_visualization(  models, output, index, offset, limit, strides, partial_model::new(60, 120), title, xlabel, ylabel, alarms)?;
                 },
                 Some(("disp", m )) => {
                     //let data : String = m.get_one::<String>("DATA").expect( "data file not specified").clone();
-                    let models : String = m.get_one::<String>("MODEL").expect( "MODEL file not specified").clone();
+                    let models : String = model_arg(m)?;
                     let output : String = m.get_one::<String>("OUTPUT").expect( "OUTPUT file not specified").clone();
                     let index : usize = m.get_one::<usize>("index" ).expect("parsing model at index").clone();
                     let offset: Option<i64>  = match m.get_one::<i64>("offset") {Some(O) => Some( *O ),None => None};
-                    let limit: Option<usize>  = match m.get_one::<usize>( "limit"){Some(O) => Some( *O ),None => None}; 
+                    let limit: Option<usize>  = limit_arg(m); 
                     let strides  = match m.get_one::<usize>("strides"){Some(O) => Some( *O ),None => None};
                     let title : Option<String> = match m.get_one::<String>("title"){Some(O) => Some( O.clone() ),None => None};
                     let xlabel : Option<String> = match m.get_one::<String>("xlabel"){Some(O) => Some( O.clone() ),None => None};
                     let ylabel : Option<String> = match m.get_one::<String>("ylabel"){Some(O) => Some( O.clone() ),None => None};
-                    disp_visualization(  models, output, index, offset, limit, strides, partial_model::new(60, 120), title, xlabel, ylabel);
+                    disp_visualization(  models, output, index, offset, limit, strides, partial_model::new(60, 120), title, xlabel, ylabel)?;
                 },
                 Some(("skew", m )) => {
                     //let data : String = m.get_one::<String>("DATA").expect( "data file not specified").clone();
-                    let models : String = m.get_one::<String>("MODEL").expect( "MODEL file not specified").clone();
+                    let models : String = model_arg(m)?;
                     let output : String = m.get_one::<String>("OUTPUT").expect( "OUTPUT file not specified").clone();
                     let index : usize = m.get_one::<usize>("index" ).expect("parsing model at index").clone();
                     let offset: Option<i64>  = match m.get_one::<i64>("offset") {Some(O) => Some( *O ),None => None};
-                    let limit: Option<usize>  = match m.get_one::<usize>( "limit"){Some(O) => Some( *O ),None => None}; 
+                    let limit: Option<usize>  = limit_arg(m); 
                     let strides  = match m.get_one::<usize>("strides"){Some(O) => Some( *O ),None => None};
                     let title : Option<String> = match m.get_one::<String>("title"){Some(O) => Some( O.clone() ),None => None};
                     let xlabel : Option<String> = match m.get_one::<String>("xlabel"){Some(O) => Some( O.clone() ),None => None};
                     let ylabel : Option<String> = match m.get_one::<String>("ylabel"){Some(O) => Some( O.clone() ),None => None};
-                    skew_visualization(  models, output, index, offset, limit, strides, partial_model::new(60, 120), title, xlabel, ylabel);
+                    skew_visualization(  models, output, index, offset, limit, strides, partial_model::new(60, 120), title, xlabel, ylabel, window_arg(m)?, m.get_one::<String>("stat").expect("stat has a default").parse()?)?;
                 },
                 _ => {}
             }
@@ -132,43 +168,75 @@ fn main() {
             match m.subcommand(){
                 Some(("fore", m )) => {
                     //let data : String = m.get_one::<String>("DATA").expect( "data file not specified").clone();
-                    let models : String = m.get_one::<String>("MODEL").expect( "MODEL file not specified").clone();
+                    let models : String = model_arg(m)?;
                     let output : String = m.get_one::<String>("OUTPUT").expect( "OUTPUT file not specified").clone();
                     let index : usize = match m.get_one::<usize>("index" ){Some(O) => *O , None => 0};
                     let offset: Option<i64>  = match m.get_one::<i64>("offset") {Some(O) => Some( *O ),None => None};
-                    let limit: Option<usize>  = match m.get_one::<usize>( "limit"){Some(O) => Some( *O ),None => None}; 
+                    let limit: Option<usize>  = limit_arg(m); 
                     let strides  = match m.get_one::<usize>("strides"){Some(O) => Some( *O ),None => None};
-                    drop_csv_data(  models, output, index, offset, limit, strides, partial_model::new(0, 0));
+                    drop_csv_data(  models, output, index, offset, limit, strides, partial_model::new(0, 0), quantiles_arg(m)?, ensemble_arg(m)?)?;
+                },
+                Some(("components", m )) => {
+                    let models : String = model_arg(m)?;
+                    let output : String = m.get_one::<String>("OUTPUT").expect( "OUTPUT file not specified").clone();
+                    let index : usize = *m.get_one::<usize>("index" ).expect("index has a default");
+                    let offset: Option<i64> = m.get_one::<i64>("offset").copied();
+                    let limit: Option<usize> = limit_arg(m);
+                    let strides: Option<usize> = m.get_one::<usize>("strides").copied();
+                    components_csv( models, output, index, offset, limit, strides, m.get_flag("incidence") )?;
+                },
+                Some(("alarms", m )) => {
+                    let models : String = model_arg(m)?;
+                    let output : String = m.get_one::<String>("OUTPUT").expect( "OUTPUT file not specified").clone();
+                    let index : usize = *m.get_one::<usize>("index" ).expect("index has a default");
+                    let offset: Option<i64> = m.get_one::<i64>("offset").copied();
+                    let limit: Option<usize> = limit_arg(m);
+                    let strides: Option<usize> = m.get_one::<usize>("strides").copied();
+                    alarms_csv( models, output, index, offset, limit, strides, &alarm_arg(m) )?;
                 },
                 Some(("residual", m )) => {
                     //let data : String = m.get_one::<String>("DATA").expect( "data file not specified").clone();
-                    let models : String = m.get_one::<String>("MODEL").expect( "MODEL file not specified").clone();
+                    let models : String = model_arg(m)?;
                     let output : String = m.get_one::<String>("OUTPUT").expect( "OUTPUT file not specified").clone();
                     let index : usize = m.get_one::<usize>("index" ).expect("parsing model at index").clone();
                     let offset: Option<i64>  = match m.get_one::<i64>("offset") {Some(O) => Some( *O ),None => None};
-                    let limit: Option<usize>  = match m.get_one::<usize>( "limit"){Some(O) => Some( *O ),None => None}; 
+                    let limit: Option<usize>  = limit_arg(m); 
                     let strides  = match m.get_one::<usize>("strides"){Some(O) => Some( *O ),None => None};
-                    residual_disp_skew_csv(  models, output, index, offset, limit, strides, partial_model::new(0, 0));
+                    residual_disp_skew_csv(  models, output, index, offset, limit, strides, partial_model::new(0, 0), None)?;
                 },
                 Some(("disp", m )) => {
                     //let data : String = m.get_one::<String>("DATA").expect( "data file not specified").clone();
-                    let models : String = m.get_one::<String>("MODEL").expect( "MODEL file not specified").clone();
+                    let models : String = model_arg(m)?;
                     let output : String = m.get_one::<String>("OUTPUT").expect( "OUTPUT file not specified").clone();
                     let index : usize = m.get_one::<usize>("index" ).expect("parsing model at index").clone();
                     let offset: Option<i64>  = match m.get_one::<i64>("offset") {Some(O) => Some( *O ),None => None};
-                    let limit: Option<usize>  = match m.get_one::<usize>( "limit"){Some(O) => Some( *O ),None => None}; 
+                    let limit: Option<usize>  = limit_arg(m); 
                     let strides  = match m.get_one::<usize>("strides"){Some(O) => Some( *O ),None => None};
-                    residual_disp_skew_csv(  models, output, index, offset, limit, strides, partial_model::new(0, 0));
+                    residual_disp_skew_csv(  models, output, index, offset, limit, strides, partial_model::new(0, 0), None)?;
                 },
                 Some(("skew", m )) => {
                     //let data : String = m.get_one::<String>("DATA").expect( "data file not specified").clone();
-                    let models : String = m.get_one::<String>("MODEL").expect( "MODEL file not specified").clone();
+                    let models : String = model_arg(m)?;
                     let output : String = m.get_one::<String>("OUTPUT").expect( "OUTPUT file not specified").clone();
                     let index : usize = m.get_one::<usize>("index" ).expect("parsing model at index").clone();
                     let offset: Option<i64>  = match m.get_one::<i64>("offset") {Some(O) => Some( *O ),None => None};
-                    let limit: Option<usize>  = match m.get_one::<usize>( "limit"){Some(O) => Some( *O ),None => None}; 
+                    let limit: Option<usize>  = limit_arg(m); 
                     let strides  = match m.get_one::<usize>("strides"){Some(O) => Some( *O ),None => None};
-                    residual_disp_skew_csv(  models, output, index, offset, limit, strides, partial_model::new(0, 0));
+                    residual_disp_skew_csv(  models, output, index, offset, limit, strides, partial_model::new(0, 0), window_arg(m)?)?;
+                },
+                Some(("hub", m )) => {
+                    let models : String = model_arg(m)?;
+                    let output : String = m.get_one::<String>("OUTPUT").expect( "OUTPUT file not specified").clone();
+                    let index : usize = m.get_one::<usize>("index" ).copied().unwrap_or(0);
+                    let hub = HubSpec {
+                        geo_value: m.get_one::<String>("geo").expect( "geo not specified").clone(),
+                        forecaster: m.get_one::<String>("forecaster").expect( "forecaster has a default").clone(),
+                        data_source: m.get_one::<String>("data_source").expect( "data source has a default").clone(),
+                        signal: m.get_one::<String>("signal").cloned(),
+                        ahead: *m.get_one::<usize>("ahead").expect( "ahead has a default"),
+                        cumulative: m.get_flag("cumulative"),
+                    };
+                    hub_csv( models, output, index, hub, quantiles_arg(m)?, ensemble_arg(m)? )?;
                 },
                 _ => {}
             }
@@ -177,21 +245,205 @@ fn main() {
             match m.subcommand(){
                 Some(("basic", m )) => 
                 {
-                    let models : String = m.get_one::<String>("MODEL").expect( "MODEL file not specified").clone();
+                    let models : String = model_arg(m)?;
                     let top : usize = m.get_one::<usize>("top" ).expect("parsing top issue").clone();
-                    basic_explanation( models, top );
+                    basic_explanation( models, top )?;
                 },
                 Some(("intermediate", m )) => {
-                    let models : String = m.get_one::<String>("MODEL").expect( "MODEL file not specified").clone();
+                    let models : String = model_arg(m)?;
                     let top : usize = m.get_one::<usize>("top" ).expect("parsing top issue").clone();
                     let pval : f64 = m.get_one::<f64>("pval" ).expect("parsing pval issue").clone();
-                    intermediate_explanation( models, top, pval );
+                    intermediate_explanation( models, top, pval )?;
+                }
+                Some(("diagnostics", m )) => {
+                    let models : String = model_arg(m)?;
+                    let top : Option<usize> = m.get_one::<usize>("top" ).copied();
+                    let alpha : f64 = *m.get_one::<f64>("alpha" ).expect("alpha has a default");
+                    diagnostics_explanation( models, top, alpha, m.get_one::<usize>("lags" ).copied() )?;
                 }
                 _ => {}
             }
         } 
+        Some( ("run", m )) => {
+            let job : String = m.get_one::<String>("JOB").expect( "JOB file not specified").clone();
+            run_job( &job )?;
+        }
+        Some( ("migrate", m )) => {
+            let models : Vec<String> = m.get_many::<String>("MODELS").expect( "MODELS not specified").cloned().collect();
+            migrate_packages( &models, m.get_one::<String>("out_dir"), m.get_one::<String>("input"), m.get_one::<String>("col"), m.get_flag("check") )?;
+        }
+        Some( ("score", m )) => {
+            let output : String = m.get_one::<String>("OUTPUT").expect( "OUTPUT not specified").clone();
+            let forecasts : Vec<String> = m.get_many::<String>("FORECASTS").expect( "FORECASTS not specified").cloned().collect();
+            let truth = read_truth( m.get_one::<String>("truth").expect( "truth not specified"),
+                m.get_one::<String>("truth_date").map( |x| x.as_str() ),
+                m.get_one::<String>("truth_column").expect( "truth column has a default"),
+                m.get_flag("truth_diff") )?;
+            score_files( &forecasts, m.get_one::<String>("forecaster").expect( "forecaster has a default"), &truth, &output )?;
+        }
+        Some( ("backtest", m )) => {
+            let input : String = m.get_one::<String>("INPUT").expect("input file required").clone();
+            let output : String = m.get_one::<String>("OUTPUT").expect("output file required").clone();
+            let humps : usize = *m.get_one::<usize>("humps" ).expect("humps has a default");
+            let samples : usize = *m.get_one::<usize>("samples" ).expect("samples has a default");
+            let offset : Option<i64> = m.get_one::<i64>("offset").copied();
+            let strides : Option<usize> = m.get_one::<usize>("strides").copied();
+            let data_column = m.get_one::<String>("col").expect("data column needed" );
+            let mut md = data_fit_load_metadata::new( input, data_slice{ offset, limit: None, strides }, data_column.clone() );
+            md.time = match m.get_one::<String>("date_col") {
+                Some(column) => Some( TimeAxis::new( column.clone(), m.get_one::<String>("time_unit").expect("time unit has a default").parse()?, m.get_one::<String>("origin").cloned() )),
+                None => None,
+            };
+            md.transform = m.get_one::<String>("transform").map( |x| x.parse() ).transpose()?;
+            md.format = m.get_one::<String>("format").map( |x| x.parse() ).transpose()?;
+            let origins = RollingOrigins{
+                first: *m.get_one::<usize>("first").expect("first origin required"),
+                every: *m.get_one::<usize>("every").expect("every has a default"),
+                last: m.get_one::<usize>("last").copied(),
+            };
+            let window : usize = *m.get_one::<usize>("window").expect("window has a default");
+            let baselines = [ Baseline::Persistence, Baseline::Linear( window ), Baseline::Drift ];
+            let config = FitConfig::default().humps( humps ).samples( samples );
+            backtest( md, &config, &origins, *m.get_one::<usize>("ahead").expect("ahead has a default"), &baselines, &output, m.get_one::<String>("plot").map( |x| x.as_str() ) )?;
+        }
         _ => {}, 
     }
+    Ok(())
+}
+
+/// the MODEL argument, or the package of `--group` when MODEL is a group index.
+fn model_arg(m: &clap::ArgMatches) -> Result<String, HumptyError> {
+    let models = m.get_one::<String>("MODEL").expect( "MODEL file not specified").clone();
+    match m.get_one::<String>("group") {
+        Some(group) => group_package( &models, group ),
+        None => Ok( models ),
+    }
+}
+
+fn group_arg() -> Arg {
+    Arg::new( "group" )
+    .long("group")
+    .long_help("MODEL is a group index written by fit --group-by, use the package of this group" )
+    .value_parser( value_parser!(String))
+}
+
+/// `--quantiles` with its `--spread`, absent for a point forecast.
+fn quantiles_arg(m: &clap::ArgMatches) -> Result<Option<ForecastQuantiles>, HumptyError> {
+    let Some(levels) = m.get_one::<String>("quantiles") else { return Ok( None ) };
+    Ok( Some( ForecastQuantiles {
+        levels: parse_quantile_levels( levels )?,
+        spread: m.get_one::<String>("spread").map( |x| x.parse() ).transpose()?.unwrap_or_default(),
+    }))
+}
+
+fn quantile_args() -> [Arg; 2] {
+    [
+        Arg::new( "quantiles" )
+        .short('q')
+        .long("quantiles")
+        .long_help("write quantile forecasts at these comma separated levels (hub for the 23 forecast hub levels), one row per time and level" )
+        .value_parser( value_parser!(String)),
+        Arg::new( "spread" )
+        .long("spread")
+        .long_help("residual: quantiles of the fit residuals, widened by sqrt(steps ahead) past the fitted data; ensemble: weighted quantiles over the --members top fits, weighted as for --ensemble" )
+        .default_value( "residual" )
+        .value_parser( ["residual", "ensemble"] ),
+    ]
+}
+
+/// `--ensemble` and `--members`, either one asks for the weighted ensemble of the top fits.
+fn ensemble_arg(m: &clap::ArgMatches) -> Result<Option<ForecastEnsemble>, HumptyError> {
+    let members = m.get_one::<usize>("members").copied();
+    match m.get_one::<String>("ensemble") {
+        Some(weights) => Ok( Some( ForecastEnsemble { weights: weights.parse()?, members } ) ),
+        None => Ok( members.map( |members| ForecastEnsemble { members: Some( members ), ..Default::default() } ) ),
+    }
+}
+
+fn ensemble_args() -> [Arg; 2] {
+    [
+        Arg::new( "ensemble" )
+        .long("ensemble")
+        .long_help("combine the top fits into a weighted ensemble, weighted by akaike (default), --ensemble=inverse_mse or --ensemble=equal: its mean, spread and each member, or its weighted quantiles with --quantiles and --spread ensemble" )
+        .num_args(0..=1)
+        .require_equals(true)
+        .default_missing_value( "akaike" )
+        .value_parser( ["akaike", "inverse_mse", "equal"] ),
+        Arg::new( "members" )
+        .long("members")
+        .long_help("number of top fits in the ensemble (or the ensemble spread), all fits of the package by default" )
+        .value_parser( value_parser!(usize)),
+    ]
+}
+
+/// `--window` or `--halflife` of csv skew and viz skew, none keeps the expanding window alone.
+fn window_arg(m: &clap::ArgMatches) -> Result<Option<StatWindow>, HumptyError> {
+    let window = match ( m.get_one::<usize>("window"), m.get_one::<f64>("halflife") ) {
+        ( Some(w), _ ) => Some( StatWindow::Trailing( *w ) ),
+        ( None, Some(h) ) => Some( StatWindow::Exponential( *h ) ),
+        ( None, None ) => None,
+    };
+    window.as_ref().map( StatWindow::check ).transpose()?;
+    Ok( window )
+}
+
+fn window_args() -> [Arg; 2] {
+    [
+        Arg::new( "window" )
+        .long("window")
+        .long_help("running mean, sd, skew and kurtosis of the residuals over the last WINDOW rows" )
+        .conflicts_with( "halflife" )
+        .value_parser( value_parser!(usize)),
+        Arg::new( "halflife" )
+        .long("halflife")
+        .long_help("running statistics weighting each row by 2^(-k/HALFLIFE) for a row k rows back" )
+        .value_parser( value_parser!(f64)),
+    ]
+}
+
+/// detectors and their settings for csv alarms and viz residual, `AlarmConfig` defaults where not given.
+fn alarm_arg(m: &clap::ArgMatches) -> AlarmConfig {
+    let mut config = AlarmConfig::default();
+    if let Some( detectors ) = m.get_many::<Detector>("detector") {
+        config.detectors = detectors.copied().collect();
+    }
+    config.drift = m.get_one::<f64>("drift").copied().unwrap_or( config.drift );
+    config.threshold = m.get_one::<f64>("threshold").copied().unwrap_or( config.threshold );
+    config.run_length = m.get_one::<f64>("run_length").copied().unwrap_or( config.run_length );
+    config.probability = m.get_one::<f64>("cp_prob").copied().unwrap_or( config.probability );
+    config
+}
+
+fn alarm_args() -> [Arg; 5] {
+    [
+        Arg::new( "detector" )
+        .long("detector")
+        .long_help("change detectors run on the standardised residuals: cusum, page_hinkley, bocpd (comma separated, all by default)" )
+        .value_delimiter( ',' )
+        .action( ArgAction::Append )
+        .value_parser( |s : &str| s.parse::<Detector>() ),
+        Arg::new( "drift" )
+        .long("drift")
+        .long_help("allowance of cusum and page_hinkley in residual standard deviations [default: 0.5]" )
+        .value_parser( value_parser!(f64)),
+        Arg::new( "threshold" )
+        .long("threshold")
+        .long_help("alarm threshold of cusum and page_hinkley in residual standard deviations [default: 5]" )
+        .value_parser( value_parser!(f64)),
+        Arg::new( "run_length" )
+        .long("run-length")
+        .long_help("expected rows between changes for bocpd, the hazard is its inverse [default: 250]" )
+        .value_parser( value_parser!(f64)),
+        Arg::new( "cp_prob" )
+        .long("cp-prob")
+        .long_help("bocpd alarms when the probability of a change in the last 10 rows exceeds this [default: 0.5]" )
+        .value_parser( value_parser!(f64)),
+    ]
+}
+
+/// the default limit (usize::MAX) stands for no limit.
+fn limit_arg(m: &clap::ArgMatches) -> Option<usize> {
+    m.get_one::<usize>("limit").copied().filter(|l| *l != usize::MAX)
 }
 
 
@@ -282,8 +534,107 @@ fn cli_model_fit( ) -> Command {
         .default_value( "1" )
         .value_parser( value_parser!(usize))
     )
+    .arg(
+        Arg::new( "keep_failed" )
+        .long("keep-failed")
+        .long_help("keep restarts where the solver failed or diverged when ranking the reported fits")
+        .action( ArgAction::SetTrue )
+    )
+    .arg(
+        Arg::new( "date_col" )
+        .long("date-col")
+        .long_help("date or datetime column mapped to the time axis, by default time is the row index" )
+        .value_parser( value_parser!(String))
+    )
+    .arg(
+        Arg::new( "time_unit" )
+        .long("time-unit")
+        .long_help("unit of the time axis for --date-col" )
+        .default_value( "day" )
+        .value_parser( ["second", "minute", "hour", "day", "week"] )
+    )
+    .arg(
+        Arg::new( "origin" )
+        .long("origin")
+        .long_help("date at t = 0 for --date-col (YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS), the first date by default" )
+        .value_parser( value_parser!(String))
+    )
+    .arg(
+        Arg::new( "resample" )
+        .long("resample")
+        .long_help("aggregate the rows to calendar weeks or months (needs a date column), each period is labelled by its first day" )
+        .value_parser( ["week", "month"] )
+    )
+    .arg(
+        Arg::new( "anchor" )
+        .long("anchor")
+        .long_help("first day of each period for --resample: a weekday (mon .. sun, default mon) for weeks, a day of the month (1 .. 28, default 1) for months" )
+        .value_parser( value_parser!(String))
+    )
+    .arg(
+        Arg::new( "agg" )
+        .long("agg")
+        .long_help("how the rows of a period are combined for --resample, missing values are skipped" )
+        .default_value( "sum" )
+        .value_parser( ["sum", "last", "mean"] )
+    )
+    .arg(
+        Arg::new( "keep_partial" )
+        .long("keep-partial")
+        .long_help("keep the first and last periods of --resample even when the data does not cover them fully" )
+        .action( ArgAction::SetTrue )
+    )
+    .arg(
+        Arg::new( "diff" )
+        .long("diff")
+        .long_help("difference the series (after --resample), e.g. cumulative counts to incidence" )
+        .action( ArgAction::SetTrue )
+    )
+    .arg(
+        Arg::new( "cumulate" )
+        .long("cumulate")
+        .long_help("running sum of the series (after --resample and --diff), e.g. incidence to cumulative counts" )
+        .action( ArgAction::SetTrue )
+    )
+    .arg(
+        Arg::new( "embed" )
+        .long("embed")
+        .long_help("store the prepared series in the package, viz, csv and exp then use it instead of reading INPUT again (always on for INPUT -)" )
+        .action( ArgAction::SetTrue )
+    )
+    .arg(
+        Arg::new( "format" )
+        .long("format")
+        .long_help("format of INPUT, by default detected from the extension or the first bytes (INPUT - reads stdin)" )
+        .value_parser( ["csv", "json", "ndjson", "parquet"] )
+    )
+    .arg(
+        Arg::new( "transform" )
+        .long("transform")
+        .long_help("fit in a transformed space (unit range, z-score or log(1 + y)), parameters come from the training slice and every output is mapped back to data units" )
+        .value_parser( ["unit", "zscore", "log1p"] )
+    )
+    .arg(
+        Arg::new( "group_by" )
+        .long("group-by")
+        .long_help("fit one model per distinct value of this column (long tables such as county,state,date,count), writing OUTPUT_<group>.yml and the index OUTPUT_groups.yml" )
+        .value_parser( value_parser!(String))
+    )
+    .arg(
+        Arg::new( "wide" )
+        .long("wide")
+        .long_help("the input has one row per region and one column per date (JHU / USAFacts), the selected rows are summed into the data column and the dates become the `date` time axis" )
+        .action( ArgAction::SetTrue )
+    )
+    .arg(
+        Arg::new( "select" )
+        .long("select")
+        .long_help("KEY=VALUE row selection for --wide (e.g. Admin2=Alameda), repeat to combine, all rows are summed when absent" )
+        .action( ArgAction::Append )
+        .value_parser( value_parser!(String))
+    )
     .arg(arg!(<OUTPUT> "A serialized model file"))
-    .arg(arg!(<INPUT> "data to consider, .. should be a list of csv files with headers" ))
+    .arg(arg!(<INPUT> "data to consider: a csv file with headers, json, json lines or parquet, - for stdin" ))
     .arg_required_else_help(true)
 }
 
@@ -297,6 +648,7 @@ fn cli_model_viz( ) -> Command {
     .subcommand(
         Command::new("basic")
         .about( "construct basic plots.")
+        .args(ensemble_args())
         .arg(                     
             Arg::new( "top" )
             .short( 't')
@@ -349,6 +701,7 @@ fn cli_model_viz( ) -> Command {
         )
         .arg(arg!(<OUTPUT> "A png file"))
         //.arg(arg!(<DATA> "data to consider, .. should be a list of csv files with headers" ))
+        .arg(group_arg())
         .arg(arg!(<MODEL> "model file, .. such as that generated in the fit procedure "))
         .arg_required_else_help(true)
     )
@@ -415,6 +768,7 @@ fn cli_model_viz( ) -> Command {
         )
         .arg(arg!(<OUTPUT> "A png file"))
         //.arg(arg!(<DATA> "data to consider, .. should be a list of csv files with headers" ))
+        .arg(group_arg())
         .arg(arg!(<MODEL> "model file, .. such as that generated in the fit procedure "))
         .arg_required_else_help(true)
     )
@@ -471,8 +825,16 @@ fn cli_model_viz( ) -> Command {
             .default_value( None )
             .value_parser( value_parser!( String ))
         )
+        .arg(
+            Arg::new( "alarms" )
+            .long("alarms")
+            .long_help("mark the alarms of the change detectors on the residuals with vertical lines, see csv alarms" )
+            .action( ArgAction::SetTrue )
+        )
+        .args(alarm_args())
         .arg(arg!(<OUTPUT> "A png file"))
         //.arg(arg!(<DATA> "data to consider, .. should be a list of csv files with headers" ))
+        .arg(group_arg())
         .arg(arg!(<MODEL> "model file, .. such as that generated in the fit procedure "))
         .arg_required_else_help(true)
     )
@@ -531,12 +893,21 @@ fn cli_model_viz( ) -> Command {
         )
         .arg(arg!(<OUTPUT> "A png file"))
         //.arg(arg!(<DATA> "data to consider, .. should be a list of csv files with headers" ))
+        .arg(group_arg())
         .arg(arg!(<MODEL> "model file, .. such as that generated in the fit procedure "))
         .arg_required_else_help(true)
     )
     .subcommand(
         Command::new("skew")
         .about( "construct basic plots with rolling quantile for skewness.")
+        .args(window_args())
+        .arg(
+            Arg::new( "stat" )
+            .long("stat")
+            .long_help("running statistic to plot" )
+            .default_value( "skew" )
+            .value_parser( ["mean", "sd", "skew", "kurtosis"] )
+        )
         .arg(                     
             Arg::new( "index" )
             .short( 'i')
@@ -590,6 +961,7 @@ fn cli_model_viz( ) -> Command {
 
         .arg(arg!(<OUTPUT> "A png file"))
         //.arg(arg!(<DATA> "data to consider, .. should be a list of csv files with headers" ))
+        .arg(group_arg())
         .arg(arg!(<MODEL> "model file, .. such as that generated in the fit procedure "))
         .arg_required_else_help(true)
     )
@@ -634,8 +1006,92 @@ fn cli_model_csv( ) -> Command {
             .default_value( "1")
             .value_parser( value_parser!( usize ))
         )
+        .args(quantile_args())
+        .args(ensemble_args())
         .arg(arg!(<OUTPUT> "A csv file"))
         //.arg(arg!(<DATA> "data to consider, .. should be a list of csv files with headers" ))
+        .arg(group_arg())
+        .arg(arg!(<MODEL> "model file, .. such as that generated in the fit procedure "))
+        .arg_required_else_help(true)
+    )
+    .subcommand(
+        Command::new("components")
+        .about( "drop a csv file with each hump of the model (ordered by t0), the baseline and their sum; a limit past the data forecasts ahead.")
+        .arg(
+            Arg::new( "index" )
+            .short( 'i')
+            .long_help("select this model from the input model file" )
+            .long("index")
+            .default_value("0" )
+            .value_parser(value_parser!(usize))
+        )
+        .arg(
+            Arg::new( "offset" )
+            .short( 'o' )
+            .long( "offset" )
+            .default_value( "0")
+            .value_parser( value_parser!( i64 ))
+        )
+        .arg(
+            Arg::new( "limit" )
+            .short('l')
+            .long("limit")
+            .default_value( "18446744073709551615")
+            .value_parser( value_parser!( usize ))
+        )
+        .arg(
+            Arg::new( "strides" )
+            .short('x')
+            .long("strides")
+            .default_value( "1")
+            .value_parser( value_parser!( usize ))
+        )
+        .arg(
+            Arg::new( "incidence" )
+            .long("incidence")
+            .long_help("also write the derivative of each hump and of the sum (incidence per unit of time)" )
+            .action( ArgAction::SetTrue )
+        )
+        .arg(arg!(<OUTPUT> "A csv file"))
+        .arg(group_arg())
+        .arg(arg!(<MODEL> "model file, .. such as that generated in the fit procedure "))
+        .arg_required_else_help(true)
+    )
+    .subcommand(
+        Command::new("alarms")
+        .about( "drop a csv file with the alarms of sequential change detectors (cusum, page_hinkley, bocpd) on the standardised residuals.")
+        .arg(
+            Arg::new( "index" )
+            .short( 'i')
+            .long_help("select this model from the input model file" )
+            .long("index")
+            .default_value("0" )
+            .value_parser(value_parser!(usize))
+        )
+        .arg(
+            Arg::new( "offset" )
+            .short( 'o' )
+            .long( "offset" )
+            .default_value( "0")
+            .value_parser( value_parser!( i64 ))
+        )
+        .arg(
+            Arg::new( "limit" )
+            .short('l')
+            .long("limit")
+            .default_value( "18446744073709551615")
+            .value_parser( value_parser!( usize ))
+        )
+        .arg(
+            Arg::new( "strides" )
+            .short('x')
+            .long("strides")
+            .default_value( "1")
+            .value_parser( value_parser!( usize ))
+        )
+        .args(alarm_args())
+        .arg(arg!(<OUTPUT> "A csv file"))
+        .arg(group_arg())
         .arg(arg!(<MODEL> "model file, .. such as that generated in the fit procedure "))
         .arg_required_else_help(true)
     )
@@ -673,6 +1129,7 @@ fn cli_model_csv( ) -> Command {
         )
         .arg(arg!(<OUTPUT> "A csv file"))
         //.arg(arg!(<DATA> "data to consider, .. should be a list of csv files with headers" ))
+        .arg(group_arg())
         .arg(arg!(<MODEL> "model file, .. such as that generated in the fit procedure "))
         .arg_required_else_help(true)
     )
@@ -710,12 +1167,14 @@ fn cli_model_csv( ) -> Command {
         )
         .arg(arg!(<OUTPUT> "A csv file"))
         //.arg(arg!(<DATA> "data to consider, .. should be a list of csv files with headers" ))
+        .arg(group_arg())
         .arg(arg!(<MODEL> "model file, .. such as that generated in the fit procedure "))
         .arg_required_else_help(true)
     )
     .subcommand(
         Command::new("skew")
         .about( "construct csv file with rolling quantile for skewness.")
+        .args(window_args())
         .arg(                     
             Arg::new( "index" )
             .short( 'i')
@@ -747,6 +1206,62 @@ fn cli_model_csv( ) -> Command {
         )
         .arg(arg!(<OUTPUT> "A png file"))
         //.arg(arg!(<DATA> "data to consider, .. should be a list of csv files with headers" ))
+        .arg(group_arg())
+        .arg(arg!(<MODEL> "model file, .. such as that generated in the fit procedure "))
+        .arg_required_else_help(true)
+    )    .subcommand(
+        Command::new("hub")
+        .about( "write forecast hub rows (ahead, geo_value, quantile, value, forecaster, forecast_date, data_source, signal, target_end_date, incidence_period) for the steps past the fitted data.")
+        .arg(                     
+            Arg::new( "index" )
+            .short( 'i')
+            .long_help("select this model from the input model file" )
+            .long("index")
+            .default_value("0" )
+            .value_parser(value_parser!(usize))
+        ) 
+        .arg(
+            Arg::new( "geo" )
+            .long("geo")
+            .long_help("geo_value of the rows, e.g. ca" )
+            .required(true)
+            .value_parser( value_parser!(String))
+        )
+        .arg(
+            Arg::new( "forecaster" )
+            .long("forecaster")
+            .default_value( "humpty" )
+            .value_parser( value_parser!(String))
+        )
+        .arg(
+            Arg::new( "data_source" )
+            .long("data-source")
+            .default_value( "jhu-csse" )
+            .value_parser( value_parser!(String))
+        )
+        .arg(
+            Arg::new( "signal" )
+            .long("signal")
+            .long_help("signal of the rows, confirmed_incidence_num (or confirmed_cumulative_num with --cumulative) by default" )
+            .value_parser( value_parser!(String))
+        )
+        .arg(
+            Arg::new( "ahead" )
+            .long("ahead")
+            .long_help("number of steps forecast past the fitted data" )
+            .default_value( "28" )
+            .value_parser( value_parser!(usize))
+        )
+        .arg(
+            Arg::new( "cumulative" )
+            .long("cumulative")
+            .long_help("write cumulative counts instead of incidence" )
+            .action( ArgAction::SetTrue )
+        )
+        .args(quantile_args())
+        .args(ensemble_args())
+        .arg(arg!(<OUTPUT> "A csv file"))
+        .arg(group_arg())
         .arg(arg!(<MODEL> "model file, .. such as that generated in the fit procedure "))
         .arg_required_else_help(true)
     )
@@ -770,6 +1285,7 @@ fn cli_model_exp( ) -> Command {
             .default_value("1" )
             .value_parser(value_parser!(usize))
         ) 
+        .arg(group_arg())
         .arg(arg!(<MODEL> "model file, .. such as that generated in the fit procedure "))
         .arg_required_else_help(true)
     )
@@ -792,11 +1308,233 @@ fn cli_model_exp( ) -> Command {
             .default_value("0.02" )
             .value_parser(value_parser!(f64))
         ) 
+        .arg(group_arg())
+        .arg(arg!(<MODEL> "model file, .. such as that generated in the fit procedure "))
+        .arg_required_else_help(true)
+    )
+    .subcommand(
+        Command::new("diagnostics")
+        .about( "test the residuals of the recovered models for autocorrelation, non-normality and heteroscedasticity.")
+        .arg(
+            Arg::new( "top" )
+            .short( 't')
+            .long_help("test this many models from the input model file, all of them by default" )
+            .long("top")
+            .value_parser(value_parser!(usize))
+        )
+        .arg(
+            Arg::new( "alpha" )
+            .short( 'a')
+            .long_help("significance level of the whole set of tests, a test fails the model when its p-value is below alpha divided by the number of tests (Bonferroni)" )
+            .long("alpha")
+            .default_value("0.05" )
+            .value_parser(value_parser!(f64))
+        )
+        .arg(
+            Arg::new( "lags" )
+            .long_help("autocorrelation lags of the Ljung-Box test, min(10, n/5) by default" )
+            .long("lags")
+            .value_parser(value_parser!(usize))
+        )
+        .arg(group_arg())
         .arg(arg!(<MODEL> "model file, .. such as that generated in the fit procedure "))
         .arg_required_else_help(true)
     )
 }
 
+fn cli_run_job( ) -> Command {
+    Command::new("run")
+    .about( "fit and render everything described in a yaml job file")
+    .arg(arg!(<JOB> "job file, .. input, column, slice, model and artifacts"))
+    .arg_required_else_help(true)
+}
+
+fn cli_migrate( ) -> Command {
+    Command::new("migrate")
+    .about( "rewrite fit packages of older layouts (e.g. lists of [residual, model] tuples) in the current layout")
+    .arg(
+        Arg::new( "out_dir" )
+        .long("out-dir")
+        .long_help("write the migrated packages into this directory instead of in place" )
+        .value_parser( value_parser!(String))
+    )
+    .arg(
+        Arg::new( "input" )
+        .long("input")
+        .long_help("data file to record in the packages, the oldest layout names none" )
+        .value_parser( value_parser!(String))
+    )
+    .arg(
+        Arg::new( "col" )
+        .short('d')
+        .long("datacolumn")
+        .long_help("data column to record with --input" )
+        .value_parser( value_parser!(String))
+    )
+    .arg(
+        Arg::new( "check" )
+        .long("check")
+        .long_help("only report the version of each package" )
+        .action( ArgAction::SetTrue )
+    )
+    .arg(arg!(<MODELS> ... "fit packages (.yml)"))
+    .arg_required_else_help(true)
+}
+
+fn cli_score( ) -> Command {
+    Command::new("score")
+    .about( "score quantile forecasts (forecast hub rows or csv fore -q output) against the truth: MSE, MAE, WIS, CRPS and interval coverage per horizon and per forecast date")
+    .arg(
+        Arg::new( "truth" )
+        .long("truth")
+        .long_help("data file with the observed series" )
+        .required(true)
+        .value_parser( value_parser!(String))
+    )
+    .arg(
+        Arg::new( "truth_column" )
+        .short('d')
+        .long("truth-column")
+        .default_value( "count" )
+        .value_parser( value_parser!(String))
+    )
+    .arg(
+        Arg::new( "truth_date" )
+        .long("truth-date")
+        .long_help("date column of the truth, matched against target_end_date; without it targets are row times" )
+        .value_parser( value_parser!(String))
+    )
+    .arg(
+        Arg::new( "truth_diff" )
+        .long("truth-diff")
+        .long_help("the truth is cumulative and the forecasts are incidence, score against its differences" )
+        .action( ArgAction::SetTrue )
+    )
+    .arg(
+        Arg::new( "forecaster" )
+        .long("forecaster")
+        .long_help("forecaster name of csv fore files (hub files carry their own)" )
+        .default_value( "humpty" )
+        .value_parser( value_parser!(String))
+    )
+    .arg(arg!(<OUTPUT> "prefix of the score tables: OUTPUT_scores.csv, OUTPUT_by_horizon.csv, OUTPUT_by_origin.csv"))
+    .arg(arg!(<FORECASTS> ... "forecast csv files"))
+    .arg_required_else_help(true)
+}
+fn cli_backtest( ) -> Command {
+    Command::new("backtest")
+    .about( "rolling-origin backtest: refit humpty at each origin and compare its forecasts to persistence, linear and drift baselines per horizon")
+    .arg(
+        Arg::new( "humps" )
+        .short( 'n')
+        .long("humps")
+        .default_value("3" )
+        .value_parser(value_parser!(usize))
+    )
+    .arg(
+        Arg::new( "samples" )
+        .short( 's')
+        .long("samples")
+        .default_value("1000")
+        .value_parser(value_parser!(usize))
+    )
+    .arg(
+        Arg::new( "col" )
+        .short('d')
+        .long("datacolumn")
+        .default_value( "count")
+        .value_parser( value_parser!( String ))
+    )
+    .arg(
+        Arg::new( "offset" )
+        .short( 'o' )
+        .long( "offset" )
+        .default_value( "0")
+        .value_parser( value_parser!( i64 ))
+    )
+    .arg(
+        Arg::new( "strides" )
+        .short('x')
+        .long("strides")
+        .default_value( "1")
+        .value_parser( value_parser!( usize ))
+    )
+    .arg(
+        Arg::new( "first" )
+        .long("first")
+        .long_help("limit (observations from the offset, as fit -l) of the first origin" )
+        .required(true)
+        .value_parser( value_parser!( usize ))
+    )
+    .arg(
+        Arg::new( "every" )
+        .long("every")
+        .long_help("observations between origins" )
+        .default_value( "7")
+        .value_parser( value_parser!( usize ))
+    )
+    .arg(
+        Arg::new( "last" )
+        .long("last")
+        .long_help("limit of the last origin, by default the last observation but one" )
+        .value_parser( value_parser!( usize ))
+    )
+    .arg(
+        Arg::new( "ahead" )
+        .long("ahead")
+        .long_help("rows forecast past each origin (in strides)" )
+        .default_value( "14")
+        .value_parser( value_parser!( usize ))
+    )
+    .arg(
+        Arg::new( "window" )
+        .short('k')
+        .long("window")
+        .long_help("observations the linear baseline is fitted to" )
+        .default_value( "7")
+        .value_parser( value_parser!( usize ))
+    )
+    .arg(
+        Arg::new( "date_col" )
+        .long("date-col")
+        .long_help("date or datetime column mapped to the time axis, by default time is the row index" )
+        .value_parser( value_parser!(String))
+    )
+    .arg(
+        Arg::new( "time_unit" )
+        .long("time-unit")
+        .long_help("unit of the time axis for --date-col" )
+        .default_value( "day" )
+        .value_parser( ["second", "minute", "hour", "day", "week"] )
+    )
+    .arg(
+        Arg::new( "origin" )
+        .long("origin")
+        .long_help("date at t = 0 for --date-col (YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS), the first date by default" )
+        .value_parser( value_parser!(String))
+    )
+    .arg(
+        Arg::new( "format" )
+        .long("format")
+        .long_help("format of INPUT, by default detected from the extension or the first bytes" )
+        .value_parser( ["csv", "json", "ndjson", "parquet"] )
+    )
+    .arg(
+        Arg::new( "transform" )
+        .long("transform")
+        .long_help("fit humpty in a transformed space, see fit --transform" )
+        .value_parser( ["unit", "zscore", "log1p"] )
+    )
+    .arg(
+        Arg::new( "plot" )
+        .long("plot")
+        .long_help("also plot the relative MAE per horizon as OUTPUT_skill.png or OUTPUT_skill.html, png needs an image renderer" )
+        .value_parser( ["png", "html"] )
+    )
+    .arg(arg!(<OUTPUT> "prefix of the report: OUTPUT_scores.csv, OUTPUT_by_horizon.csv, OUTPUT_skill.csv and the optional skill plot"))
+    .arg(arg!(<INPUT> "data to consider: a csv file with headers, json, json lines or parquet" ))
+    .arg_required_else_help(true)
+}
 fn cli() -> Command {
     Command::new("top-level")
         .about("CLI")
@@ -818,6 +1556,18 @@ fn cli() -> Command {
         .subcommand(
             cli_model_exp()
         )
+        .subcommand(
+            cli_run_job()
+        )
+        .subcommand(
+            cli_migrate()
+        )
+        .subcommand(
+            cli_score()
+        )
+        .subcommand(
+            cli_backtest()
+        )
 
 }
 
@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::PathBuf;
+
+use csv::Writer;
+use serde::Serialize;
+
+use crate::error::{HumptyError, Result};
+use crate::api::FitConfig;
+use crate::main_mod_dm_fit::{data_fit_load_metadata, data_slice, fit_at, fit_frame, load_frame, TimeAxis};
+use crate::main_mod_dm_score::{ForecastRecord, score_forecasts, ScoreSummary, summarize_by, write_scores, write_summaries};
+use crate::models::ParameterizedModel;
+use crate::viz_lib2::{plot_lines, PlotAction};
+
+////////////////
+///
+/// naive forecasters humpty is compared against, each sees only the training slice (missing values skipped):
+///     persistence :  y(t) = y_n
+///     linear(k) :    least squares line through the last k observations, extrapolated
+///     drift :        y(t) = y_n + ( t - t_n ) ( y_n - y_1 ) / ( t_n - t_1 )
+/// times are in the units of the time axis, so strided or irregular series extrapolate by elapsed time.
+#[derive(Debug, Clone, Copy, PartialEq )]
+pub enum Baseline {
+    Persistence,
+    Linear( usize ),
+    Drift,
+}
+
+impl Baseline {
+    pub fn name( &self ) -> String {
+        match self {
+            Baseline::Persistence => String::from( "persistence" ),
+            Baseline::Linear( k ) => format!( "linear_{k}" ),
+            Baseline::Drift => String::from( "drift" ),
+        }
+    }
+
+    /// the forecast at `at` from the training series, NaN when there are too few observations.
+    pub fn forecast( &self, time : &[f64], value : &[f64], at : &[f64] ) -> Vec<f64> {
+        let ( t, y ) : (Vec<f64>, Vec<f64>) = time.iter().zip( value.iter() ).filter( |(t, y)| t.is_finite() && y.is_finite() ).map( |(t, y)| ( *t, *y ) ).unzip();
+        let n = y.len();
+        let line = match self {
+            _ if n == 0 => None,
+            Baseline::Persistence => Some( ( y[n-1], 0. ) ),
+            Baseline::Linear( k ) => least_squares_line( &t[n.saturating_sub( *k )..], &y[n.saturating_sub( *k )..] ),
+            Baseline::Drift if n < 2 || t[n-1] == t[0] => None,
+            Baseline::Drift => Some( ( y[n-1], ( y[n-1] - y[0] ) / ( t[n-1] - t[0] ) ) ),
+        };
+        // ( value at t_n, slope ).
+        match line {
+            Some( ( level, slope ) ) => at.iter().map( |x| level + slope * ( x - t[n-1] ) ).collect(),
+            None => vec![ f64::NAN; at.len() ],
+        }
+    }
+}
+
+/// ( value at the last time, slope ) of the least squares line, None for fewer than two distinct times.
+fn least_squares_line( t : &[f64], y : &[f64] ) -> Option<(f64, f64)> {
+    let n = t.len() as f64;
+    let tm = t.iter().sum::<f64>() / n;
+    let ym = y.iter().sum::<f64>() / n;
+    let stt : f64 = t.iter().map( |x| ( x - tm ) * ( x - tm ) ).sum();
+    if t.len() < 2 || stt <= 0. {
+        return None
+    }
+    let slope = t.iter().zip( y.iter() ).map( |(x, v)| ( x - tm ) * ( v - ym ) ).sum::<f64>() / stt;
+    Some( ( ym + slope * ( t[t.len()-1] - tm ), slope ) )
+}
+
+/// relative skill of `reference` against one baseline at one horizon (`all` pools the horizons):
+/// relative_mae = MAE(reference) / MAE(baseline), below 1 the reference is better;
+/// mse_skill = 1 - MSE(reference) / MSE(baseline), above 0 the reference is better.
+#[derive(Debug, Serialize, Clone )]
+pub struct SkillRow {
+    pub baseline : String,
+    pub horizon : String,
+    pub n : usize,
+    pub mae_reference : f64,
+    pub mae_baseline : f64,
+    pub relative_mae : f64,
+    pub mse_skill : f64,
+}
+
+/// skill rows of `reference` against every other forecaster of the summaries, for the keys both have.
+pub fn relative_skill<K : Ord + Clone + ToString>( summaries : &BTreeMap<(String, K), ScoreSummary>, reference : &str ) -> Vec<SkillRow> {
+    summaries.iter().filter( |((f, _), _)| f != reference ).filter_map( |((f, key), b)| {
+        let r = summaries.get( &( reference.to_string(), key.clone() ) )?;
+        Some( SkillRow {
+            baseline: f.clone(),
+            horizon: key.to_string(),
+            n: r.n.min( b.n ),
+            mae_reference: r.mae,
+            mae_baseline: b.mae,
+            relative_mae: r.mae / b.mae,
+            mse_skill: 1. - r.mse / b.mse,
+        })
+    }).collect()
+}
+
+/// rolling origins, given as slice limits (observations counted from the offset as in `load_data`): every
+/// `every` observations from `first` up to `last`, by default the last observation but one.
+#[derive(Debug, Clone )]
+pub struct RollingOrigins {
+    pub first : usize,
+    pub every : usize,
+    pub last : Option<usize>,
+}
+
+impl RollingOrigins {
+    /// the limits for a series with `n` observations from the offset.
+    pub fn limits( &self, n : usize ) -> Result<Vec<usize>> {
+        let last = self.last.unwrap_or( n.saturating_sub( 1 ) );
+        if self.every == 0 {
+            return Err( HumptyError::UnsupportedSlice( String::from( "backtest origins need --every of at least 1" )))
+        }
+        if self.first < 2 || self.first > last {
+            return Err( HumptyError::UnsupportedSlice( format!( "the first origin {} is not between 2 and the last {last}", self.first )))
+        }
+        Ok( ( self.first..=last ).step_by( self.every ).collect() )
+    }
+}
+
+////////////////
+///
+/// rolling-origin backtest: for each origin the slice of `md` (offset and strides as in `load_data`) is cut to
+/// its limit, humpty is refitted on it and the best fit and the baselines forecast the next `ahead`
+/// rows of the same stride.  Targets past the end of the data are not scored.  As for forecast hub files the
+/// origin is named by its last training observation and horizon 1 is the first step.  Written next to `output`:
+///     <output>_scores.csv       every forecast scored against the data
+///     <output>_by_horizon.csv   mean scores per forecaster and horizon
+///     <output>_skill.csv        relative MAE and MSE skill of humpty against each baseline per horizon
+///     <output>_skill.png        relative MAE per horizon, only with `plot` "png" (.html with "html")
+pub fn backtest( mut md : data_fit_load_metadata, config : &FitConfig, origins : &RollingOrigins, ahead : usize, baselines : &[Baseline], output : &str, plot : Option<&str> ) -> Result<()> {
+    let df = md.read_frame()?;
+    let data_slice{ offset, strides, .. } = md.slice.clone();
+    let stride = strides.unwrap_or( 1 ).max( 1 );
+    let start = offset.unwrap_or( 0 ).max( 0 ) as usize;
+    let mut records : Vec<ForecastRecord> = Vec::new();
+    let mut truth : BTreeMap<String, f64> = BTreeMap::new();
+    let axis = md.time.as_ref().map( |t| t.resolve( &df ) ).transpose()?;
+    let (( _, _ ), _, ( tfull, _ )) = load_frame( &df, &md.input, offset, None, None, Some( &md.colname ), axis.as_ref() )?;
+    let limits = origins.limits( tfull.len().saturating_sub( start ) )?;
+    let mut fitted = 0;
+    for limit in &limits {
+        let mut mo = md.clone();
+        mo.slice.limit = Some( *limit );
+        let pack = match fit_frame( mo, &df, config, false ) {
+            Ok( pack ) => pack,
+            Err( e ) => {
+                eprintln!( " WARNING origin at limit {limit} is skipped: {e}" );
+                continue
+            },
+        };
+        let best = &fit_at( &pack.fits, 0 )?.fitted_model;
+        let lm = &pack.load_metadata;
+        let (( _, tsplice ), dsplice, ( tfull, dfull )) = load_frame( &df, &lm.input, offset, Some( *limit ), strides, Some( &lm.colname ), lm.time.as_ref() )?;
+        let last = start + stride * ( tsplice.len() - 1 );
+        let rows : Vec<usize> = ( 1..=ahead ).map( |h| last + stride * h ).take_while( |j| *j < tfull.len() ).collect();
+        if rows.is_empty() {
+            eprintln!( " WARNING origin at limit {limit} has no observation ahead, skipped" );
+            continue
+        }
+        let key = |t : f64| key_of( lm.time.as_ref(), t );
+        let at : Vec<f64> = rows.iter().map( |j| tfull[*j] ).collect();
+        let origin = key( tfull[last] );
+        rows.iter().for_each( |j| { truth.insert( key( tfull[*j] ), dfull[*j] ); } );
+
+        let mut forecasters : Vec<(String, Vec<f64>)> = vec![ ( String::from( "humpty" ), at.iter().map( |t| best.eval( *t ) ).collect() ) ];
+        forecasters.extend( baselines.iter().map( |b| ( b.name(), b.forecast( tsplice.as_slice(), dsplice.as_slice(), &at ) ) ) );
+        for ( name, values ) in forecasters {
+            records.extend( at.iter().zip( values ).enumerate().map( |(h, (t, v))| ForecastRecord {
+                forecaster: name.clone(),
+                origin: origin.clone(),
+                target: key( *t ),
+                horizon: h as i64 + 1,
+                quantiles: vec![ ( 0.5, v ) ],
+            }));
+        }
+        println!( " origin {origin}: {} training observations, {} targets", tsplice.len(), at.len() );
+        fitted += 1;
+    }
+    if fitted == 0 {
+        return Err( HumptyError::parse( "backtest", "no origin could be fitted and scored" ))
+    }
+
+    let ( scored, _ ) = score_forecasts( &records, &truth );
+    let by_horizon = summarize_by( &scored, |s| s.record.horizon );
+    let mut skill = relative_skill( &by_horizon, "humpty" );
+    skill.extend( relative_skill( &summarize_by( &scored, |_| String::from( "all" ) ), "humpty" ) );
+
+    let mut stem = PathBuf::from( output );
+    stem.set_extension( "" );
+    let stem = stem.to_string_lossy().to_string();
+    let path = format!( "{stem}_scores.csv" );
+    write_scores( &path, &scored )?;
+    println!( "writing file {path}" );
+    let path = format!( "{stem}_by_horizon.csv" );
+    write_summaries( &path, "horizon", &by_horizon )?;
+    println!( "writing file {path}" );
+    let path = format!( "{stem}_skill.csv" );
+    let mut wtr = Writer::from_writer( File::create( &path ).map_err( |e| HumptyError::io( path.clone(), e ))? );
+    skill.iter().try_for_each( |r| wtr.serialize( r ) )?;
+    wtr.flush()?;
+    println!( "writing file {path}" );
+
+    println!( " {fitted} of {} origins, humpty against the baselines over all horizons:", limits.len() );
+    skill.iter().filter( |r| r.horizon == "all" ).for_each( |r|
+        println!( "\t{:<14} relative MAE {:.3}  MSE skill {:.3}  (n = {})", r.baseline, r.relative_mae, r.mse_skill, r.n ) );
+
+    let Some( format ) = plot else {
+        return Ok(())
+    };
+    let lines : Vec<(String, Vec<f64>, Vec<f64>)> = baselines.iter().map( |b| {
+        let rows : Vec<&SkillRow> = skill.iter().filter( |r| r.baseline == b.name() && r.horizon != "all" ).collect();
+        ( b.name(), rows.iter().map( |r| r.horizon.parse::<f64>().unwrap_or( f64::NAN ) ).collect(), rows.iter().map( |r| r.relative_mae ).collect() )
+    }).collect();
+    let ( path, act ) = if format == "html" {
+        let path = format!( "{stem}_skill.html" );
+        ( path.clone(), PlotAction::HTML( path ) )
+    } else {
+        let path = format!( "{stem}_skill.png" );
+        ( path.clone(), PlotAction::PNG( path, 800, 600, 2.0 ) )
+    };
+    plot_lines( &lines, Some( 1. ), act, String::from( "humpty MAE relative to the baselines (below 1 humpty is better)" ),
+        String::from( "horizon" ), String::from( "relative MAE" ) );
+    println!( "writing file {path}" );
+    Ok(())
+}
+
+/// the score key of a time: its date on a date axis, the number otherwise (as in `read_truth`).
+fn key_of( time : Option<&TimeAxis>, t : f64 ) -> String {
+    match time {
+        Some( axis ) => axis.date( t ),
+        None => format!( "{t}" ),
+    }
+}
+
+#[test]
+fn test_baseline_forecasts() {
+    let t = [ 0., 2., 4., 6. ];
+    let y = [ 1., 5., 5., 9. ];
+    assert_eq!( Baseline::Persistence.forecast( &t, &y, &[ 8., 10. ] ), vec![ 9., 9. ] );
+    // drift through the first and last point: slope 8 / 6.
+    assert_eq!( Baseline::Drift.forecast( &t, &y, &[ 9. ] ), vec![ 13. ] );
+    // the last two points give slope 2 per unit, the last three 1 per unit through their mean.
+    assert_eq!( Baseline::Linear( 2 ).forecast( &t, &y, &[ 8. ] ), vec![ 13. ] );
+    let l3 = Baseline::Linear( 3 ).forecast( &t, &y, &[ 8. ] );
+    assert!( ( l3[0] - ( 19. / 3. + 1. * 4. ) ).abs() < 1e-12 );
+    // missing values are skipped, too short a series gives NaN.
+    assert_eq!( Baseline::Persistence.forecast( &t, &[ 1., 2., 3., f64::NAN ], &[ 8. ] ), vec![ 3. ] );
+    assert!( Baseline::Linear( 3 ).forecast( &[ 0. ], &[ 1. ], &[ 1. ] )[0].is_nan() );
+    assert_eq!( RollingOrigins{ first: 10, every: 5, last: Some( 22 ) }.limits( 100 ).unwrap(), vec![ 10, 15, 20 ] );
+    assert_eq!( RollingOrigins{ first: 10, every: 5, last: None }.limits( 21 ).unwrap(), vec![ 10, 15, 20 ] );
+}
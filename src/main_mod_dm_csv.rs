@@ -13,11 +13,17 @@ use ndarray::ShapeBuilder;
 use polars::frame::row::Row;
 use polars::prelude::RollingQuantileParams;
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
 
+use crate::api::{self, Components, EnsembleForecast, QuantileForecast, RollingMoments, Series, Spread, StatWindow, Weighting};
+use crate::error::{HumptyError, Result};
 use crate::main_mod_dm_fit::data_fit_package;
+use crate::main_mod_dm_fit::fit_at;
 use crate::main_mod_dm_fit::model_fit;
+use crate::main_mod_dm_fit::read_fit_package;
 use crate::main_mod_dm_fit::reload_data;
-use crate::models::ModelAffine::AffineAdditive;
+use crate::resample::{Period, SeriesStep};
+use crate::stats::{Alarm, AlarmConfig};
 use crate::models::ModelTanh::ModelTanh;
 
 use crate::main_mod_dm_viz::*;
@@ -36,15 +42,23 @@ csv [forcast]           drop a csv file.
 #[derive(serde::Serialize)]
 pub struct RowOut {
     time: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
     model: f64, 
     data: f64,
 } 
 
-pub fn serialize_csv_data(path: &str, t :&Vec<f64>, m :&Vec<f64>, d :&Vec<f64> ) -> Result<(), Box<dyn Error>>  {
-    let mut wtr = Writer::from_path(path)?;
-    for ( (tx,dx), mx ) in t.iter().zip( d ).zip( m ){
+/// the output path as a str, a path that is not UTF-8 is an error rather than a panic.
+fn output_path(path: &Path) -> Result<&str> {
+    path.to_str().ok_or_else(|| HumptyError::parse(path.to_string_lossy(), "output path is not valid UTF-8"))
+}
+
+/// `dates` adds a date column when the package has a time axis.
+pub fn serialize_csv_data(path: &str, t :&Vec<f64>, dates : Option<&Vec<String>>, m :&Vec<f64>, d :&Vec<f64> ) -> Result<()>  {
+    let mut wtr = Writer::from_writer(File::create(path).map_err(|e| HumptyError::io(path, e))?);
+    for ( k, ((tx,dx), mx) ) in t.iter().zip( d ).zip( m ).enumerate(){
         wtr.serialize( 
-            RowOut { time : *tx , model : *mx, data: *dx }
+            RowOut { time : *tx , date : dates.map( |v| v[k].clone() ), model : *mx, data: *dx }
         )?; 
     }
     wtr.flush()?;
@@ -53,19 +67,301 @@ pub fn serialize_csv_data(path: &str, t :&Vec<f64>, m :&Vec<f64>, d :&Vec<f64> )
 #[derive(serde::Serialize)]
 pub struct RowOut2 {
     time: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
     model: f64, 
 } 
-pub fn serialize_csv_data2(path: &str, t :&Vec<f64>, m :&Vec<f64> ) -> Result<(), Box<dyn Error>>  {
-    let mut wtr = Writer::from_path(path)?;
-    for (tx,mx) in t.iter().zip( m ){
+pub fn serialize_csv_data2(path: &str, t :&Vec<f64>, dates : Option<&Vec<String>>, m :&Vec<f64> ) -> Result<()>  {
+    let mut wtr = Writer::from_writer(File::create(path).map_err(|e| HumptyError::io(path, e))?);
+    for ( k, (tx,mx) ) in t.iter().zip( m ).enumerate(){
         wtr.serialize( 
-            RowOut2 { time : *tx , model : *mx }
+            RowOut2 { time : *tx , date : dates.map( |v| v[k].clone() ), model : *mx }
         )?; 
     }
     wtr.flush()?;
     Ok(()) 
 }
 
+/// the 23 levels of the COVID-19 forecast hubs.
+pub const HUB_QUANTILES: [f64; 23] = [
+    0.01, 0.025, 0.05, 0.1, 0.15, 0.2, 0.25, 0.3, 0.35, 0.4, 0.45, 0.5, 0.55, 0.6, 0.65, 0.7, 0.75, 0.8, 0.85, 0.9, 0.95, 0.975, 0.99,
+];
+
+/// comma separated quantile levels, `hub` for `HUB_QUANTILES`.
+pub fn parse_quantile_levels(s: &str) -> Result<Vec<f64>> {
+    if s.trim() == "hub" {
+        return Ok(HUB_QUANTILES.to_vec());
+    }
+    s.split(',')
+        .map(|q| q.trim().parse::<f64>().map_err(|e| HumptyError::parse(format!("quantile level '{q}'"), e)))
+        .collect()
+}
+
+/// quantile output of `csv fore`, one row per time and level instead of the point forecast.  An ensemble spread
+/// takes its members and weights from the `ForecastEnsemble` given with it (all fits, akaike weights by default).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ForecastQuantiles {
+    pub levels: Vec<f64>,
+    #[serde(default)]
+    pub spread: Spread,
+}
+
+#[derive(serde::Serialize)]
+pub struct RowOutQuantile {
+    time: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+    ahead: f64,
+    quantile: f64,
+    value: f64,
+}
+
+pub fn serialize_csv_quantiles(path: &str, qf: &QuantileForecast, dates: Option<&Vec<String>>) -> Result<()> {
+    let mut wtr = Writer::from_writer(File::create(path).map_err(|e| HumptyError::io(path, e))?);
+    for (k, (tx, hx)) in qf.time.iter().zip(qf.ahead.iter()).enumerate() {
+        for (q, vx) in qf.quantiles.iter().zip(qf.value[k].iter()) {
+            wtr.serialize(RowOutQuantile { time: *tx, date: dates.map(|v| v[k].clone()), ahead: *hx, quantile: *q, value: *vx })?;
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// the settings of an ensemble spread, which a residual spread has no use for.
+fn spread_ensemble(quantiles: &ForecastQuantiles, ensemble: Option<&ForecastEnsemble>) -> Result<ForecastEnsemble> {
+    match (quantiles.spread, ensemble) {
+        (Spread::Residual, Some(_)) => {
+            Err(HumptyError::parse("forecast quantiles", "ensemble members and weights need the ensemble spread"))
+        }
+        (_, ensemble) => Ok(ensemble.cloned().unwrap_or_default()),
+    }
+}
+
+/// quantile forecast of fit `item` (or of the weighted top fits, see `ForecastEnsemble`) at `times`, see `api::Spread`.
+pub fn quantile_forecast(
+    fits: &[model_fit<ModelTanh>],
+    item: usize,
+    fitted: &Series,
+    times: &[f64],
+    quantiles: &ForecastQuantiles,
+    ensemble: Option<&ForecastEnsemble>,
+) -> Result<QuantileForecast> {
+    let ensemble = spread_ensemble(quantiles, ensemble)?;
+    match quantiles.spread {
+        Spread::Residual => api::residual_quantile_forecast(&fit_at(fits, item)?.fitted_model, fitted, times, &quantiles.levels),
+        Spread::Ensemble => {
+            let ef = ensemble_forecast(fits, fitted, times, &ensemble)?;
+            api::ensemble_quantile_forecast(&ef, &fitted.time, &quantiles.levels)
+        }
+    }
+}
+
+/// incidence quantiles of fit `item` (or of the weighted top fits) between consecutive `times`, from the differenced
+/// residual random-walk paths or ensemble members (see `api::incidence_quantiles`).
+pub fn incidence_forecast(
+    fits: &[model_fit<ModelTanh>],
+    item: usize,
+    fitted: &Series,
+    times: &[f64],
+    quantiles: &ForecastQuantiles,
+    ensemble: Option<&ForecastEnsemble>,
+) -> Result<Vec<Vec<f64>>> {
+    let ensemble = spread_ensemble(quantiles, ensemble)?;
+    let (paths, weights) = match quantiles.spread {
+        Spread::Residual => (api::residual_paths(&fit_at(fits, item)?.fitted_model, fitted, times, api::RESIDUAL_PATHS, 0)?, vec![1.; api::RESIDUAL_PATHS]),
+        Spread::Ensemble => {
+            let top = ensemble.members.unwrap_or(fits.len()).min(fits.len());
+            let ef = api::ensemble_forecast(&fits[..top].iter().collect::<Vec<_>>(), fitted, times, ensemble.weights)?;
+            (ef.members, ef.weights)
+        }
+    };
+    api::incidence_quantiles(&paths, &weights, &quantiles.levels)
+}
+
+/// the top fits combined with weights from their residuals (see `api::Weighting`): the ensemble output of `csv fore`
+/// and `viz basic`, and the members of an ensemble quantile spread (`csv fore`, `csv hub`).
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ForecastEnsemble {
+    #[serde(default)]
+    pub weights: Weighting,
+    /// number of top fits combined, all fits when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub members: Option<usize>,
+}
+
+/// the ensemble of the top fits at `times`, weighted by their residuals on `fitted`.
+pub fn ensemble_forecast(
+    fits: &[model_fit<ModelTanh>],
+    fitted: &Series,
+    times: &[f64],
+    ensemble: &ForecastEnsemble,
+) -> Result<EnsembleForecast> {
+    let top = ensemble.members.unwrap_or(fits.len()).min(fits.len());
+    let members: Vec<&model_fit<ModelTanh>> = fits[..top].iter().collect();
+    let ef = api::ensemble_forecast(&members, fitted, times, ensemble.weights)?;
+    println!("ensemble of {top} fits, {:?} weights:", ensemble.weights);
+    ef.weights.iter().zip(ef.rss.iter()).enumerate().for_each(|(k, (w, r))| println!("\tfit {k}: weight {w:.4}, residual sum of squares {r:.6e}"));
+    Ok(ef)
+}
+
+/// time, date, data (inside the data), the ensemble mean and spread, then the forecast of each member as `fit_<k>`;
+/// the weights go to `<path stem>_weights.csv`.
+pub fn serialize_csv_ensemble(path: &str, ef: &EnsembleForecast, dates: Option<&Vec<String>>, d: &[f64]) -> Result<()> {
+    let mut wtr = Writer::from_writer(File::create(path).map_err(|e| HumptyError::io(path, e))?);
+    let mut header: Vec<String> = vec![String::from("time")];
+    if dates.is_some() {
+        header.push(String::from("date"));
+    }
+    header.extend(["data", "mean", "spread"].iter().map(|x| x.to_string()));
+    header.extend((0..ef.members.len()).map(|k| format!("fit_{k}")));
+    wtr.write_record(&header)?;
+    for (k, tx) in ef.time.iter().enumerate() {
+        let mut row: Vec<String> = vec![tx.to_string()];
+        if let Some(dates) = dates {
+            row.push(dates[k].clone());
+        }
+        row.push(d.get(k).map_or(String::new(), |x| x.to_string()));
+        row.push(ef.mean[k].to_string());
+        row.push(ef.spread[k].to_string());
+        row.extend(ef.members.iter().map(|m| m[k].to_string()));
+        wtr.write_record(&row)?;
+    }
+    wtr.flush()?;
+
+    let mut weights_path = PathBuf::from(path);
+    let stem = weights_path.file_stem().map(|x| x.to_string_lossy().to_string()).unwrap_or_default();
+    weights_path.set_file_name(format!("{stem}_weights.csv"));
+    let wpath = weights_path.to_string_lossy().to_string();
+    let mut wtr = Writer::from_writer(File::create(&wpath).map_err(|e| HumptyError::io(wpath.clone(), e))?);
+    wtr.write_record(["fit", "weight", "rss"])?;
+    for (k, (w, r)) in ef.weights.iter().zip(ef.rss.iter()).enumerate() {
+        wtr.write_record([k.to_string(), w.to_string(), r.to_string()])?;
+    }
+    wtr.flush()?;
+    println!("writing file {}", wpath);
+    Ok(())
+}
+
+////////////////
+///
+/// forecast hub rows for `csv hub`:
+///
+/// ```text
+/// ahead,geo_value,quantile,value,forecaster,forecast_date,data_source,signal,target_end_date,incidence_period
+/// ```
+///
+/// The forecast date is the date of the last fitted data point, `ahead` counts time steps past it (1 is the next
+/// step) and `target_end_date` is the date forecast.  The model is taken to be on cumulative counts (unless its load
+/// steps end with `diff`), the incidence of a step comes from differenced forecasts: the quantiles over the ensemble
+/// members, or over residual random-walk paths, of the count at its end less the count one step before, clamped at
+/// zero (see `api::incidence_quantiles`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HubSpec {
+    pub geo_value: String,
+    #[serde(default = "default_forecaster")]
+    pub forecaster: String,
+    #[serde(default = "default_data_source")]
+    pub data_source: String,
+    /// confirmed_incidence_num or confirmed_cumulative_num by variant when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<String>,
+    #[serde(default = "default_ahead")]
+    pub ahead: usize,
+    /// write cumulative counts rather than incidence.
+    #[serde(default)]
+    pub cumulative: bool,
+}
+
+fn default_forecaster() -> String { String::from("humpty") }
+fn default_data_source() -> String { String::from("jhu-csse") }
+fn default_ahead() -> usize { 28 }
+
+#[derive(serde::Serialize)]
+pub struct RowOutHub {
+    ahead: usize,
+    geo_value: String,
+    quantile: f64,
+    value: f64,
+    forecaster: String,
+    forecast_date: String,
+    data_source: String,
+    signal: String,
+    target_end_date: String,
+    incidence_period: String,
+}
+
+/// write the forecast hub rows of fit `item` (see `HubSpec`), quantiles default to the median alone.
+pub fn hub_csv(
+    models: String,
+    output: String,
+    item: usize,
+    hub: HubSpec,
+    quantiles: Option<ForecastQuantiles>,
+    ensemble: Option<ForecastEnsemble>,
+) -> Result<()> {
+    let VX: data_fit_package<ModelTanh> = read_fit_package(&models)?;
+    let data_fit_package { load_metadata: lmd, fits: VV, .. } = VX;
+    let Some(axis) = lmd.time.clone().filter(|a| !a.numeric) else {
+        return Err(HumptyError::parse("csv hub", "the package has no date axis, fit with --date-col"));
+    };
+    let incidence_model = matches!(lmd.steps.last(), Some(SeriesStep::Diff));
+    if incidence_model && hub.cumulative {
+        return Err(HumptyError::parse("csv hub", "the model was fitted on differenced counts, only the incidence variant is available"));
+    }
+    let period = match lmd.steps.iter().rev().find_map(|s| match s { SeriesStep::Resample { period, .. } => Some(*period), _ => None }) {
+        Some(Period::Week) => "week",
+        Some(Period::Month) => "month",
+        None => "day",
+    };
+    let ((_, tsplice), dsplice, _) = reload_data(lmd)?;
+    let fitted = Series::new(tsplice.as_slice().to_vec(), dsplice.as_slice().to_vec())?;
+    let last = fitted.time.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let step = api::time_step(&fitted.time);
+    // the last fitted time and `ahead` steps past it.
+    let times: Vec<f64> = (0..=hub.ahead).map(|h| last + h as f64 * step).collect();
+
+    let quantiles = quantiles.unwrap_or(ForecastQuantiles { levels: vec![0.5], spread: Spread::default() });
+    let qf = quantile_forecast(&VV, item, &fitted, &times, &quantiles, ensemble.as_ref())?;
+    let incidence = match hub.cumulative || incidence_model {
+        true => None,
+        false => Some(incidence_forecast(&VV, item, &fitted, &times, &quantiles, ensemble.as_ref())?),
+    };
+    let signal = hub.signal.clone().unwrap_or_else(|| {
+        String::from(if hub.cumulative { "confirmed_cumulative_num" } else { "confirmed_incidence_num" })
+    });
+
+    let mut path_output = PathBuf::from(output.as_str());
+    path_output.set_extension("csv");
+    let PX = output_path(&path_output)?;
+    let mut wtr = Writer::from_writer(File::create(PX).map_err(|e| HumptyError::io(PX, e))?);
+    let forecast_date = axis.date(times[0]);
+    for h in 1..times.len() {
+        for (j, q) in qf.quantiles.iter().enumerate() {
+            let value = match (&incidence, incidence_model) {
+                (Some(incidence), _) => incidence[h - 1][j],
+                // a model of the incidence itself, clamped as the differenced forecasts are.
+                (None, true) => qf.value[h][j].max(0.),
+                (None, false) => qf.value[h][j],
+            };
+            wtr.serialize(RowOutHub {
+                ahead: h,
+                geo_value: hub.geo_value.clone(),
+                quantile: *q,
+                value,
+                forecaster: hub.forecaster.clone(),
+                forecast_date: forecast_date.clone(),
+                data_source: hub.data_source.clone(),
+                signal: signal.clone(),
+                target_end_date: axis.date(times[h]),
+                incidence_period: String::from(period),
+            })?;
+        }
+    }
+    wtr.flush()?;
+    println!("writing file {}", PX);
+    Ok(())
+}
+
 //drop_csv_data
 pub fn drop_csv_data(
     models: String,
@@ -75,337 +371,281 @@ pub fn drop_csv_data(
     limit: Option<usize>,
     strides: Option<usize>,
     pmod: partial_model,
-) {
-    let mut path = PathBuf::from(models.as_str()); // get the model file.
-    path.set_extension("yml");
-    let mut data_file =
-        File::open(path).expect("open model(s) file failed, please insure the extention is yml");
-    let mut reader = BufReader::new(data_file);
-    let VX: data_fit_package<ModelTanh> =
-        serde_yaml::from_reader(reader).expect("problem reading yaml file");
+    quantiles: Option<ForecastQuantiles>,
+    ensemble: Option<ForecastEnsemble>,
+) -> Result<()> {
+    let VX: data_fit_package<ModelTanh> = read_fit_package(&models)?; // get the model file.
     let data_fit_package {
         load_metadata: lmd,
         fits: VV,
+        ..
     } = VX;
-    let ((tspan, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd);
+    let time = lmd.time.clone();
+    let ((tspan, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd)?;
 
     // read the model file.
     // step 4: calculate the clipping slice or viz slice
-    let eoffset = offset.unwrap_or(0_i64) as usize;
-    let elimit: usize = limit.unwrap_or(dfull.shape()[0]);
-    let delimit : usize = limit.unwrap_or( dfull.shape()[0]).min( dfull.shape()[0]);
-    let estrides = strides.unwrap_or(1);
+    let (eoffset, delimit, estrides) = viz_slice(offset, limit, strides, dfull.shape()[0])?;
+    let elimit: usize = limit.unwrap_or(delimit); // may run past the data in forecast mode.
     // All time/data in the viz slice
-    let tall: Vec<f64> = {
-        if elimit <= delimit {
-         tfull
-        .slice(s![eoffset..(eoffset+elimit); estrides])
-        .to_vec()
-    } else {
-        (eoffset..(eoffset+elimit)).step_by(estrides).map(|x|{x as f64}).collect()
-    }};
+    let tall: Vec<f64> = forecast_times(&tfull, eoffset, elimit, estrides);
+    let dates: Option<Vec<String>> = time.map(|axis| tall.iter().map(|t| axis.date(*t)).collect());
    
     let dall: Vec<f64> = dfull
         .slice(s![eoffset..(eoffset+delimit); estrides])
         .to_vec();
-    if (estrides > 1) {
-        panic!(" estrides Not yet supported ");
-    }
 
     let mut path_output = PathBuf::from(output.as_str());
     path_output.set_extension("csv");
     let output_stem = path_output.clone();
 
-    (item..(item + 1)).into_par_iter().for_each(|k| {
+    if let Some(quantiles) = quantiles {
+        let fitted = Series::new(tsplice.as_slice().to_vec(), dsplice.as_slice().to_vec())?;
+        let qf = quantile_forecast(&VV, item, &fitted, &tall, &quantiles, ensemble.as_ref())?;
+        let PX = output_path(&path_output)?;
+        serialize_csv_quantiles(PX, &qf, dates.as_ref())?;
+        println!("writing file {}", PX);
+        return Ok(());
+    }
+
+    if let Some(ensemble) = ensemble {
+        let fitted = Series::new(tsplice.as_slice().to_vec(), dsplice.as_slice().to_vec())?;
+        let ef = ensemble_forecast(&VV, &fitted, &tall, &ensemble)?;
+        let PX = output_path(&path_output)?;
+        serialize_csv_ensemble(PX, &ef, dates.as_ref(), &dall)?;
+        println!("writing file {}", PX);
+        return Ok(());
+    }
+
+    (item..(item + 1)).into_par_iter().try_for_each(|k| -> Result<()> {
         let model_fit {
             humps: humps,
             fitted_model: fitted_model,
             initial_model: initial_model,
             residual_total: residual_total,
             residual_per_point: residual_per_point,
-        } = &VV[k];
+            ..
+        } = fit_at(&VV, k)?;
 
-        let mxall: Vec<f64> = eval_M(&fitted_model, &tall);
-        let PX = path_output.to_str().unwrap();
+        let mxall: Vec<f64> = api::forecast(fitted_model, &tall).value;
+        let PX = output_path(&path_output)?;
         if elimit <= delimit { 
-            serialize_csv_data(PX, &tall, &mxall, &dall );
+            serialize_csv_data(PX, &tall, dates.as_ref(), &mxall, &dall )?;
         }   else {
-            serialize_csv_data2(PX, &tall, &mxall );
+            serialize_csv_data2(PX, &tall, dates.as_ref(), &mxall )?;
         }
 
         println!("writing file {}", PX);
-    });
+        Ok(())
+    })
 }
 
 
-#[derive(serde::Serialize)]
-pub struct RowOutResid {
-    time: f64,
-    model: f64, 
-    data: f64,
-    residual: f64,
-    disp : f64,
-    skew : f64
-} 
-pub fn serialize_csv_res_data(path: &str, t :&Vec<f64>, m :&Vec<f64>, d :&Vec<f64>, r: &Vec<f64>, rpp:&Vec<f64> , skew : &Vec<f64>) -> Result<(), Box<dyn Error>>  {
-    let mut wtr = Writer::from_path(path)?;
-    for (((((tx,mx), dx ) , rx), rppx), sx) in t.iter().zip( d ).zip( m ).zip( r ).zip(rpp).zip(skew){
-        wtr.serialize( 
-            RowOutResid { time : *tx , model : *mx, data: *dx, residual: *rx, disp: *rppx, skew: *sx }
-        )?; 
+/// time, date, data (inside the data), then `hump_<k>` per hump ordered by t0, `baseline` and `sum` (and `model` in
+/// data units when the parts are in the log1p space); with `incidence` also `incidence_<k>` and `incidence_sum`.
+pub fn serialize_csv_components(path: &str, c: &Components, dates: Option<&Vec<String>>, d: &[f64], model: &[f64], incidence: bool) -> Result<()> {
+    let mut wtr = Writer::from_writer(File::create(path).map_err(|e| HumptyError::io(path, e))?);
+    let mut header: Vec<String> = vec![String::from("time")];
+    if dates.is_some() {
+        header.push(String::from("date"));
+    }
+    header.push(String::from("data"));
+    header.extend((0..c.humps.len()).map(|k| format!("hump_{k}")));
+    header.extend(["baseline", "sum"].iter().map(|x| x.to_string()));
+    if !c.data_units {
+        header.push(String::from("model"));
+    }
+    if incidence {
+        header.extend((0..c.rates.len()).map(|k| format!("incidence_{k}")));
+        header.push(String::from("incidence_sum"));
+    }
+    wtr.write_record(&header)?;
+    for (k, tx) in c.time.iter().enumerate() {
+        let mut row: Vec<String> = vec![tx.to_string()];
+        if let Some(dates) = dates {
+            row.push(dates[k].clone());
+        }
+        row.push(d.get(k).map_or(String::new(), |x| x.to_string()));
+        row.extend(c.humps.iter().map(|h| h[k].to_string()));
+        row.push(c.baseline[k].to_string());
+        row.push(c.sum[k].to_string());
+        if !c.data_units {
+            row.push(model[k].to_string());
+        }
+        if incidence {
+            row.extend(c.rates.iter().map(|r| r[k].to_string()));
+            row.push(c.rate_sum[k].to_string());
+        }
+        wtr.write_record(&row)?;
     }
     wtr.flush()?;
-    Ok(()) 
+    Ok(())
 }
 
-pub fn residual_disp_skew_csv(
+/// the humps of fit `item` side by side over the rows of the slice, a `limit` past the data forecasts ahead as in `csv fore`.
+pub fn components_csv(
     models: String,
     output: String,
     item: usize,
     offset: Option<i64>,
     limit: Option<usize>,
     strides: Option<usize>,
-    pmod: partial_model,
-) {
-    let mut path = PathBuf::from(models.as_str()); // get the model file.
-    path.set_extension("yml");
-    let mut data_file =
-        File::open(path).expect("open model(s) file failed, please insure the extention is yml");
-    let mut reader = BufReader::new(data_file);
-    let VX: data_fit_package<ModelTanh> =
-        serde_yaml::from_reader(reader).expect("problem reading yaml file");
-    let data_fit_package {
-        load_metadata: lmd,
-        fits: VV,
-    } = VX;
-    let ((tspan, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd);
-
-    // read the model file.
-    // step 4: calculate the clipping slice or viz slice
-    let eoffset = offset.unwrap_or(0_i64) as usize;
-    let elimit: usize = limit.unwrap_or(dfull.shape()[0]).min( dfull.shape()[0]);
-    let delimit : usize = limit.unwrap_or( dfull.shape()[0]).min( dfull.shape()[0]);
-    let estrides = strides.unwrap_or(1);
-    // All time/data in the viz slice
-    let tall: Vec<f64> = {
-        if elimit <= delimit {
-         tfull
-        .slice(s![eoffset..(eoffset+elimit); estrides])
-        .to_vec()
-        } else {
-            println!( " elimit {elimit} and delimit {delimit}");
-            panic!( "not supported ");
-        
-            (eoffset..(eoffset+elimit)).step_by(estrides).map(|x|{x as f64}).collect()
-        }};
-   
-    let dall: Vec<f64> = dfull
-        .slice(s![eoffset..(eoffset+delimit); estrides])
-        .to_vec();
-    if (estrides > 1) {
-        panic!(" estrides Not yet supported ");
+    incidence: bool,
+) -> Result<()> {
+    let VX: data_fit_package<ModelTanh> = read_fit_package(&models)?;
+    let data_fit_package { load_metadata: lmd, fits: VV, .. } = VX;
+    let time = lmd.time.clone();
+    let (_, _, (tfull, dfull)) = reload_data(lmd)?;
+
+    let (eoffset, delimit, estrides) = viz_slice(offset, limit, strides, dfull.shape()[0])?;
+    let elimit: usize = limit.unwrap_or(delimit);
+    let tall: Vec<f64> = forecast_times(&tfull, eoffset, elimit, estrides);
+    let dates: Option<Vec<String>> = time.as_ref().map(|axis| tall.iter().map(|t| axis.date(*t)).collect());
+    let dall: Vec<f64> = dfull.slice(s![eoffset..(eoffset+delimit); estrides]).to_vec();
+
+    let fitted_model = &fit_at(&VV, item)?.fitted_model;
+    let c = api::components(fitted_model, &tall);
+    c.t0.iter().enumerate().for_each(|(k, t0)| match &time {
+        Some(axis) => println!("\thump_{k}: t0 {t0:.3} ({})", axis.date(*t0)),
+        None => println!("\thump_{k}: t0 {t0:.3}"),
+    });
+    if !c.data_units {
+        println!("\tthe fit is in log(1 + y), humps, baseline and sum are in that space, model is in data units");
     }
+    let model: Vec<f64> = api::forecast(fitted_model, &tall).value;
 
     let mut path_output = PathBuf::from(output.as_str());
     path_output.set_extension("csv");
-    let output_stem = path_output.clone();
-
-    let ETS: Vec<(usize, &f64)> = tsplice
-        .iter()
-        .enumerate()
-        .filter(|(k, g)| ((**g as usize) >= eoffset) && ((**g as usize) < (eoffset + elimit)))
-        .collect();
+    let PX = output_path(&path_output)?;
+    serialize_csv_components(PX, &c, dates.as_ref(), &dall, &model, incidence)?;
+    println!("writing file {}", PX);
+    Ok(())
+}
 
-    let pre = 0_usize; // no flanking regions
-    let post = 0_usize; // no flanking regions
-    (item..(item + 1)).into_par_iter().for_each(|k| {
-        let model_fit {
-            humps: humps,
-            fitted_model: fitted_model,
-            initial_model: initial_model,
-            residual_total: residual_total,
-            residual_per_point: residual_per_point,
-        } = &VV[k];
-    
-        let (rsumsqxx, rsumsq_ppxx, residxx, resid1xx) = fitted_model.residual(&tall, &dall);
-    
-        let mxall: Vec<f64> = eval_M(&fitted_model, &tall);
-
-        let res_viz: Vec<f64> = resid1xx.clone();
-        ////
-        /// m_3( m ) = sum_j^m( r[j] - <r[:m]> )^3 / ((m-1) [r[:m])
-        let mu3: Vec<f64> = (1..)
-            .take(res_viz.len() + 1)
-            .map(|m| {
-                let avg_resid: f64 = res_viz.iter().take(m).sum::<f64>() / (m as f64);
-                let resid_disp: Vec<f64> = res_viz.iter().take(m).map(|x| *x - avg_resid).collect();
-                let resid_disp_sq: Vec<f64> = resid_disp.iter().map(|x| (*x) * (*x)).collect();
-                let resid_disp_cu: Vec<f64> =
-                    resid_disp.iter().map(|x| (*x) * (*x) * (*x)).collect();
-                let sv = sqrt(resid_disp_sq.iter().sum::<f64>() / (m as f64));
-                let mut dnom: f64 = sv * sv * sv * ((m - 1) as f64);
-                if dnom <= 0.0 {
-                    dnom = 1.0;
-                }
-                let m_3: f64 = resid_disp_cu.iter().sum::<f64>() / (dnom);
-                m_3
-            })
-            .collect();
-
-        let PX = path_output.to_str().unwrap();
-        if elimit <= delimit { 
-            serialize_csv_res_data( PX, &tall, &mxall, &dall, &residxx, &resid1xx, &mu3);
-        }   else {
-            panic!("not supported ");
+pub fn serialize_csv_alarms(path: &str, alarms: &[Alarm], t: &[f64], dates: Option<&Vec<String>>) -> Result<()> {
+    let mut wtr = Writer::from_writer(File::create(path).map_err(|e| HumptyError::io(path, e))?);
+    let mut header: Vec<&str> = vec!["detector", "time"];
+    if dates.is_some() {
+        header.push("date");
+    }
+    header.push("onset");
+    if dates.is_some() {
+        header.push("onset_date");
+    }
+    header.extend(["magnitude", "statistic"]);
+    wtr.write_record(&header)?;
+    for a in alarms {
+        let mut row: Vec<String> = vec![a.detector.name().to_string(), t[a.index].to_string()];
+        if let Some(dates) = dates {
+            row.push(dates[a.index].clone());
         }
-
-        println!("writing file {}", PX);
-    });
-
+        row.push(t[a.onset].to_string());
+        if let Some(dates) = dates {
+            row.push(dates[a.onset].clone());
+        }
+        row.extend([a.magnitude.to_string(), a.statistic.to_string()]);
+        wtr.write_record(&row)?;
+    }
+    wtr.flush()?;
+    Ok(())
 }
 
-/* 
-/////////////////////////////////
-/// TODO : clean up this function its copied from residual_visualization but needs very little of the present code.
-///
-pub fn disp_visualization(
+/// change detector alarms on the residuals of fit `item` over the rows of the slice, see `api::residual_alarms`.
+pub fn alarms_csv(
     models: String,
     output: String,
     item: usize,
     offset: Option<i64>,
     limit: Option<usize>,
     strides: Option<usize>,
-    pmod: partial_model,
-) {
-    let mut path = PathBuf::from(models.as_str()); // get the model file.
-    path.set_extension("yml");
-    let mut data_file =
-        File::open(path).expect("open model(s) file failed, please insure the extention is yml");
-    let mut reader = BufReader::new(data_file);
-    let VX: data_fit_package<ModelTanh> =
-        serde_yaml::from_reader(reader).expect("problem reading yaml file");
-    let data_fit_package {
-        load_metadata: lmd,
-        fits: VV,
-    } = VX;
-    let ((tspan, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd);
+    config: &AlarmConfig,
+) -> Result<()> {
+    let VX: data_fit_package<ModelTanh> = read_fit_package(&models)?;
+    let data_fit_package { load_metadata: lmd, fits: VV, .. } = VX;
+    let time = lmd.time.clone();
+    let ((_, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd)?;
+
+    let (eoffset, elimit, estrides) = viz_slice(offset, limit, strides, dfull.shape()[0])?;
+    let (tall, dall) = viz_rows(&tfull, &dfull, eoffset, elimit, estrides);
+    let dates: Option<Vec<String>> = time.as_ref().map(|axis| tall.iter().map(|t| axis.date(*t)).collect());
+
+    let fitted_model = &fit_at(&VV, item)?.fitted_model;
+    let fitted = Series::new(tsplice.as_slice().to_vec(), dsplice.as_slice().to_vec())?;
+    let alarms = api::residual_alarms(fitted_model, &fitted, &Series::new(tall.clone(), dall)?, config)?;
+    alarms.iter().for_each(|a| {
+        let at = match &dates {
+            Some(dates) => format!("{} ({})", tall[a.index], dates[a.index]),
+            None => tall[a.index].to_string(),
+        };
+        println!("	{}: time {at}, onset {}, magnitude {:.3} sd", a.detector.name(), tall[a.onset], a.magnitude);
+    });
 
-    // read the model file.
-    // step 4: calculate the clipping slice or viz slice
-    let eoffset = offset.unwrap_or(0_i64) as usize;
-    let elimit: usize = limit.unwrap_or(dfull.shape()[0]);
-    let estrides = strides.unwrap_or(1);
-    // All time/data in the viz slice
-    let tall: Vec<f64> = tfull
-        .slice(s![eoffset..(eoffset+elimit); estrides])
-        .to_vec();
-    let dall: Vec<f64> = dfull
-        .slice(s![eoffset..(eoffset+elimit); estrides])
-        .to_vec();
+    let mut path_output = PathBuf::from(output.as_str());
+    path_output.set_extension("csv");
+    let PX = output_path(&path_output)?;
+    serialize_csv_alarms(PX, &alarms, &tall, dates.as_ref())?;
+    println!("writing file {} ({} alarms)", PX, alarms.len());
+    Ok(())
+}
 
-    if (estrides > 1) {
-        panic!(" estrides Not yet supported ");
+#[derive(serde::Serialize)]
+pub struct RowOutResid {
+    time: f64,
+    model: f64, 
+    data: f64,
+    residual: f64,
+    disp : f64,
+    skew : f64
+} 
+pub fn serialize_csv_res_data(path: &str, t :&Vec<f64>, m :&Vec<f64>, d :&Vec<f64>, r: &Vec<f64>, rpp:&Vec<f64> , skew : &Vec<f64>) -> Result<()>  {
+    let mut wtr = Writer::from_writer(File::create(path).map_err(|e| HumptyError::io(path, e))?);
+    for (((((tx,mx), dx ) , rx), rppx), sx) in t.iter().zip( m ).zip( d ).zip( r ).zip(rpp).zip(skew){
+        wtr.serialize( 
+            RowOutResid { time : *tx , model : *mx, data: *dx, residual: *rx, disp: *rppx, skew: *sx }
+        )?; 
     }
-    // model (time) clipped to viz slice.  tsplice is the model support (in global data index), now we further restrict that to viz_slice
-    let ETS: Vec<(usize, &f64)> = tsplice
-        .iter()
-        .enumerate()
-        .filter(|(k, g)| ((**g as usize) >= eoffset) && ((**g as usize) < (eoffset + elimit)))
-        .collect();
-    let etsplice: Vec<f64> = ETS.iter().map(|(a, b)| **b).collect();
-    let etslice: Vec<usize> = ETS.iter().map(|(a, b)| *a).collect();
+    wtr.flush()?;
+    Ok(()) 
+}
 
-    let mut path_output = PathBuf::from(output.as_str());
-    path_output.set_extension("");
-    let output_stem = path_output.clone();
-    path_output.set_extension("png");
+#[derive(serde::Serialize)]
+pub struct RowOutResidWindow {
+    time: f64,
+    model: f64,
+    data: f64,
+    residual: f64,
+    disp: f64,
+    skew: f64,
+    roll_mean: f64,
+    roll_sd: f64,
+    roll_skew: f64,
+    roll_kurtosis: f64,
+}
 
-    (item..(item + 1)).into_par_iter().for_each(|k| {
-        let model_fit {
-            humps: humps,
-            fitted_model: fitted_model,
-            initial_model: initial_model,
-            residual_total: residual_total,
-            residual_per_point: residual_per_point,
-        } = &VV[k];
-        let mx = eval_M(&fitted_model, &etsplice);
-        //(* flanking prediction *)
-        let partial_model {
-            before: pre,
-            after: post,
-        } = pmod;
-
-        let ipre: Vec<usize> = tall
-            .iter()
-            .enumerate()
-            .filter(|(k, g)| **g < *etsplice.first().unwrap())
-            .map(|(k, v)| k)
-            .collect();
-        let ipre: Vec<usize> = ipre.iter().rev().take(pre).rev().map(|k| *k).collect();
-        let tpre: Vec<f64> = tall
-            .iter()
-            .filter(|g| **g < *etsplice.first().unwrap())
-            .map(|v| *v)
-            .collect();
-        let tpre: Vec<f64> = tpre.iter().rev().take(pre).rev().map(|v| *v).collect(); //tpre[ 0.max(tpre.len() - pre).. ].to_vec();
-        let tpost: Vec<f64> = tall
-            .iter()
-            .filter(|g| **g > *etsplice.last().unwrap())
-            .map(|v| *v)
-            .take(post)
-            .collect();
-        let ipost: Vec<usize> = tall
-            .iter()
-            .enumerate()
-            .filter(|(k, g)| **g > *etsplice.last().unwrap())
-            .map(|(k, g)| k)
-            .take(post)
-            .collect();
-
-        let mxall: Vec<f64> = eval_M(&fitted_model, &tall);
-        let mxpre: Vec<f64> = eval_M(&fitted_model, &tpre);
-        let mxpost: Vec<f64> = eval_M(&fitted_model, &tpost);
-
-        let lsm = residual_total;
-        // local resid
-        //let ( rsumsqxx,rsumsq_ppxx,residxx, resid1xx ) = fitted_model.residual_mat(&tslice, &dsplice);
-        // global resid
-        let (rsumsqxx, rsumsq_ppxx, residxx, resid1xx) = fitted_model.residual(&tall, &dall);
-
-        let i_viz: Vec<usize> = ipre
-            .iter()
-            .chain(etslice.iter().chain(ipost.iter()))
-            .map(|k| *k)
-            .collect();
-        //let res_pre : Vec<f64> = ipre.iter().map(|k|{resid1xx[*k]}).collect();
-        //let res_mod : Vec<f64> = etslice.iter().map( |k | { resid1xx[*k] }).collect();
-        //let res_post : Vec<f64> = ipost.iter().map(|k|{resid1xx[*k]}).collect();
-        ///////////////
-        /// form envelopes
-        let res_viz: Vec<f64> = i_viz.iter().map(|k| resid1xx[*k]).collect();
-        //let dat_viz : Vec<f64> = i_viz.iter().map(|k|{dall[*k]}).collect();
-
-        viz_lib2::plot_e(
-            &tall,
-            &res_viz,
-            viz_lib2::PlotAction::PNG(
-                format!("{}_{}", output_stem.to_str().unwrap(), k).into(),
-                800,
-                600,
-                2.0,
-            ),
-            String::from(format!("model fit {} \n LSM {} ", k, lsm)),
-            String::from("time "),
-            String::from("displacements"),
-        );
-        println!("writing file {}", output_stem.to_str().unwrap())
-    });
+/// as `serialize_csv_res_data` with the running statistics of a trailing or exponential window appended.
+pub fn serialize_csv_res_window(path: &str, t: &[f64], m: &[f64], d: &[f64], r: &[f64], rpp: &[f64], skew: &[f64], rm: &RollingMoments) -> Result<()> {
+    let mut wtr = Writer::from_writer(File::create(path).map_err(|e| HumptyError::io(path, e))?);
+    for k in 0..t.len() {
+        wtr.serialize(RowOutResidWindow {
+            time: t[k],
+            model: m[k],
+            data: d[k],
+            residual: r[k],
+            disp: rpp[k],
+            skew: skew[k],
+            roll_mean: rm.mean[k],
+            roll_sd: rm.sd[k],
+            roll_skew: rm.skew[k],
+            roll_kurtosis: rm.kurtosis[k],
+        })?;
+    }
+    wtr.flush()?;
+    Ok(())
 }
 
-/////////////////////////////////
-/// TODO : clean up this function its copied from residual_visualization but needs very little of the present code.
-///
-pub fn skew_visualization(
+/// residual, displacement and expanding skew of fit `item`; with `window` also the running mean, sd, skew and
+/// kurtosis of the residuals over that window (see `api::StatWindow`).
+pub fn residual_disp_skew_csv(
     models: String,
     output: String,
     item: usize,
@@ -413,148 +653,99 @@ pub fn skew_visualization(
     limit: Option<usize>,
     strides: Option<usize>,
     pmod: partial_model,
-) {
-    let mut path = PathBuf::from(models.as_str()); // get the model file.
-    path.set_extension("yml");
-    let mut data_file =
-        File::open(path).expect("open model(s) file failed, please insure the extention is yml");
-    let mut reader = BufReader::new(data_file);
-    let VX: data_fit_package<ModelTanh> =
-        serde_yaml::from_reader(reader).expect("problem reading yaml file");
+    window: Option<StatWindow>,
+) -> Result<()> {
+    let VX: data_fit_package<ModelTanh> = read_fit_package(&models)?; // get the model file.
     let data_fit_package {
         load_metadata: lmd,
         fits: VV,
+        ..
     } = VX;
-    let ((tspan, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd);
+    let ((tspan, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd)?;
 
     // read the model file.
     // step 4: calculate the clipping slice or viz slice
-    let eoffset = offset.unwrap_or(0_i64) as usize;
-    let elimit: usize = limit.unwrap_or(dfull.shape()[0]);
-    let estrides = strides.unwrap_or(1);
+    // the limit is clamped to the data, there is no forecast past it here.
+    let (eoffset, elimit, estrides) = viz_slice(offset, limit, strides, dfull.shape()[0])?;
     // All time/data in the viz slice
     let tall: Vec<f64> = tfull
         .slice(s![eoffset..(eoffset+elimit); estrides])
         .to_vec();
+   
     let dall: Vec<f64> = dfull
         .slice(s![eoffset..(eoffset+elimit); estrides])
         .to_vec();
 
-    if (estrides > 1) {
-        panic!(" estrides Not yet supported ");
-    }
-    // model (time) clipped to viz slice.  tsplice is the model support (in global data index), now we further restrict that to viz_slice
-    let ETS: Vec<(usize, &f64)> = tsplice
-        .iter()
-        .enumerate()
-        .filter(|(k, g)| ((**g as usize) >= eoffset) && ((**g as usize) < (eoffset + elimit)))
-        .collect();
-    let etsplice: Vec<f64> = ETS.iter().map(|(a, b)| **b).collect();
-    let etslice: Vec<usize> = ETS.iter().map(|(a, b)| *a).collect();
-
     let mut path_output = PathBuf::from(output.as_str());
-    path_output.set_extension("");
+    path_output.set_extension("csv");
     let output_stem = path_output.clone();
-    path_output.set_extension("png");
 
-    (item..(item + 1)).into_par_iter().for_each(|k| {
+    (item..(item + 1)).into_par_iter().try_for_each(|k| -> Result<()> {
         let model_fit {
             humps: humps,
             fitted_model: fitted_model,
             initial_model: initial_model,
             residual_total: residual_total,
             residual_per_point: residual_per_point,
-        } = &VV[k];
-        let mx = eval_M(&fitted_model, &etsplice);
-        //(* flanking prediction *)
-        let partial_model {
-            before: pre,
-            after: post,
-        } = pmod;
-
-        let ipre: Vec<usize> = tall
-            .iter()
-            .enumerate()
-            .filter(|(k, g)| **g < *etsplice.first().unwrap())
-            .map(|(k, v)| k)
-            .collect();
-        let ipre: Vec<usize> = ipre.iter().rev().take(pre).rev().map(|k| *k).collect();
-        let tpre: Vec<f64> = tall
-            .iter()
-            .filter(|g| **g < *etsplice.first().unwrap())
-            .map(|v| *v)
-            .collect();
-        let tpre: Vec<f64> = tpre.iter().rev().take(pre).rev().map(|v| *v).collect(); //tpre[ 0.max(tpre.len() - pre).. ].to_vec();
-        let tpost: Vec<f64> = tall
-            .iter()
-            .filter(|g| **g > *etsplice.last().unwrap())
-            .map(|v| *v)
-            .take(post)
-            .collect();
-        let ipost: Vec<usize> = tall
-            .iter()
-            .enumerate()
-            .filter(|(k, g)| **g > *etsplice.last().unwrap())
-            .map(|(k, g)| k)
-            .take(post)
-            .collect();
-
-        let mxall: Vec<f64> = eval_M(&fitted_model, &tall);
-        let mxpre: Vec<f64> = eval_M(&fitted_model, &tpre);
-        let mxpost: Vec<f64> = eval_M(&fitted_model, &tpost);
-
-        let lsm = residual_total;
-        // local resid
-        //let ( rsumsqxx,rsumsq_ppxx,residxx, resid1xx ) = fitted_model.residual_mat(&tslice, &dsplice);
-        // global resid
-        let (rsumsqxx, rsumsq_ppxx, residxx, resid1xx) = fitted_model.residual(&tall, &dall);
-
-        let i_viz: Vec<usize> = ipre
-            .iter()
-            .chain(etslice.iter().chain(ipost.iter()))
-            .map(|k| *k)
-            .collect();
-        //let res_pre : Vec<f64> = ipre.iter().map(|k|{resid1xx[*k]}).collect();
-        //let res_mod : Vec<f64> = etslice.iter().map( |k | { resid1xx[*k] }).collect();
-        //let res_post : Vec<f64> = ipost.iter().map(|k|{resid1xx[*k]}).collect();
-        ///////////////
-        /// form envelopes
-        let res_viz: Vec<f64> = i_viz.iter().map(|k| resid1xx[*k]).collect();
-        //let dat_viz : Vec<f64> = i_viz.iter().map(|k|{dall[*k]}).collect();
-        ////
-        /// m_3( m ) = sum_j^m( r[j] - <r[:m]> )^3 / ((m-1) [r[:m])
-        let mu3: Vec<f64> = (1..)
-            .take(res_viz.len() + 1)
-            .map(|m| {
-                let avg_resid: f64 = res_viz.iter().take(m).sum::<f64>() / (m as f64);
-                let resid_disp: Vec<f64> = res_viz.iter().take(m).map(|x| *x - avg_resid).collect();
-                let resid_disp_sq: Vec<f64> = resid_disp.iter().map(|x| (*x) * (*x)).collect();
-                let resid_disp_cu: Vec<f64> =
-                    resid_disp.iter().map(|x| (*x) * (*x) * (*x)).collect();
-                let sv = sqrt(resid_disp_sq.iter().sum::<f64>() / (m as f64));
-                let mut dnom: f64 = sv * sv * sv * ((m - 1) as f64);
-                if dnom <= 0.0 {
-                    dnom = 1.0;
-                }
-                let m_3: f64 = resid_disp_cu.iter().sum::<f64>() / (dnom);
-                m_3
-            })
-            .collect();
-
-        viz_lib2::plot_e(
-            &tall,
-            &mu3,
-            viz_lib2::PlotAction::PNG(
-                format!("{}_{}", output_stem.to_str().unwrap(), k).into(),
-                800,
-                600,
-                2.0,
-            ),
-            String::from(format!("model fit {} \n LSM {} ", k, lsm)),
-            String::from("time "),
-            String::from("skew"),
-        );
-        println!("writing file {}", output_stem.to_str().unwrap())
-    });
+            ..
+        } = fit_at(&VV, k)?;
+    
+        let stats = api::residual_stats(fitted_model, &Series::new(tall.clone(), dall.clone())?);
+
+        let PX = output_path(&path_output)?;
+        match window {
+            Some(window) => {
+                let rm = api::rolling_moments(&stats.residuals, window)?;
+                serialize_csv_res_window( PX, &tall, &stats.model, &dall, &stats.squared, &stats.residuals, &stats.running_skew, &rm)?
+            },
+            None => serialize_csv_res_data( PX, &tall, &stats.model, &dall, &stats.squared, &stats.residuals, &stats.running_skew)?,
+        }
+
+        println!("writing file {}", PX);
+        Ok(())
+    })
+
+}
+
+#[test]
+fn test_hub_csv_dates_and_incidence() {
+    use crate::main_mod_dm_fit::{data_fit_load_metadata, data_slice, EmbeddedSeries, FitSummary, TimeAxis, write_fit_package, AxisUnit};
+    use crate::main_mod_dm_migrate::PACKAGE_VERSION;
+    use crate::models::{ModelAdditive::ModelAdditive, ModelAffine::AffineAdditive, ModelConstant::ModelConstant, ParameterizedModel};
+    let model = AffineAdditive::new(ModelAdditive::new(vec![ModelTanh::new(50., 0.3, -3.)]), ModelConstant::new(0.));
+    // ten days of cumulative counts around the model, the residual paths can step down.
+    let time: Vec<f64> = (0..10).map(|t| t as f64).collect();
+    let value: Vec<f64> = time.iter().enumerate().map(|(k, t)| model.eval(*t) + if k % 2 == 0 { 20. } else { -20. }).collect();
+    let mut md = data_fit_load_metadata::new(String::from("/nonexistent/hub.csv"), data_slice::default(), String::from("count"));
+    md.time = Some(TimeAxis::new(String::from("date"), AxisUnit::Day, Some(String::from("2020-03-01"))));
+    md.data = Some(EmbeddedSeries { time, value });
+    let fit = model_fit { humps: 1, fitted_model: model.clone(), initial_model: model, residual_total: 0., residual_per_point: 0., diagnostics: Default::default() };
+    let pack = data_fit_package { version: PACKAGE_VERSION, load_metadata: md, fits: vec![fit], summary: FitSummary::default(), job: None };
+    let base = std::env::temp_dir().join(format!("humpty_hub_{}", std::process::id())).to_string_lossy().to_string();
+    write_fit_package(&format!("{base}.yml"), &pack).unwrap();
+
+    let hub = HubSpec { geo_value: String::from("ca"), forecaster: default_forecaster(), data_source: default_data_source(), signal: None, ahead: 3, cumulative: false };
+    let levels = ForecastQuantiles { levels: vec![0.05, 0.5, 0.95], spread: Spread::Residual };
+    hub_csv(format!("{base}.yml"), format!("{base}_hub"), 0, hub, Some(levels), None).unwrap();
+    let mut rdr = csv::Reader::from_path(format!("{base}_hub.csv")).unwrap();
+    let rows: Vec<(usize, f64, f64, String, String)> = rdr
+        .deserialize::<std::collections::HashMap<String, String>>()
+        .map(|r| {
+            let r = r.unwrap();
+            (r["ahead"].parse().unwrap(), r["quantile"].parse().unwrap(), r["value"].parse().unwrap(), r["forecast_date"].clone(), r["target_end_date"].clone())
+        })
+        .collect();
+    std::fs::remove_file(format!("{base}.yml")).unwrap();
+    std::fs::remove_file(format!("{base}_hub.csv")).unwrap();
+
+    assert_eq!(rows.len(), 9);
+    for (k, chunk) in rows.chunks(3).enumerate() {
+        // forecast from the last observed day, the first target is the day after.
+        assert!(chunk.iter().all(|r| r.0 == k + 1 && r.3 == "2020-03-10"));
+        assert_eq!(chunk[0].4, format!("2020-03-{}", 11 + k));
+        assert!(chunk.iter().all(|r| r.2 >= 0.));
+        assert!(chunk[0].2 <= chunk[1].2 && chunk[1].2 <= chunk[2].2);
+    }
+    // the alternating residuals give steps down as well as up at the low quantile.
+    assert_eq!(rows[0].2, 0.);
 }
-*/
\ No newline at end of file
@@ -1,41 +1,40 @@
-use std::{path::PathBuf, fs::File, io::BufReader, cmp::min};
+use std::{path::PathBuf, fs::File, io::BufReader, cmp::min, cmp::Ordering};
 
 use crate::models::{ModelAffine::AffineAdditive, ModelTanh::ModelTanh};
 use crate::models::ParameterizedModel;
 use crate::viz_lib::invert_tanh;
-use crate::main_mod_dm_fit::{reload_data, load_data, data_fit_load_metadata, data_fit_package, model_fit};
+use crate::main_mod_dm_fit::{reload_data, load_data, read_fit_package, data_fit_load_metadata, data_fit_package, model_fit};
+use crate::error::Result;
+use crate::api::explain;
+use crate::stats::{default_lags, residual_diagnostics};
 
 
-pub fn basic_explanation(models: String, top: usize) {
+pub fn basic_explanation(models: String, top: usize) -> Result<()> {
 
-    let mut path = PathBuf::from(models.as_str() );
-    path.set_extension("yml"); 
-    let mut data_file = File::open(path).expect("open model(s) file failed, please insure the extention is yml");
-    let mut reader = BufReader::new(data_file);
-    let VX : data_fit_package<ModelTanh> = serde_yaml::from_reader(reader).expect("problem reading yaml file");
-    let data_fit_package{ load_metadata: lmd,  fits: VV } = VX;
+    let VX : data_fit_package<ModelTanh> = read_fit_package(&models)?;
+    let data_fit_package{ load_metadata: lmd,  fits: VV, summary, .. } = VX;
     //let V:  Vec<(f64, AffineAdditive<ModelTanh>, AffineAdditive<ModelTanh>)>  
 
+    if summary.samples > 0 {
+        println!("solver: {} of {} restarts failed ({:.1}%)", summary.failed, summary.samples, 100. * summary.failure_rate );
+    }
     (0..min(top, VV.len())).for_each(
         |k|
         { 
-            let model_fit{ humps : humps,fitted_model : fitted_model, initial_model : initial_model, residual_total : residual_total,residual_per_point : residual_per_point} = &VV[k] ;
+            let model_fit{ humps : humps,fitted_model : fitted_model, initial_model : initial_model, residual_total : residual_total,residual_per_point : residual_per_point, diagnostics} = &VV[k] ;
             let lsm = residual_total; 
             let M = &fitted_model; 
             println!("rank.{k}, lsm.{lsm}, {:?}", M); 
+            println!("\tsolver: success.{}, termination.{}, evaluations.{}, |grad|.{}", diagnostics.success, diagnostics.termination, diagnostics.evaluations, diagnostics.gradient_norm );
         }
     );
+    Ok(())
 
 }
 
-pub fn intermediate_explanation(models: String, top: usize, pval: f64) {
-    let mut path = PathBuf::from(models.as_str() );
-
-    path.set_extension("yml"); 
-    let mut data_file = File::open(path).expect("open model(s) file failed, please insure the extention is yml");
-    let mut reader = BufReader::new(data_file);
-    let VX : data_fit_package<ModelTanh> = serde_yaml::from_reader(reader).expect("problem reading yaml file");
-    let data_fit_package{ load_metadata: lmd,  fits: VV } = VX;
+pub fn intermediate_explanation(models: String, top: usize, pval: f64) -> Result<()> {
+    let VX : data_fit_package<ModelTanh> = read_fit_package(&models)?;
+    let data_fit_package{ load_metadata: lmd,  fits: VV, .. } = VX;
     //let V:  Vec<(f64, AffineAdditive<ModelTanh>, AffineAdditive<ModelTanh>)>  
 
     let mut mathematica_code = String::new();
@@ -46,31 +45,37 @@ pub fn intermediate_explanation(models: String, top: usize, pval: f64) {
         |k|
         { 
 
-            let model_fit{ humps : humps,fitted_model : fitted_model, initial_model : initial_model, residual_total : residual_total,residual_per_point : residual_per_point} = &VV[k] ;
+            let model_fit{ humps : humps,fitted_model : fitted_model, initial_model : initial_model, residual_total : residual_total,residual_per_point : residual_per_point, ..} = &VV[k] ;
 
             let lsm = residual_total; 
             let M = &fitted_model; 
             println!("rank.{k}, lsm.{lsm}:");
-            let mut SortComp: Vec<ModelTanh> = M.tm.components.iter().map( |x| {x.clone()}).collect();
-            SortComp.sort_by( | c , d |{ (-c.beta/c.alpha ).partial_cmp( &(-d.beta/d.alpha) ).unwrap()} );
-            SortComp.iter().enumerate().for_each(
-                |(j, c )|
+            let explanation = explain( M );
+            explanation.humps.iter().enumerate().for_each(
+                |(j, h )|
                 {
-                    println!("\thump {j}\n\t\tκ = {},\n\t\tα = {},\n\t\tβ = {}", c.kappa, c.alpha, c.beta );
-                    let mut MQ: Vec<f64> = vec![invert_tanh( 0.25, c.alpha , c.beta ), invert_tanh( 0.5, c.alpha , c.beta ), invert_tanh( 0.75, c.alpha , c.beta ) ];
-                    MQ.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                    println!("\thump {j}\n\t\tκ = {},\n\t\tα = {},\n\t\tβ = {}", h.kappa, h.alpha, h.beta );
+                    if let Some( kd ) = h.kappa_data {
+                        println!("\t\tκ (data units) = {kd}");
+                    }
+                    if let Some( f ) = h.factor {
+                        println!("\t\t1 + y changes by e^(2κ) = {f} over the hump");
+                    }
+                    let MQ = h.motion_quantiles;
                     println!("\t\tmotion-quantiles: {} {} {}", MQ[0], MQ[1], MQ[2]); 
-                    let t_0 = -c.beta/c.alpha;
-                    let X_0 = c.kappa;
-                    let r = 2.*c.alpha;
-                    let K = 2.*c.kappa;
-                    println!("\t\tlogistic (alt parameters):\n\t\t\tt_0 = {},\n\t\t\tX_0 = {},\n\t\t\tr = {},\n\t\t\tK = {}", t_0, X_0, r, K );
-                    mathematica_code.push_str( format!( "\ng{k}x{j} = κ ( 1 + Tanh[α t + β ] ) /. {{ κ -> {}, α -> {}, β -> {} }} (* hump {k}x{j} *)", c.kappa, c.alpha, c.beta  ).as_str());
-                    matlab_code.push_str( format!( "\nfunction V = hump{k}x{j}( t )\n\tV= {} *( 1. + tanh({}*t + {} ) );\nend", c.kappa, c.alpha, c.beta ).as_str() );
+                    if let Some( axis ) = lmd.time_axis() {
+                        println!("\t\tcalendar: t_0 = {}, motion-quantiles: {} {} {}", axis.date( h.t0 ), axis.date( MQ[0] ), axis.date( MQ[1] ), axis.date( MQ[2] ));
+                    }
+                    println!("\t\tlogistic (alt parameters):\n\t\t\tt_0 = {},\n\t\t\tX_0 = {},\n\t\t\tr = {},\n\t\t\tK = {}", h.t0, h.logistic_x0, h.logistic_r, h.logistic_k );
+                    mathematica_code.push_str( format!( "\ng{k}x{j} = κ ( 1 + Tanh[α t + β ] ) /. {{ κ -> {}, α -> {}, β -> {} }} (* hump {k}x{j} *)", h.kappa, h.alpha, h.beta  ).as_str());
+                    matlab_code.push_str( format!( "\nfunction V = hump{k}x{j}( t )\n\tV= {} *( 1. + tanh({}*t + {} ) );\nend", h.kappa, h.alpha, h.beta ).as_str() );
                 }
             );
-            let constant_val = M.km.eval( 0. ); 
+            let constant_val = explanation.offset; 
             println!( "\toffset: K = {constant_val}");
+            if let Some( od ) = explanation.offset_data {
+                println!( "\toffset (data units): {od}");
+            }
 
             mathematica_code.push_str( format!( "\ng{k}x{} = K /. {{ K-> {} }} (* constant offset *)", M.tm.components.len(), constant_val  ).as_str());
             matlab_code.push_str( format!( "\nfunction V = hump{k}x{}(t)\n\tV = {} + 0.*t \nend", M.tm.components.len(), constant_val ).as_str() ); 
@@ -81,10 +86,50 @@ pub fn intermediate_explanation(models: String, top: usize, pval: f64) {
             mathematica_code.push_str( format!( "\nPlot[ {} , {{t, tstart, tend}}]" , (0..(M.tm.components.len() + 1)).map( |x| String::from( format!( "g{}x{}", k, x ))).collect::<Vec<String>>().join( " + " )  ).as_str());
             matlab_code.push_str( format!( "\nplot( TD, {} ) " , (0..(M.tm.components.len() + 1)).map( |x| String::from( format!( "hump{}x{}(TD)", k, x ))).collect::<Vec<String>>().join( " + " )  ).as_str());
  
+            if let Some( link ) = &lmd.transform {
+                println!( "(the code below is in the transformed space of {:?})", link );
+            }
             println!( "(* Mathematica Code *)\n{}", mathematica_code );
             println!( "// matlab or octave code\n{}", matlab_code );
          
         }
     );
+    Ok(())
 }
 
+
+/// formal residual tests of each model against the data it was fitted on, see `residual_diagnostics`.
+/// A model passes when no test rejects white noise at the level `alpha`.
+pub fn diagnostics_explanation(models: String, top: Option<usize>, alpha: f64, lags: Option<usize>) -> Result<()> {
+    let VX : data_fit_package<ModelTanh> = read_fit_package(&models)?;
+    let data_fit_package{ load_metadata: lmd,  fits: VV, .. } = VX;
+    let ((_, tsplice), dsplice, _) = reload_data( lmd )?;
+    let (tsplice, dsplice) : (Vec<f64>, Vec<f64>) = (tsplice.as_slice().to_vec(), dsplice.as_slice().to_vec());
+    let lags = lags.unwrap_or( default_lags( dsplice.iter().filter( |d| d.is_finite() ).count() ));
+
+    let mut passed = 0;
+    let shown = min(top.unwrap_or(VV.len()), VV.len());
+    (0..shown).for_each(
+        |k|
+        {
+            let model_fit{ fitted_model, residual_total, .. } = &VV[k];
+            let (_, _, _, resid1) = fitted_model.residual( &tsplice, &dsplice );
+            let model : Vec<f64> = tsplice.iter().map( |t| fitted_model.eval( *t ) ).collect();
+            let tests = residual_diagnostics( &resid1, &model, lags );
+            // Bonferroni: the model fails at level alpha when any of the tests fails at alpha / tests.
+            let level = alpha / tests.len() as f64;
+            let verdicts : Vec<Option<bool>> = tests.iter().map( |d| d.passes( level ) ).collect();
+            let pass = verdicts.iter().all( |v| *v != Some( false ) );
+            if pass {
+                passed += 1;
+            }
+            println!("rank.{k}, lsm.{residual_total}: {} at level {alpha} (Bonferroni, {level:.4} per test)", if pass { "PASS" } else { "FAIL" } );
+            tests.iter().zip( verdicts ).for_each( |(d, v)| {
+                let verdict = match v { Some( true ) => "pass", Some( false ) => "FAIL", None => "n/a" };
+                println!("\t{:<16} {:<24} statistic {:<12.6} p {:<10.4e} {verdict}", d.name, d.checks, d.statistic, d.p_value );
+            });
+        }
+    );
+    println!("{passed} of {shown} models pass");
+    Ok(())
+}
@@ -1,31 +1,261 @@
-use std::{cmp::min, path::{Path, PathBuf}, io::BufWriter};
+use std::{cmp::min, collections::BTreeMap, path::{Path, PathBuf}, io::BufWriter};
 use nalgebra::{DVector, Matrix, Dyn, Const};
 use ndarray::{Array, s};
 use csv::Writer;
-use polars::prelude::{CsvReader, PolarsResult, DataFrame, SerReader};
+use polars::prelude::{DataFrame, DataType, ChunkApply, IntoSeries, TimeUnit, Utf8Methods};
 use rayon::prelude::IntoParallelIterator;
 use std::collections::BinaryHeap;
 use std::cmp::Reverse;
 use rayon::iter::ParallelIterator;
 use std::fs::File;
-use std::io::Write;
-use crate::models::{ModelAffine::AffineAdditive, ModelTanh::ModelTanh, ParameterizedModel, VarProAdapter};
+use std::io::{BufReader, Write};
+use crate::models::{ModelAffine::{AffineAdditive, FitDiagnostics}, ModelTanh::ModelTanh, ParameterizedModel, VarProAdapter, nan_if_null};
 
 use serde::{Serialize, Deserialize};
+use crate::error::{HumptyError, Result};
+use crate::api::{self, FitConfig, FitResult, Series};
+use crate::main_mod_dm_run::JobSpec;
+use crate::ingest::{content_hash, GroupKey, group_values, read_input, WideTable, InputFormat};
+use crate::resample::{apply_steps, SeriesStep};
+use crate::transform::ValueTransform;
+use crate::main_mod_dm_migrate::{default_package_version, upgrade_package, PACKAGE_VERSION};
 
 
-#[derive(Debug, Serialize, Deserialize, Clone )]
+#[derive(Debug, Serialize, Deserialize, Clone, Default )]
 pub struct data_slice{
-    offset: Option<i64>,
-    limit: Option<usize>,
-    strides : Option<usize>,
+    pub offset: Option<i64>,
+    pub limit: Option<usize>,
+    pub strides : Option<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone )]
 pub struct data_fit_load_metadata{
-    input : String,  //this should probably be a global path or URI
-    slice : data_slice, 
-    colname : String 
+    pub input : String,  //this should probably be a global path or URI
+    pub slice : data_slice, 
+    pub colname : String,
+    /// mapping from a date column to the numeric time axis, absent when time is the row index.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time : Option<TimeAxis>,
+    /// the input is a wide table melted into a `date` column and `colname`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wide : Option<WideTable>,
+    /// only the rows of this group are loaded (long tables fitted with --group-by).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group : Option<GroupKey>,
+    /// resampling, differencing and cumulating applied in order after the rows are selected, see `SeriesStep`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps : Vec<SeriesStep>,
+    /// format of the input, detected when the data is first read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format : Option<InputFormat>,
+    /// content hash of the input when it was fitted, a later mismatch is reported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_hash : Option<String>,
+    /// the prepared series (group, wide, steps applied, missing rows dropped, data units) the slice is taken from,
+    /// when embedded it is used instead of reading the input again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data : Option<EmbeddedSeries>,
+    /// value transform the fits were made under, with its parameters taken from the training slice.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform : Option<ValueTransform>,
+}
+
+impl data_fit_load_metadata {
+    pub fn new( input : String, slice : data_slice, colname : String ) -> Self {
+        Self { input, slice, colname, time: None, wide: None, group: None, steps: Vec::new(), format: None, source_hash: None, data: None, transform: None }
+    }
+
+    pub fn time_axis( &self ) -> Option<&TimeAxis> {
+        self.time.as_ref()
+    }
+
+    /// read the input (recording its format) and bring it into one row per observation.
+    /// warns when the input no longer matches the recorded hash.
+    pub fn read_frame( &mut self ) -> Result<DataFrame> {
+        let ( df, format, hash ) = read_input( &self.input, self.format )?;
+        self.format = Some( format );
+        self.check_source( hash );
+        self.prepare_frame( df )
+    }
+
+    /// record the hash of a freshly read input, or warn when it differs from the recorded one.
+    fn check_source( &mut self, hash : String ) {
+        match &self.source_hash {
+            Some( recorded ) if *recorded != hash =>
+                eprintln!( "WARNING {} changed since the fit (content hash mismatch), the reloaded series may differ from the training series; fit with --embed to keep it.", self.input ),
+            Some( _ ) => {},
+            None => self.source_hash = Some( hash ),
+        }
+    }
+
+    /// the date column, `date` for wide tables unless another one is given.
+    pub fn time_column( &self ) -> Option<&str> {
+        match ( &self.time, &self.wide ) {
+            ( Some( t ), _ ) => Some( t.column.as_str() ),
+            ( None, Some( _ ) ) => Some( "date" ),
+            ( None, None ) => None,
+        }
+    }
+
+    /// keep the group rows, melt a wide table, then run the resampling steps.
+    pub fn prepare_frame( &self, df : DataFrame ) -> Result<DataFrame> {
+        let df = match &self.group {
+            Some( group ) => group.filter( &df )?,
+            None => df,
+        };
+        let df = match &self.wide {
+            Some( wide ) => wide.melt( &df, &self.colname )?,
+            None => df,
+        };
+        apply_steps( &self.steps, df, &self.colname, self.time_column() )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default )]
+#[serde(rename_all = "lowercase")]
+pub enum AxisUnit {
+    Second,
+    Minute,
+    Hour,
+    #[default]
+    Day,
+    Week,
+}
+
+impl AxisUnit {
+    pub fn seconds( &self ) -> f64 {
+        match self {
+            AxisUnit::Second => 1.,
+            AxisUnit::Minute => 60.,
+            AxisUnit::Hour => 3600.,
+            AxisUnit::Day => 86400.,
+            AxisUnit::Week => 7. * 86400.,
+        }
+    }
+}
+
+impl std::str::FromStr for AxisUnit {
+    type Err = HumptyError;
+    fn from_str( s : &str ) -> Result<Self> {
+        match s {
+            "second" => Ok( AxisUnit::Second ),
+            "minute" => Ok( AxisUnit::Minute ),
+            "hour" => Ok( AxisUnit::Hour ),
+            "day" => Ok( AxisUnit::Day ),
+            "week" => Ok( AxisUnit::Week ),
+            _ => Err( HumptyError::parse( "time unit", format!( "'{s}' is not one of second, minute, hour, day, week" ))),
+        }
+    }
+}
+
+////////////////
+/// time column -> numeric time:
+///     date or datetime column:  t = ( date - origin ) / unit
+///     numeric column:           t = value - origin  (the values are already in their own unit)
+///     the origin defaults to the first time in the column and is recorded when the data is fitted,
+///     so every later reload maps the column to the same t.  Rows without a time are dropped.
+#[derive(Debug, Serialize, Deserialize, Clone )]
+pub struct TimeAxis {
+    pub column : String,
+    #[serde(default)]
+    pub unit : AxisUnit,
+    #[serde(default)]
+    pub origin : Option<String>,
+    /// the column holds plain numbers rather than dates (set when the axis is resolved).
+    #[serde(default)]
+    pub numeric : bool,
+}
+
+impl TimeAxis {
+    pub fn new( column : String, unit : AxisUnit, origin : Option<String> ) -> Self {
+        Self { column, unit, origin, numeric: false }
+    }
+
+    /// raw values of the time column: seconds since the unix epoch for dates, the values themselves
+    /// for numeric columns (second element true), None for missing entries.
+    fn raw_times( &self, df : &DataFrame ) -> Result<(Vec<Option<f64>>, bool)> {
+        let column = df.column( &self.column ).map_err( |_| HumptyError::MissingColumn {
+            column: self.column.clone(),
+            available: df.get_column_names().iter().map( |x| x.to_string() ).collect() })?;
+        let column = match column.dtype() {
+            // monthly data such as 1935-02 is read as the first of the month.
+            DataType::Utf8 => column.utf8()?.as_date( None, false )
+                .or_else( |e| column.utf8()?.apply( |v| std::borrow::Cow::Owned( format!( "{v}-01" ) ) ).as_date( Some( "%Y-%m-%d" ), false ).map_err( |_| e ) )
+                .map_err( |e| HumptyError::parse( format!( "date column {}", self.column ), e ))?
+                .into_series(),
+            _ => column.clone(),
+        };
+        let scale = match column.dtype() {
+            DataType::Date => 86400.,
+            DataType::Datetime( TimeUnit::Nanoseconds, _ ) => 1e-9,
+            DataType::Datetime( TimeUnit::Microseconds, _ ) => 1e-6,
+            DataType::Datetime( TimeUnit::Milliseconds, _ ) => 1e-3,
+            other if other.is_numeric() => 1.,
+            other => return Err( HumptyError::parse( format!( "time column {}", self.column ), format!( "expected a date, datetime or number, found {other}" ))),
+        };
+        let numeric = column.dtype().is_numeric();
+        let raw = column.to_physical_repr().cast( &DataType::Float64 )?;
+        Ok(( raw.f64()?.into_iter().map( |v| v.filter( |v| v.is_finite() ).map( |v| v * scale ) ).collect(), numeric ))
+    }
+
+    /// fill in the origin from the first time when none was given.
+    pub fn resolve( &self, df : &DataFrame ) -> Result<Self> {
+        let mut resolved = self.clone();
+        let ( raw, numeric ) = self.raw_times( df )?;
+        resolved.numeric = numeric;
+        if resolved.origin.is_none() {
+            let first = raw.iter().flatten().next().copied().ok_or_else(
+                || HumptyError::parse( format!( "time column {}", self.column ), "no rows with a time" ))?;
+            resolved.origin = Some( if numeric { format!( "{first}" ) } else { format_epoch_seconds( first ) } );
+        }
+        resolved.origin_value()?;
+        Ok( resolved )
+    }
+
+    /// the origin as a raw time (seconds since the epoch, or a plain number for numeric columns).
+    fn origin_value( &self ) -> Result<f64> {
+        match &self.origin {
+            Some( origin ) if self.numeric => origin.parse::<f64>().map_err( |e| HumptyError::parse( "time origin", e ) ),
+            Some( origin ) => parse_epoch_seconds( origin ),
+            None => Err( HumptyError::parse( "time axis", "the origin has not been resolved" )),
+        }
+    }
+
+    /// the numeric time axis for every row of the data frame, NaN where the time is missing.
+    pub fn times( &self, df : &DataFrame ) -> Result<Vec<f64>> {
+        let origin = self.origin_value()?;
+        let unit = if self.numeric { 1. } else { self.unit.seconds() };
+        Ok( self.raw_times( df )?.0.iter().map( |s| match s { Some( s ) => ( s - origin ) / unit, None => f64::NAN } ).collect() )
+    }
+
+    /// calendar date at numeric time t, with the time of day for sub-day units (the column value for numeric columns).
+    pub fn date( &self, t : f64 ) -> String {
+        match self.origin_value() {
+            Ok( origin ) if self.numeric => format!( "{}", origin + t ),
+            Ok( origin ) if self.unit.seconds() >= 86400. => format_epoch_seconds( ( ( origin + t * self.unit.seconds() ) / 86400. ).floor() * 86400. ),
+            Ok( origin ) => format_epoch_seconds( origin + t * self.unit.seconds() ),
+            Err( _ ) => format!( "{t}" ),
+        }
+    }
+}
+
+fn parse_epoch_seconds( s : &str ) -> Result<f64> {
+    if let Ok( d ) = chrono::NaiveDate::parse_from_str( s, "%Y-%m-%d" ) {
+        return Ok( d.and_hms_opt( 0, 0, 0 ).unwrap().and_utc().timestamp() as f64 )
+    }
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S"].iter()
+        .find_map( |f| chrono::NaiveDateTime::parse_from_str( s, f ).ok() )
+        .map( |d| d.and_utc().timestamp() as f64 )
+        .ok_or_else( || HumptyError::parse( "time origin", format!( "'{s}' is not YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS" )))
+}
+
+fn format_epoch_seconds( s : f64 ) -> String {
+    let whole = s.round() as i64;
+    match chrono::DateTime::from_timestamp( whole, 0 ) {
+        Some( d ) if whole % 86400 == 0 => d.format( "%Y-%m-%d" ).to_string(),
+        Some( d ) => d.format( "%Y-%m-%dT%H:%M:%S" ).to_string(),
+        None => format!( "{s}" ),
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone )]
@@ -33,14 +263,52 @@ pub struct model_fit<M> where M: ParameterizedModel + Clone  + VarProAdapter {
     pub humps : usize,
     pub fitted_model : AffineAdditive<M>,
     pub initial_model : AffineAdditive<M>,
+    #[serde(deserialize_with = "nan_if_null")]
     pub residual_total : f64, 
+    #[serde(deserialize_with = "nan_if_null")]
     pub residual_per_point : f64,
+    #[serde(default)]
+    pub diagnostics : FitDiagnostics,
 } 
 
+/// failure rates over all random restarts of a fit, not only the reported ones.
+#[derive(Debug, Serialize, Deserialize, Clone, Default )]
+pub struct FitSummary {
+    pub samples : usize,
+    pub succeeded : usize,
+    pub failed : usize,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub failure_rate : f64,
+    pub terminations : BTreeMap<String, usize>,
+}
+
+impl FitSummary {
+    pub fn from_diagnostics<'a>( diagnostics : impl Iterator<Item = &'a FitDiagnostics> ) -> Self {
+        let mut summary = FitSummary::default();
+        diagnostics.for_each( |d| {
+            summary.samples += 1;
+            if d.success { summary.succeeded += 1 } else { summary.failed += 1 }
+            *summary.terminations.entry( d.termination.clone() ).or_insert(0) += 1;
+        });
+        if summary.samples > 0 {
+            summary.failure_rate = summary.failed as f64 / summary.samples as f64;
+        }
+        summary
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone )]
 pub struct data_fit_package<M> where M: ParameterizedModel + Clone  + VarProAdapter  {
+    /// layout version, see `PACKAGE_VERSION`; packages without it are version 1.
+    #[serde(default = "default_package_version")]
+    pub version : u32,
     pub load_metadata : data_fit_load_metadata,
-    pub fits : Vec<model_fit<M>>
+    pub fits : Vec<model_fit<M>>,
+    #[serde(default)]
+    pub summary : FitSummary,
+    /// the resolved job specification when the package was produced by `humpty run`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job : Option<JobSpec>,
 }
 ///////////////////
 /// TODO here the data_fit_package can furnish several types of anlaysis.
@@ -50,49 +318,194 @@ pub struct data_fit_package<M> where M: ParameterizedModel + Clone  + VarProAdap
 ///     Finding the best of each hump index, and presented with a cost function (in hump index) calculate the best regularized model.
 /// 
 
-pub fn readcsv(path : &String) -> PolarsResult<DataFrame> { 
-    let dataset = CsvReader::from_path(path)?
-                                .has_header(true)
-                                //.with_dtypes(Some(Arc::new( schema)) )
-                                //.with_try_parse_dates(true)
-                                .finish();
-    dataset
+pub fn readcsv(path : &String) -> Result<DataFrame> { 
+    Ok( read_input( path, Some( InputFormat::Csv ) )?.0 )
+}
+
+/// on-disk format of a fit package, taken from the file extension.
+#[derive(Debug, Clone, Copy, PartialEq )]
+pub enum PackageFormat {
+    Yaml,
+    Json,
+    /// MessagePack with field names, compact and fast to parse.
+    MsgPack,
+}
+
+impl PackageFormat {
+    pub fn extension( &self ) -> &'static str {
+        match self {
+            PackageFormat::Yaml => "yml",
+            PackageFormat::Json => "json",
+            PackageFormat::MsgPack => "msgpack",
+        }
+    }
+}
+
+/// path and format of a fit package: .yml/.yaml, .json and .msgpack/.mpk are kept, any other (or no) extension becomes .yml.
+pub fn package_path( models : &str ) -> (PathBuf, PackageFormat) {
+    let mut path = PathBuf::from( models );
+    let format = match path.extension().and_then( |e| e.to_str() ).map( |e| e.to_ascii_lowercase() ).as_deref() {
+        Some( "yml" | "yaml" ) => return ( path, PackageFormat::Yaml ),
+        Some( "json" ) => PackageFormat::Json,
+        Some( "msgpack" | "mpk" ) => PackageFormat::MsgPack,
+        _ => {
+            path.set_extension( "yml" );
+            PackageFormat::Yaml
+        }
+    };
+    ( path, format )
+}
+
+/// read a fit package written by `model_curve_fitting` (yaml, json or msgpack by extension, .yml when there is none),
+/// older layouts are migrated in memory (see `upgrade_package`).
+pub fn read_fit_package( models : &String ) -> Result<data_fit_package<ModelTanh>> {
+    let ( value, display ) = read_package_value( models )?;
+    let ( package, version ) = upgrade_package( value, &display )?;
+    if version < PACKAGE_VERSION {
+        eprintln!( "note: {display} has package version {version}, migrated in memory (humpty migrate updates the file)." );
+    }
+    Ok( package )
+}
+
+/// parse a package file of any format without interpreting its layout, returns it with the path read.
+pub fn read_package_value( models : &str ) -> Result<(serde_yaml::Value, String)> {
+    let ( path, format ) = package_path( models );
+    let display = path.to_string_lossy().to_string();
+    let data_file = File::open(&path).map_err( |e| HumptyError::io( display.clone(), e ))?;
+    let reader = BufReader::new(data_file);
+    let parsed : std::result::Result<serde_yaml::Value, String> = match format {
+        PackageFormat::Yaml => serde_yaml::from_reader( reader ).map_err( |e| e.to_string() ),
+        PackageFormat::Json => serde_json::from_reader( reader ).map_err( |e| e.to_string() ),
+        PackageFormat::MsgPack => rmp_serde::from_read( reader ).map_err( |e| e.to_string() ),
+    };
+    Ok(( parsed.map_err( |e| HumptyError::parse( display.clone(), e ))?, display ))
+}
+
+/// write a fit package in the format of the extension of `output` (see `package_path`).
+pub fn write_fit_package( output : &String, model_pack : &data_fit_package<ModelTanh> ) -> Result<()> {
+    let ( path, format ) = package_path( output );
+    let display = path.to_string_lossy().to_string();
+    let data_file = File::create(&path).map_err( |e| HumptyError::io( display.clone(), e ))?;
+    let mut writer = BufWriter::new(data_file);
+    match format {
+        PackageFormat::Yaml => serde_yaml::to_writer( &mut writer, model_pack ).map_err( |e| e.to_string() ),
+        PackageFormat::Json => serde_json::to_writer( &mut writer, model_pack ).map_err( |e| e.to_string() ),
+        PackageFormat::MsgPack => rmp_serde::encode::write_named( &mut writer, model_pack ).map_err( |e| e.to_string() ),
+    }.map_err( |e| HumptyError::parse( display.clone(), e ))?;
+    writer.flush().map_err( |e| HumptyError::io( display, e ))?;
+    Ok(())
+}
+
+/// series stored in the package by `fit --embed`, `time` is the numeric time axis.
+#[derive(Debug, Serialize, Deserialize, Clone, Default )]
+pub struct EmbeddedSeries {
+    pub time : Vec<f64>,
+    pub value : Vec<f64>,
+}
+
+////////////////
+///
+/// `fit --group-by` writes one package per group and this index next to them,
+/// `viz`, `csv` and `exp` take the index with `--group NAME` to address one of them.
+#[derive(Debug, Serialize, Deserialize, Clone )]
+pub struct GroupIndex {
+    pub column : String,
+    /// group value -> package file.
+    pub groups : BTreeMap<String, String>,
+}
+
+pub fn read_group_index( index : &String ) -> Result<GroupIndex> {
+    let mut path = PathBuf::from( index.as_str() );
+    path.set_extension("yml");
+    let display = path.to_string_lossy().to_string();
+    let text = std::fs::read_to_string( &path ).map_err( |e| HumptyError::io( display.clone(), e ))?;
+    serde_yaml::from_str( &text ).map_err( |e| HumptyError::parse( display, e ))
+}
+
+/// the package file of `group` listed in the group index.
+pub fn group_package( index : &String, group : &str ) -> Result<String> {
+    let gi = read_group_index( index )?;
+    gi.groups.get( group.trim() ).cloned().ok_or_else( || HumptyError::parse( format!( "group index {index}" ),
+        format!( "no {} '{}', available: {}", gi.column, group, gi.groups.keys().cloned().collect::<Vec<_>>().join( ", " ) )))
+}
+
+/// file name friendly form of a group value, e.g. "Los Angeles County " -> "Los_Angeles_County".
+fn group_file_stem( group : &str ) -> String {
+    let stem : String = group.trim().chars().map( |c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' } ).collect();
+    stem.trim_matches( '_' ).to_string()
 }
 
-pub fn reload_data( ds : data_fit_load_metadata ) -> (
+/// select the fit at `index` from a package.
+pub fn fit_at<M>( fits : &[model_fit<M>], index : usize ) -> Result<&model_fit<M>> 
+where M: ParameterizedModel + Clone + VarProAdapter {
+    fits.get( index ).ok_or( HumptyError::MissingModel { index, available: fits.len() })
+}
+
+pub fn reload_data( ds : data_fit_load_metadata ) -> Result<(
     ( Matrix<f64, Dyn, Const<1>, nalgebra::VecStorage<f64, Dyn, Const<1>>> , Matrix<f64, Dyn, Const<1>, nalgebra::VecStorage<f64, Dyn, Const<1>>> ),
     Matrix<f64, Dyn, Const<1>, nalgebra::VecStorage<f64, Dyn, Const<1>>>,
-    ( ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>>, ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>> )){
-    let data_fit_load_metadata{ input, slice , colname }: data_fit_load_metadata= ds;
-    let data_slice{ offset, limit, strides } = slice; 
-    load_data( &input, offset, limit, strides, Some( &colname ))
+    ( ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>>, ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>> ))>{
+    let mut ds = ds;
+    let data_slice{ offset, limit, strides } = ds.slice.clone(); 
+    if let Some( data ) = ds.data.take() {
+        // the embedded series wins, the raw bytes of the source are only hashed to check it (stdin is not read again).
+        let bytes = if ds.input == "-" { None } else { std::fs::read( &ds.input ).ok() };
+        if let Some( bytes ) = bytes {
+            if ds.source_hash.as_ref().is_some_and( |h| *h != content_hash( &bytes ) ) {
+                eprintln!( "WARNING {} changed since the fit, using the series embedded in the package.", ds.input );
+            }
+        } else {
+            eprintln!( "note: {} is not read, using the series embedded in the package.", ds.input );
+        }
+        return slice_series( data.time.into_iter().collect(), data.value.into_iter().collect(), &ds.input, offset, limit, strides )
+    }
+    let df = ds.read_frame()?;
+    let data_fit_load_metadata{ input, colname, time, .. }: data_fit_load_metadata= ds;
+    load_frame( &df, &input, offset, limit, strides, Some( &colname ), time.as_ref() )
+}
+
+/// numeric (float or integer) column to f64, missing values become NaN.
+///     a text column is accepted when some of it parses as numbers (e.g. gaps marked NA), the rest is missing.
+pub(crate) fn column_to_f64( column : &polars::prelude::Series ) -> Result<ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>>> {
+    let not_numeric = || HumptyError::parse( format!( "column {}", column.name() ), format!( "expected float or int data, found {}", column.dtype() ));
+    let cast = match column.dtype() {
+        DataType::Utf8 => column.cast( &DataType::Float64 ).map_err( |_| not_numeric() )?,
+        dtype if dtype.is_numeric() => column.cast( &DataType::Float64 )?,
+        _ => return Err( not_numeric() ),
+    };
+    if cast.null_count() == cast.len() && column.null_count() < column.len() {
+        return Err( not_numeric() )
+    }
+    Ok( cast.f64()?.into_iter().map( |v| v.unwrap_or( f64::NAN ) ).collect() )
 }
 
 pub fn load_data( input : &String,
     offset: Option<i64>,
     limit: Option<usize>,
     strides : Option<usize>,
-    colname : Option<&String> ) -> (
+    colname : Option<&String>,
+    time : Option<&TimeAxis> ) -> Result<(
         ( Matrix<f64, Dyn, Const<1>, nalgebra::VecStorage<f64, Dyn, Const<1>>> , Matrix<f64, Dyn, Const<1>, nalgebra::VecStorage<f64, Dyn, Const<1>>> ),
         Matrix<f64, Dyn, Const<1>, nalgebra::VecStorage<f64, Dyn, Const<1>>>,
-        ( ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>>, ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>> ))
+        ( ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>>, ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>> ))>
+    {
+        let df = read_input( input, None )?.0;
+        load_frame( &df, input, offset, limit, strides, colname, time )
+}
+
+/// as `load_data`, on a data frame that is already read.
+pub fn load_frame( df : &DataFrame,
+    input : &String,
+    offset: Option<i64>,
+    limit: Option<usize>,
+    strides : Option<usize>,
+    colname : Option<&String>,
+    time : Option<&TimeAxis> ) -> Result<(
+        ( Matrix<f64, Dyn, Const<1>, nalgebra::VecStorage<f64, Dyn, Const<1>>> , Matrix<f64, Dyn, Const<1>, nalgebra::VecStorage<f64, Dyn, Const<1>>> ),
+        Matrix<f64, Dyn, Const<1>, nalgebra::VecStorage<f64, Dyn, Const<1>>>,
+        ( ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>>, ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>> ))>
     {
         let verbose = false; 
-        let df = readcsv( &input ).expect("problem opening file ...");
-        let offset_data : i64 = match offset{
-            Some(skip) => skip.clone(),
-            None => 0,
-        };
-        let limit_data = match limit{
-            Some(x) => min( offset_data as usize + x ,df.height()),
-            None => df.height(),
-        };
-        let strides_data = match strides{
-            Some(x) => x.clone(),
-            None => 1,
-        };
-        //let df = df.slice( offset_data as i64, limit_data ); 
-        // the data frame is now trimmed ..
         let data_column = match colname {
             Some(X) => X.clone(),
             None => String::from( "count" ),
@@ -100,30 +513,66 @@ pub fn load_data( input : &String,
         if verbose { println!(" df {:?}", df ); }
         // POLARS Data frame to NDARRAY
         
-        let DFULL: ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>> = match df.column( &data_column ) {
-            Ok(CD) => CD.f64().unwrap().to_ndarray().unwrap().to_owned(),
+        let DRAW: ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>> = match df.column( &data_column ) {
+            Ok(CD) => column_to_f64( CD )?,
             Err(_) => { 
-                println!( "ERROR no column by name {data_column}, instead will use the last data column by default.");
-                let P1 = df.get_columns().last().unwrap().f64();
-                match( &P1 ){
-                    Ok(x) => x.to_ndarray().unwrap().to_owned(),
-                    Err(_) => {
-                        let P2 = df.get_columns().last().unwrap().i64();
-                        match( P2 ){
-                            Ok(x2) => { 
-                                let x3 = x2.to_ndarray().unwrap();
-                                let x4 = x3.mapv(|x| x as f64 );
-                                x4
-                            },
-                            Err(_) => panic!( " ERROR column (last column) data appears to be neither float nor int"),
-                        }
+                let available : Vec<String> = df.get_column_names().iter().map( |x| x.to_string() ).collect();
+                match df.get_columns().last().filter( |c| c.dtype().is_numeric() && ( time.map_or( true, |t| t.column != c.name() ) ) ) {
+                    Some( last ) => {
+                        eprintln!( "WARNING no column by name {data_column}, instead will use the last data column ({}) by default.", last.name() );
+                        column_to_f64( last )?
                     },
+                    None => return Err( HumptyError::MissingColumn { column: data_column, available }),
                 }
             }
         }; 
+        let NRAW = DRAW.shape()[0];
+        // time is taken before missing rows are dropped, so observations keep their true position (row index or time column).
+        let TRAW: Vec<f64> = match time {
+            Some( axis ) => axis.times( df )?,
+            None => (0..NRAW).map( |x| x as f64 ).collect(),
+        };
+        let keep: Vec<usize> = (0..NRAW).filter( |k| DRAW[*k].is_finite() && TRAW[*k].is_finite() ).collect();
+        if keep.len() < NRAW {
+            eprintln!( "WARNING dropped {} of {NRAW} rows with a missing value or time in {input}.", NRAW - keep.len() );
+        }
+        let DFULL: ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>> = keep.iter().map( |k| DRAW[*k] ).collect();
+        let TFULL: ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>> = keep.iter().map( |k| TRAW[*k] ).collect();
+        slice_series( TFULL, DFULL, input, offset, limit, strides )
+}
+
+/// apply offset, limit and strides to the full (time, value) series of `load_frame`, or to an embedded series.
+pub fn slice_series( TFULL : ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>>,
+    DFULL : ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>>,
+    input : &String,
+    offset: Option<i64>,
+    limit: Option<usize>,
+    strides : Option<usize> ) -> Result<(
+        ( Matrix<f64, Dyn, Const<1>, nalgebra::VecStorage<f64, Dyn, Const<1>>> , Matrix<f64, Dyn, Const<1>, nalgebra::VecStorage<f64, Dyn, Const<1>>> ),
+        Matrix<f64, Dyn, Const<1>, nalgebra::VecStorage<f64, Dyn, Const<1>>>,
+        ( ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>>, ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>> ))>
+    {
         let NFULL = DFULL.shape()[0];
-        let TFULL: ndarray::ArrayBase<ndarray::OwnedRepr<f64>, ndarray::Dim<[usize; 1]>>  = Array::linspace( 0., (NFULL-1) as f64, NFULL );
 
+        // offset, limit and strides count the observations that are kept.
+        let offset_data : i64 = match offset{
+            Some(skip) => skip.clone(),
+            None => 0,
+        };
+        if offset_data < 0 || offset_data as usize >= NFULL {
+            return Err( HumptyError::UnsupportedSlice( format!( "offset {offset_data} is outside the {NFULL} observations of {input}" )))
+        }
+        let limit_data = match limit{
+            Some(x) => min( (offset_data as usize).saturating_add( x ) ,NFULL),
+            None => NFULL,
+        };
+        let strides_data = match strides{
+            Some(x) => x.clone(),
+            None => 1,
+        };
+        if strides_data == 0 {
+            return Err( HumptyError::UnsupportedSlice( String::from( "strides must be at least 1" )))
+        }
 
         let DSLICE = DFULL.slice(s![(offset_data as usize)..(limit_data); strides_data]);
         let NSLICE = DSLICE.shape()[0];
@@ -136,9 +585,9 @@ pub fn load_data( input : &String,
         let tsplice: Matrix<f64, Dyn, Const<1>, nalgebra::VecStorage<f64, Dyn, Const<1>>> = DVector::from_vec( TSLICE.to_vec() );
         let dslice = DVector::from_vec( DSLICE.to_vec());
         let tslice :  Matrix<f64, Dyn, Const<1>, nalgebra::VecStorage<f64, Dyn, Const<1>>> = DVector::from_vec( (0..NSLICE).map(|x|{x as f64}).collect() );
-        (   (tslice, tsplice ), // tslice is indexing for dslice, tsplice is indexing in D. 
+        Ok((   (tslice, tsplice ), // tslice is indexing for dslice, tsplice is indexing in D. 
             dslice,               // spliced time (indexes of full time .. ( k...N+k) and sliced data), 
-            (TFULL, DFULL.to_owned()))  // full time full data
+            (TFULL, DFULL.to_owned())))  // full time full data
 }
 
 pub fn model_curve_fitting( input : String, 
@@ -149,62 +598,183 @@ pub fn model_curve_fitting( input : String,
                             data_column: &String,
                             offset: Option<i64>,
                             limit: Option<usize>,
-                            strides : Option<usize>
-                        ){
-    let ds = data_slice{ offset: offset.clone(), limit: limit.clone(), strides: strides.clone()};
-    let md: data_fit_load_metadata = data_fit_load_metadata{ input:input.clone(), slice:ds , colname: data_column.clone()};
-    let (( tspan ,texact), dspan  , (tspanfull, dspanfull)) = load_data(&input,offset,limit,strides, Some( &data_column.clone()) );
-    //let N = dspan.shape().0;
-    let mut parlist = (0..samples).into_par_iter().map(
-        |k|
-        {
-            let mut m2 = AffineAdditive::<ModelTanh>::random_model_given_humps( humps, &mut rand::thread_rng() );  
-            let m2init = m2.clone();
-            m2.curve_fit(&texact, &dspan);
-            let ( rsumsq,rsumsq_pp,resid , resid1) = m2.residual_mat(&texact, &dspan); 
-            (rsumsq, rsumsq_pp, m2, m2init)
-        });
-    let mut list  : Vec<(f64, f64, AffineAdditive<ModelTanh>, AffineAdditive<ModelTanh>)> = parlist.collect();
-
-    list.sort_by(
-        |a, b| 
-        a.0.partial_cmp(&b.0).unwrap()
-    );
-
-    (0..reports).for_each(
-        |k|
-        {
-            println!( "{:#?}", list[k] );
+                            strides : Option<usize>,
+                            time : Option<TimeAxis>,
+                            wide : Option<WideTable>,
+                            group_by : Option<String>,
+                            steps : Vec<SeriesStep>,
+                            transform : Option<ValueTransform>,
+                            format : Option<InputFormat>,
+                            embed : bool,
+                            keep_failed : bool
+                        ) -> Result<()> {
+    let mut md = data_fit_load_metadata::new( input, data_slice{ offset, limit, strides }, data_column.clone() );
+    md.time = time;
+    md.wide = wide;
+    md.steps = steps;
+    md.transform = transform;
+    md.format = format;
+    let config = FitConfig::default().humps( humps ).samples( samples ).reports( reports ).keep_failed( keep_failed );
+    match group_by {
+        Some( column ) => grouped_curve_fitting( md, &output, &config, &column, embed ),
+        None => {
+            let model_pack = fit_data_package( md, &config, embed )?;
+            write_fit_package( &output, &model_pack )
         }
-    );
+    }
+}
 
-    let mfits : Vec<model_fit<ModelTanh>> = list.iter().take(reports).map(|(r, rpp, m, minit)|
-    {
-        model_fit {
-            humps: humps,
-            fitted_model: m.clone(),
-            initial_model: minit.clone(),
-            residual_total : *r, 
-            residual_per_point : *rpp 
-        } 
+/// fit every group of `column` in parallel, write `<output>_<group>.<ext>` per group (in the format of `output`) and the index `<output>_groups.yml`.
+/// groups where the fit fails are reported and left out of the index.
+pub fn grouped_curve_fitting( mut md : data_fit_load_metadata, output : &str, config : &FitConfig, column : &str, embed : bool ) -> Result<()> {
+    let ( df, format, hash ) = read_input( &md.input, md.format )?;
+    md.format = Some( format );
+    md.check_source( hash );
+    let groups = group_values( &df, column )?;
+    println!( " fitting {} groups of {column}", groups.len() );
+    let packs : Vec<(String, Result<data_fit_package<ModelTanh>>)> = groups.into_par_iter().map( |g| {
+        let mut mg = md.clone();
+        mg.group = Some( GroupKey::new( column.to_string(), g.clone() ) );
+        let pack = mg.prepare_frame( df.clone() ).and_then( |dg| fit_frame( mg, &dg, config, embed ) );
+        (g, pack)
     }).collect();
 
-    let model_pack = data_fit_package{ load_metadata: md, fits: mfits};
-    // Create a file
-    let mut path = PathBuf::from(output.as_str() );
-    path.set_extension("yml");
- 
+    let ( mut base, format ) = package_path( output );
+    base.set_extension( "" );
+    let base = base.to_string_lossy().to_string();
+    let mut index = GroupIndex{ column: column.to_string(), groups: BTreeMap::new() };
+    let mut first_error : Option<HumptyError> = None;
+    for (g, pack) in packs {
+        println!( "group {column} = {g}:" );
+        match pack {
+            Ok( pack ) => {
+                report_fit( &pack, config );
+                let path = format!( "{base}_{}.{}", group_file_stem( &g ), format.extension() );
+                write_fit_package( &path, &pack )?;
+                index.groups.insert( g, path );
+            },
+            Err( e ) => {
+                eprintln!( " WARNING group {g} is skipped: {e}" );
+                first_error.get_or_insert( e );
+            }
+        }
+    }
+    if index.groups.is_empty() {
+        if let Some( e ) = first_error {
+            return Err( e )
+        }
+    }
+    let path = PathBuf::from( format!( "{base}_groups.yml" ) );
+    let display = path.to_string_lossy().to_string();
+    let text = serde_yaml::to_string( &index ).map_err( |e| HumptyError::parse( display.clone(), e ))?;
+    std::fs::write( &path, text ).map_err( |e| HumptyError::io( display.clone(), e ))?;
+    println!( " group index written to {display}" );
+    Ok(())
+}
 
-    let mut data_file = File::create(path).expect("creation failed");
-    let mut writer = BufWriter::new(data_file);
+/// load the data slice described by `md`, fit it and assemble the package without writing it.
+pub fn fit_data_package( mut md : data_fit_load_metadata, config : &FitConfig, embed : bool ) -> Result<data_fit_package<ModelTanh>> {
+    let df = md.read_frame()?;
+    let model_pack = fit_frame( md, &df, config, embed )?;
+    report_fit( &model_pack, config );
+    Ok( model_pack )
+}
+
+/// fit the slice of an already prepared frame (see `data_fit_load_metadata::prepare_frame`), nothing is printed.
+/// with `embed` the prepared series is stored in the package (see `EmbeddedSeries`), always for stdin (`-`),
+/// which cannot be read again when the package is loaded.
+pub fn fit_frame( mut md : data_fit_load_metadata, df : &DataFrame, config : &FitConfig, embed : bool ) -> Result<data_fit_package<ModelTanh>> {
+    if md.wide.is_some() && md.time.is_none() {
+        md.time = Some( TimeAxis::new( String::from( "date" ), AxisUnit::Day, None ) );
+    }
+    md.time = md.time.map( |t| t.resolve( df ) ).transpose()?;
+    let data_slice{ offset, limit, strides } = md.slice.clone();
+    let (( _tspan ,texact), dspan  , (tfull, dfull)) = load_frame( df, &md.input, offset, limit, strides, Some( &md.colname ), md.time.as_ref() )?;
+    if embed || md.input == "-" {
+        md.data = Some( EmbeddedSeries{ time: tfull.to_vec(), value: dfull.to_vec() } );
+    }
+    let mut values : Vec<f64> = dspan.iter().cloned().collect();
+    md.transform = md.transform.map( |tr| tr.resolve( &values ) ).transpose()?;
+    if let Some( tr ) = &md.transform {
+        values = tr.apply( &values )?;
+    }
+    let series = Series::new( texact.iter().cloned().collect(), values )?;
+    let FitResult { fits: mut mfits, summary } = api::fit( &series, config )?;
+    // fitted in the transformed space, evaluated in data units.
+    mfits.iter_mut().for_each( |f| {
+        f.fitted_model.link = md.transform.clone();
+        f.initial_model.link = md.transform.clone();
+    });
 
     //old method:
     // serde_yaml::to_writer(&mut writer, &list[0..reports]).expect("serde yaml serialization fails.");
 
-    serde_yaml::to_writer(&mut writer, &model_pack).expect("serde yaml serialization fails.");
-    writer.flush().expect("error finalizing serde yaml buffer.");
+    Ok( data_fit_package{ version: PACKAGE_VERSION, load_metadata: md, fits: mfits, summary, job: None } )
+}
+
+/// print the solver summary and the fits of a package.
+fn report_fit( model_pack : &data_fit_package<ModelTanh>, config : &FitConfig ) {
+    let summary = &model_pack.summary;
+    let mfits = &model_pack.fits;
+    println!( " solver: {} of {} restarts failed ({:.1}%)", summary.failed, summary.samples, 100. * summary.failure_rate );
+    summary.terminations.iter().for_each( |(reason, count)| println!( "\t{count} x {reason}" ) );
+    if ! config.keep_failed && mfits.len() < config.reports {
+        println!( " WARNING only {} successful fits are available for {} reports, use --keep-failed to include failed restarts.", mfits.len(), config.reports );
+    }
+    mfits.iter().for_each( |f| println!( "{:#?}", f ) );
+}
+
+#[test]
+fn test_time_axis_dates(){
+    let axis = TimeAxis::new( String::from( "date" ), AxisUnit::Week, Some( String::from( "2020-02-02" ) ));
+    assert_eq!( axis.date( 0. ), "2020-02-02" );
+    assert_eq!( axis.date( 2.5 ), "2020-02-19" );
+    let hourly = TimeAxis::new( String::from( "date" ), AxisUnit::Hour, Some( String::from( "2020-02-02T06:00:00" ) ));
+    assert_eq!( hourly.date( 3. ), "2020-02-02T09:00:00" );
+}
 
- 
+#[test]
+fn test_missing_values_keep_time_positions(){
+    use polars::prelude::{NamedFrom, Series};
+    let df = DataFrame::new( vec![
+        Series::new( "t", &[0., 1., 2.5, 4., 7.5] ),
+        Series::new( "v", &[Some(1.), None, Some(3.), Some(f64::NAN), Some(5.)] ),
+    ]).unwrap();
+    let axis = TimeAxis::new( String::from( "t" ), AxisUnit::Day, None ).resolve( &df ).unwrap();
+    let ((_, tsplice), dslice, _) = load_frame( &df, &String::from( "test" ), None, None, None, Some( &String::from( "v" ) ), Some( &axis ) ).unwrap();
+    assert_eq!( tsplice.as_slice(), &[0., 2.5, 7.5] );
+    assert_eq!( dslice.as_slice(), &[1., 3., 5.] );
+    let ((_, tsplice), _, _) = load_frame( &df, &String::from( "test" ), None, None, None, Some( &String::from( "v" ) ), None ).unwrap();
+    assert_eq!( tsplice.as_slice(), &[0., 2., 4.] );
 }
 
+#[test]
+fn test_reload_embedded_series(){
+    let mut md = data_fit_load_metadata::new( String::from( "/nonexistent/moved.csv" ), data_slice{ offset: Some( 1 ), limit: Some( 2 ), strides: None }, String::from( "v" ) );
+    md.data = Some( EmbeddedSeries{ time: vec![0., 2., 3., 5.], value: vec![1., 4., 9., 25.] } );
+    let ((_, tsplice), dslice, (tfull, _)) = reload_data( md ).unwrap();
+    assert_eq!( tsplice.as_slice(), &[2., 3.] );
+    assert_eq!( dslice.as_slice(), &[4., 9.] );
+    assert_eq!( tfull.len(), 4 );
+}
 
+#[test]
+fn test_package_formats_round_trip(){
+    assert_eq!( package_path( "xt" ), ( PathBuf::from( "xt.yml" ), PackageFormat::Yaml ) );
+    assert_eq!( package_path( "xt.YAML" ).1, PackageFormat::Yaml );
+    assert_eq!( package_path( "xt.mpk" ).1, PackageFormat::MsgPack );
+    let mut md = data_fit_load_metadata::new( String::from( "x.csv" ), data_slice::default(), String::from( "x" ) );
+    md.transform = Some( ValueTransform::ZScore { mean: 2., sd: f64::NAN } );
+    let pack = data_fit_package{ version: PACKAGE_VERSION, load_metadata: md, fits: vec![], summary: FitSummary::default(), job: None };
+    let dir = std::env::temp_dir();
+    for ext in [ "yml", "json", "msgpack" ] {
+        let path = dir.join( format!( "humpty_round_trip_{}.{ext}", std::process::id() ) ).to_string_lossy().to_string();
+        write_fit_package( &path, &pack ).unwrap();
+        let back = read_fit_package( &path ).unwrap();
+        std::fs::remove_file( &path ).unwrap();
+        let Some( ValueTransform::ZScore { mean, sd } ) = back.load_metadata.transform else { panic!( "{ext}: transform lost" ) };
+        assert_eq!( mean, 2. );
+        assert!( sd.is_nan(), "{ext}" );
+        assert_eq!( back.summary.failure_rate.is_nan(), pack.summary.failure_rate.is_nan(), "{ext}" );
+    }
+}
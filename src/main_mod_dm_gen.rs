@@ -1,5 +1,5 @@
 
-use std::error::Error;
+use std::fs::File;
 
 use serde::*; 
 use rand::Rng;
@@ -7,6 +7,7 @@ use rand::{seq::IteratorRandom, thread_rng};
 use csv::Writer;
 use rand_distr::{Normal, Distribution};
 use crate::models::{ParameterizedModel, ModelAdditive::ModelAdditive, ModelConstant::ModelConstant, ModelTanh::ModelTanh};
+use crate::error::{HumptyError, Result};
 
 
 #[derive(serde::Serialize)]
@@ -14,8 +15,8 @@ pub struct Row {
     time: f64, 
     count: f64
 } 
-pub fn write_data(path: &str, t : &Vec<f64>, d : &Vec<f64> )-> Result<(), Box<dyn Error>> {   
-    let mut wtr = Writer::from_path(path)?;
+pub fn write_data(path: &str, t : &Vec<f64>, d : &Vec<f64> )-> Result<()> {   
+    let mut wtr = Writer::from_writer( File::create(path).map_err( |e| HumptyError::io( path, e ))? );
     for ( x,y ) in t.iter().zip( d ){
         wtr.serialize( 
             Row { time : *x , count : *y }
@@ -45,7 +46,7 @@ fn eval_tahn_model( M: &Vec<ModelTanh>, t: &Vec<f64> ) -> Vec<f64>{
 /// 
 /// (C) W. Casey
 
-pub fn generate_tanh_model( time_steps : usize, humps: usize, swing: (f64, f64 ) , error_mod : f64 , rpos : bool, verbose : bool) -> (Vec<f64>, Vec<f64>, Vec<f64>, Vec<ModelTanh>){
+pub fn generate_tanh_model( time_steps : usize, humps: usize, swing: (f64, f64 ) , error_mod : f64 , rpos : bool, verbose : bool) -> Result<(Vec<f64>, Vec<f64>, Vec<f64>, Vec<ModelTanh>)>{
     let mut t : Vec<f64> = (0..).take( time_steps).map( |x| x as f64 ).collect(); 
     let mut rng = rand::thread_rng();
     let (m,M) = swing;  
@@ -66,12 +67,17 @@ pub fn generate_tanh_model( time_steps : usize, humps: usize, swing: (f64, f64 )
     let d : Vec<f64> = eval_tahn_model(&mods, &t);
     let e : Vec<f64> = (0..).take( time_steps).map( |x| rng.gen::<f64>()* error_mod ).collect();
     let E = d.iter().zip(e).map( |(a,b)| a + b ).collect(); 
-    (t, d, E ,mods)
+    Ok((t, d, E ,mods))
 }
 
-pub fn build_tanh_model_from_string( time_steps : usize, des : String, error_mod : f64, verbose: bool ) -> (Vec<f64>, Vec<f64>, Vec<f64>, Vec<ModelTanh>){
+pub fn build_tanh_model_from_string( time_steps : usize, des : String, error_mod : f64, verbose: bool ) -> Result<(Vec<f64>, Vec<f64>, Vec<f64>, Vec<ModelTanh>)>{
     let mut rng = rand::thread_rng();
-    let x = des.split(",").into_iter().map( |x| x.parse::<f64>().unwrap()).collect::<Vec<f64>>();
+    let x = des.split(",").into_iter()
+        .map( |x| x.trim().parse::<f64>().map_err( |e| HumptyError::parse( format!( "model parameter '{x}'" ), e )))
+        .collect::<Result<Vec<f64>>>()?;
+    if x.len() % 3 != 0 {
+        return Err( HumptyError::parse( "model parameters", format!( "expected kappa,alpha,beta triples, found {} values", x.len() )))
+    }
     let mods : Vec<ModelTanh> = (0..x.len()/3).map( 
         |i| { 
             let K = x[3*i]; 
@@ -85,9 +91,9 @@ pub fn build_tanh_model_from_string( time_steps : usize, des : String, error_mod
     let mut t : Vec<f64> = (0..).take( time_steps).map( |x| x as f64 ).collect();
     let d : Vec<f64> = eval_tahn_model(&mods, &t);
  
-    let normal = Normal::new(0., error_mod ).unwrap();
+    let normal = Normal::new(0., error_mod ).map_err( |e| HumptyError::parse( "error magnitude", e ))?;
     let e : Vec<f64> = normal.sample_iter(&mut rand::thread_rng()).take(time_steps).collect();
     let E = d.iter().zip(e).map( |(a,b)| a + b ).collect(); 
-    (t,d, E,mods )
+    Ok((t,d, E,mods ))
 }
 
@@ -0,0 +1,129 @@
+use std::path::PathBuf;
+
+use serde_yaml::Value;
+
+use crate::error::{HumptyError, Result};
+use crate::main_mod_dm_fit::{data_fit_load_metadata, data_fit_package, data_slice, FitSummary, model_fit, read_package_value, write_fit_package};
+use crate::models::ModelAffine::{AffineAdditive, FitDiagnostics};
+use crate::models::ModelTanh::ModelTanh;
+
+////////////////
+///
+/// fit package layouts, `version` in the package names the layout it was written with:
+///     0 :  a bare list of [residual, fitted model, initial model] (or [residual, model]) tuples, e.g. doc/x.yml
+///     1 :  data_fit_package { load_metadata, fits, summary, job } without a version field
+///     2 :  as 1 with `version`
+/// `upgrade_package` brings any of them to `PACKAGE_VERSION`.
+pub const PACKAGE_VERSION : u32 = 2;
+
+pub fn default_package_version() -> u32 { 1 }
+
+/// layout version of a parsed package.
+pub fn package_version( value : &Value ) -> Result<u32> {
+    match value {
+        Value::Sequence( _ ) => Ok( 0 ),
+        Value::Mapping( m ) => match m.get( "version" ) {
+            None => Ok( 1 ),
+            Some( v ) => v.as_u64().map( |v| v as u32 ).ok_or_else( || HumptyError::parse( "fit package", format!( "version {v:?} is not a number" ))),
+        },
+        _ => Err( HumptyError::parse( "fit package", "neither a package nor a list of fits" )),
+    }
+}
+
+/// read a package of any known layout, returns it in the current layout together with the version it had.
+pub fn upgrade_package( value : Value, context : &str ) -> Result<(data_fit_package<ModelTanh>, u32)> {
+    let version = package_version( &value )?;
+    if version > PACKAGE_VERSION {
+        return Err( HumptyError::parse( context, format!( "package version {version} is newer than this humpty ({PACKAGE_VERSION})" )))
+    }
+    let mut package : data_fit_package<ModelTanh> = match version {
+        0 => upgrade_v0( value, context )?,
+        _ => serde_yaml::from_value( value ).map_err( |e| HumptyError::parse( context, e ))?,
+    };
+    package.version = PACKAGE_VERSION;
+    Ok(( package, version ))
+}
+
+/// version 0 files carry no data reference, the input is left empty unless `migrate --input` provides one.
+fn upgrade_v0( value : Value, context : &str ) -> Result<data_fit_package<ModelTanh>> {
+    let entries : Vec<Vec<Value>> = serde_yaml::from_value( value ).map_err( |e| HumptyError::parse( context, e ))?;
+    let fits = entries.into_iter().enumerate().map( |(k, entry)| {
+        let bad = |m : String| HumptyError::parse( context, format!( "entry {k}: {m}" ));
+        let residual = entry.first().and_then( |r| r.as_f64() ).ok_or_else( || bad( String::from( "no residual" )))?;
+        let fitted : AffineAdditive<ModelTanh> = serde_yaml::from_value( entry.get( 1 ).cloned().ok_or_else( || bad( String::from( "no model" )))? )
+            .map_err( |e| bad( e.to_string() ))?;
+        let initial = match entry.get( 2 ) {
+            Some( m ) => serde_yaml::from_value( m.clone() ).map_err( |e| bad( e.to_string() ))?,
+            None => fitted.clone(),
+        };
+        Ok( model_fit {
+            humps: fitted.tm.components.len(),
+            fitted_model: fitted,
+            initial_model: initial,
+            residual_total: residual,
+            residual_per_point: f64::NAN,
+            diagnostics: FitDiagnostics::default(),
+        })
+    }).collect::<Result<Vec<_>>>()?;
+    Ok( data_fit_package {
+        version: 0,
+        load_metadata: data_fit_load_metadata::new( String::new(), data_slice::default(), String::from( "count" ) ),
+        fits,
+        summary: FitSummary::default(),
+        job: None,
+    })
+}
+
+/// rewrite packages in the current layout, in place or into `out_dir`.  Packages already current are only
+/// rewritten when a data reference is attached.  With `check` nothing is written.
+pub fn migrate_packages( models : &[String], out_dir : Option<&String>, input : Option<&String>, column : Option<&String>, check : bool ) -> Result<()> {
+    let mut failed = 0;
+    for path in models {
+        let outcome = read_package_value( path ).and_then( |(value, display)| upgrade_package( value, &display ));
+        let ( mut package, version ) = match outcome {
+            Ok( x ) => x,
+            Err( e ) => {
+                eprintln!( "{path}: {e}" );
+                failed += 1;
+                continue
+            },
+        };
+        if let Some( input ) = input {
+            package.load_metadata.input = input.clone();
+        }
+        if let Some( column ) = column {
+            package.load_metadata.colname = column.clone();
+        }
+        let changed = version < PACKAGE_VERSION || input.is_some() || column.is_some();
+        let target = match out_dir {
+            Some( dir ) => PathBuf::from( dir ).join( PathBuf::from( path ).file_name().unwrap_or_default() ).to_string_lossy().to_string(),
+            None => path.clone(),
+        };
+        if check || ( ! changed && out_dir.is_none() ) {
+            println!( "{path}: version {version}{}", if version < PACKAGE_VERSION { " (needs migration)" } else { "" } );
+            continue
+        }
+        if version == 0 && package.load_metadata.input.is_empty() {
+            eprintln!( "{path}: WARNING version 0 packages name no data file, use --input to let viz and csv reload it" );
+        }
+        write_fit_package( &target, &package )?;
+        println!( "{path}: version {version} -> {PACKAGE_VERSION}, written to {target}" );
+    }
+    if failed > 0 {
+        return Err( HumptyError::parse( "migrate", format!( "{failed} of {} packages could not be read", models.len() )))
+    }
+    Ok(())
+}
+
+#[test]
+fn test_upgrade_tuple_list() {
+    let text = std::fs::read_to_string( concat!( env!( "CARGO_MANIFEST_DIR" ), "/doc/x200.yml" ) ).unwrap();
+    let ( package, version ) = upgrade_package( serde_yaml::from_str( &text ).unwrap(), "x200" ).unwrap();
+    assert_eq!( version, 0 );
+    assert_eq!( package.version, PACKAGE_VERSION );
+    assert_eq!( package.fits[0].humps, 2 );
+    assert!( ( package.fits[0].residual_total - 3915797.631332931 ).abs() < 1e-6 );
+    // the current layout round trips.
+    let again = upgrade_package( serde_yaml::to_value( &package ).unwrap(), "again" ).unwrap();
+    assert_eq!( again.1, PACKAGE_VERSION );
+}
@@ -0,0 +1,287 @@
+use serde::{Serialize, Deserialize};
+
+use crate::error::{HumptyError, Result};
+use crate::api::{FitConfig, MomentStat, StatWindow};
+use crate::ingest::{WideTable, InputFormat};
+use crate::resample::SeriesStep;
+use crate::stats::AlarmConfig;
+use crate::transform::ValueTransform;
+use crate::main_mod_dm_fit::{data_fit_load_metadata, data_slice, fit_data_package, package_path, TimeAxis, write_fit_package};
+use crate::main_mod_dm_viz::*;
+use crate::main_mod_dm_csv::{alarms_csv, components_csv, drop_csv_data, ForecastEnsemble, ForecastQuantiles, hub_csv, HubSpec, residual_disp_skew_csv};
+use crate::main_mod_dm_exp::{basic_explanation, intermediate_explanation};
+
+////////////////
+///
+/// a job file describes one fit and the artifacts rendered from it, e.g.
+///
+/// ```yaml
+/// input: x.csv
+/// column: x
+/// time: { column: date, unit: day }
+/// slice: { offset: 0, limit: 120 }
+/// model: { family: tanh, humps: 2, samples: 200, reports: 3, loss: least_squares }
+/// output: xt
+/// artifacts:
+///   - { kind: csv_fore, output: xt_fore, slice: { limit: 160 } }
+///   - { kind: csv_fore, output: xt_q, quantiles: { levels: [0.05, 0.5, 0.95], spread: residual } }
+///   - { kind: viz_basic, output: xt_basic, top: 3 }
+///   - { kind: viz_basic, output: xt_ens, ensemble: { weights: akaike, members: 5 } }
+///   - { kind: csv_alarms, output: xt_alarms, alarms: { detectors: [cusum, bocpd], threshold: 6 }, slice: { limit: 160 } }
+///   - { kind: exp_intermediate }
+/// ```
+///
+/// artifacts use the job slice unless they carry their own.
+#[derive(Debug, Serialize, Deserialize, Clone )]
+pub struct JobSpec {
+    /// data file, `-` for stdin.
+    pub input : String,
+    /// csv, json, ndjson or parquet, detected when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format : Option<InputFormat>,
+    pub column : String,
+    #[serde(default)]
+    pub slice : data_slice,
+    /// date column mapped to the time axis, see `TimeAxis`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time : Option<TimeAxis>,
+    /// the input is a wide table, see `WideTable`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wide : Option<WideTable>,
+    /// resampling chain, see `SeriesStep`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps : Vec<SeriesStep>,
+    /// value transform, see `ValueTransform`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform : Option<ValueTransform>,
+    /// store the prepared series in the package, see `EmbeddedSeries`.
+    #[serde(default)]
+    pub embed : bool,
+    #[serde(default)]
+    pub model : JobModel,
+    /// the fit package is written here (.yml, .json or .msgpack, .yml when there is no extension).
+    pub output : String,
+    #[serde(default)]
+    pub artifacts : Vec<JobArtifact>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone )]
+pub struct JobModel {
+    #[serde(default = "default_family")]
+    pub family : String,
+    #[serde(default = "default_humps")]
+    pub humps : usize,
+    #[serde(default = "default_samples")]
+    pub samples : usize,
+    #[serde(default = "default_reports")]
+    pub reports : usize,
+    #[serde(default = "default_loss")]
+    pub loss : String,
+    #[serde(default)]
+    pub keep_failed : bool,
+}
+
+impl Default for JobModel {
+    fn default() -> Self {
+        Self { family: default_family(), humps: default_humps(), samples: default_samples(), reports: default_reports(), loss: default_loss(), keep_failed: false }
+    }
+}
+
+fn default_family() -> String { String::from( "tanh" ) }
+fn default_humps() -> usize { 3 }
+fn default_samples() -> usize { 1000 }
+fn default_reports() -> usize { 1 }
+fn default_loss() -> String { String::from( "least_squares" ) }
+fn default_top() -> usize { 1 }
+fn default_pval() -> f64 { 0.02 }
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq )]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactKind {
+    CsvFore,
+    CsvResidual,
+    CsvDisp,
+    CsvSkew,
+    CsvHub,
+    CsvComponents,
+    CsvAlarms,
+    VizBasic,
+    VizIntermediate,
+    VizResidual,
+    VizDisp,
+    VizSkew,
+    ExpBasic,
+    ExpIntermediate,
+}
+
+/// one output of the job, the fields mirror the options of the matching `csv`, `viz` or `exp` subcommand.
+#[derive(Debug, Serialize, Deserialize, Clone )]
+pub struct JobArtifact {
+    pub kind : ArtifactKind,
+    /// output file, not used by the `exp_*` kinds.
+    #[serde(default)]
+    pub output : Option<String>,
+    /// model index (csv_*, viz_intermediate, viz_residual, viz_disp, viz_skew).
+    #[serde(default)]
+    pub index : usize,
+    /// number of models (viz_basic, exp_*).
+    #[serde(default = "default_top")]
+    pub top : usize,
+    #[serde(default = "default_pval")]
+    pub pval : f64,
+    #[serde(default)]
+    pub slice : Option<data_slice>,
+    #[serde(default)]
+    pub title : Option<String>,
+    #[serde(default)]
+    pub xlabel : Option<String>,
+    #[serde(default)]
+    pub ylabel : Option<String>,
+    /// quantile forecast (csv_fore, csv_hub), see `ForecastQuantiles`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantiles : Option<ForecastQuantiles>,
+    /// forecast hub columns (csv_hub), see `HubSpec`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hub : Option<HubSpec>,
+    /// trailing or exponential window of the running residual statistics (csv_skew, viz_skew), see `StatWindow`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window : Option<StatWindow>,
+    /// running statistic plotted (viz_skew).
+    #[serde(default)]
+    pub stat : MomentStat,
+    /// also write the derivative of each hump (csv_components).
+    #[serde(default)]
+    pub incidence : bool,
+    /// change detectors on the residuals (csv_alarms, and overlaid by viz_residual), see `AlarmConfig`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alarms : Option<AlarmConfig>,
+    /// weighted ensemble of the top fits (csv_fore, viz_basic), also the members of an ensemble spread (csv_fore, csv_hub),
+    /// see `ForecastEnsemble`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ensemble : Option<ForecastEnsemble>,
+}
+
+pub fn read_job_spec( path : &str ) -> Result<JobSpec> {
+    let text = std::fs::read_to_string( path ).map_err( |e| HumptyError::io( path, e ) )?;
+    serde_yaml::from_str( &text ).map_err( |e| HumptyError::parse( format!( "job file {path}" ), e ) )
+}
+
+impl JobSpec {
+    /// check the job and fill in every artifact slice, so the spec stored in the package is complete.
+    pub fn resolve( mut self ) -> Result<Self> {
+        if self.model.family != "tanh" {
+            return Err( HumptyError::parse( "job model", format!( "unknown model family '{}', supported: tanh", self.model.family ) ) )
+        }
+        if self.model.loss != "least_squares" {
+            return Err( HumptyError::parse( "job model", format!( "unknown loss '{}', supported: least_squares", self.model.loss ) ) )
+        }
+        let slice = self.slice.clone();
+        for (k, a) in self.artifacts.iter_mut().enumerate() {
+            let needs_output = ! matches!( a.kind, ArtifactKind::ExpBasic | ArtifactKind::ExpIntermediate );
+            if needs_output && a.output.is_none() {
+                return Err( HumptyError::parse( "job artifacts", format!( "artifact {k} ({:?}) needs an output", a.kind ) ) )
+            }
+            if a.kind == ArtifactKind::CsvHub && a.hub.is_none() {
+                return Err( HumptyError::parse( "job artifacts", format!( "artifact {k} (CsvHub) needs hub: {{ geo_value: ... }}" ) ) )
+            }
+            if let Some( window ) = &a.window {
+                window.check().map_err( |e| HumptyError::parse( "job artifacts", format!( "artifact {k} ({:?}): {e}", a.kind ) ) )?;
+            }
+            if a.slice.is_none() {
+                a.slice = Some( slice.clone() );
+            }
+        }
+        Ok( self )
+    }
+}
+
+/// run the fit of a job, write the package with the job embedded, then render the artifacts in order.
+pub fn run_job( path : &str ) -> Result<()> {
+    let mut job = read_job_spec( path )?.resolve()?;
+    println!( "resolved job:\n{}", serde_yaml::to_string( &job )? );
+
+    let m = &job.model;
+    let mut md = data_fit_load_metadata::new( job.input.clone(), job.slice.clone(), job.column.clone() );
+    md.time = job.time.clone();
+    md.wide = job.wide.clone();
+    md.steps = job.steps.clone();
+    md.transform = job.transform.clone();
+    md.format = job.format;
+    let config = FitConfig::default().humps( m.humps ).samples( m.samples ).reports( m.reports ).keep_failed( m.keep_failed );
+    let mut model_pack = fit_data_package( md, &config, job.embed )?;
+    if let Some( time ) = &model_pack.load_metadata.time {
+        job.time = Some( time.clone() );
+    }
+    job.transform = model_pack.load_metadata.transform.clone();
+    job.format = model_pack.load_metadata.format;
+    model_pack.job = Some( job.clone() );
+    write_fit_package( &job.output, &model_pack )?;
+
+    let models = package_path( &job.output ).0.to_string_lossy().to_string();
+
+    job.artifacts.iter().try_for_each( |a| run_artifact( &models, a ) )
+}
+
+fn run_artifact( models : &str, a : &JobArtifact ) -> Result<()> {
+    let data_slice{ offset, limit, strides } = a.slice.clone().unwrap_or_default();
+    let output = a.output.clone().unwrap_or_default();
+    let models = models.to_string();
+    match a.kind {
+        ArtifactKind::CsvFore => drop_csv_data( models, output, a.index, offset, limit, strides, partial_model::new(0, 0), a.quantiles.clone(), a.ensemble.clone() ),
+        ArtifactKind::CsvResidual | ArtifactKind::CsvDisp =>
+            residual_disp_skew_csv( models, output, a.index, offset, limit, strides, partial_model::new(0, 0), None ),
+        ArtifactKind::CsvSkew =>
+            residual_disp_skew_csv( models, output, a.index, offset, limit, strides, partial_model::new(0, 0), a.window ),
+        ArtifactKind::CsvHub => a.hub.clone().ok_or_else( || HumptyError::parse( "job artifacts", "csv_hub needs hub" ))
+            .and_then( |hub| hub_csv( models, output, a.index, hub, a.quantiles.clone(), a.ensemble.clone() )),
+        ArtifactKind::CsvComponents => components_csv( models, output, a.index, offset, limit, strides, a.incidence ),
+        ArtifactKind::CsvAlarms => alarms_csv( models, output, a.index, offset, limit, strides, &a.alarms.clone().unwrap_or_default() ),
+        ArtifactKind::VizBasic =>
+            basic_visualization( models, output, a.top, offset, limit, strides, partial_model::new(60, 120), a.title.clone(), a.xlabel.clone(), a.ylabel.clone(), a.ensemble.clone() ),
+        ArtifactKind::VizIntermediate =>
+            intermediate_visualization( models, output, a.index, a.pval, offset, limit, strides, a.title.clone(), a.xlabel.clone(), a.ylabel.clone() ),
+        ArtifactKind::VizResidual =>
+            residual_visualization( models, output, a.index, offset, limit, strides, partial_model::new(60, 120), a.title.clone(), a.xlabel.clone(), a.ylabel.clone(), a.alarms.clone() ),
+        ArtifactKind::VizDisp =>
+            disp_visualization( models, output, a.index, offset, limit, strides, partial_model::new(60, 120), a.title.clone(), a.xlabel.clone(), a.ylabel.clone() ),
+        ArtifactKind::VizSkew =>
+            skew_visualization( models, output, a.index, offset, limit, strides, partial_model::new(60, 120), a.title.clone(), a.xlabel.clone(), a.ylabel.clone(), a.window, a.stat ),
+        ArtifactKind::ExpBasic => basic_explanation( models, a.top ),
+        ArtifactKind::ExpIntermediate => intermediate_explanation( models, a.top, a.pval ),
+    }
+}
+
+#[test]
+fn test_job_resolve(){
+    let job = | artifacts : &str | serde_yaml::from_str::<JobSpec>( &format!( "input: x.csv\ncolumn: x\nslice: {{ offset: 2, limit: 30 }}\noutput: xt\nartifacts:\n{artifacts}" ) ).unwrap().resolve();
+    assert!( job( "  - { kind: csv_fore }" ).is_err() );
+    assert!( job( "  - { kind: csv_hub, output: xt_hub }" ).is_err() );
+    assert!( job( "  - { kind: csv_skew, output: xt_skew, window: !trailing 0 }" ).is_err() );
+    let resolved = job( "  - { kind: csv_fore, output: xt_fore, slice: { limit: 50 } }\n  - { kind: exp_basic }" ).unwrap();
+    assert_eq!( resolved.artifacts[0].slice.as_ref().unwrap().limit, Some( 50 ) );
+    let inherited = resolved.artifacts[1].slice.as_ref().unwrap();
+    assert_eq!( ( inherited.offset, inherited.limit ), ( Some( 2 ), Some( 30 ) ) );
+}
+
+#[test]
+fn test_run_job_stores_resolved_job(){
+    use crate::main_mod_dm_fit::read_fit_package;
+    let base = std::env::temp_dir().join( format!( "humpty_job_{}", std::process::id() ) ).to_string_lossy().to_string();
+    let rows : Vec<String> = (0..40).map( |t| format!( "{t},{}", 3. + 10. * ( 1. + ( 0.2 * t as f64 - 4. ).tanh() ) ) ).collect();
+    std::fs::write( format!( "{base}.csv" ), format!( "time,x\n{}\n", rows.join( "\n" ) ) ).unwrap();
+    let spec = format!( "input: {base}.csv\ncolumn: x\noutput: {base}.yml\nmodel: {{ humps: 1, samples: 20 }}\nartifacts:\n  - {{ kind: csv_fore, output: {base}_fore }}\n  - {{ kind: exp_basic }}\n" );
+    std::fs::write( format!( "{base}_job.yml" ), spec ).unwrap();
+    run_job( &format!( "{base}_job.yml" ) ).unwrap();
+
+    let stored = read_fit_package( &format!( "{base}.yml" ) ).unwrap().job.unwrap();
+    assert_eq!( stored.format, Some( InputFormat::Csv ) );
+    assert!( stored.artifacts.iter().all( |a| a.slice.is_some() ) );
+    assert!( std::path::Path::new( &format!( "{base}_fore.csv" ) ).exists() );
+    // the stored job reads back and resolves to itself.
+    std::fs::write( format!( "{base}_stored.yml" ), serde_yaml::to_string( &stored ).unwrap() ).unwrap();
+    let again = read_job_spec( &format!( "{base}_stored.yml" ) ).unwrap().resolve().unwrap();
+    assert_eq!( serde_yaml::to_string( &again ).unwrap(), serde_yaml::to_string( &stored ).unwrap() );
+    for suffix in [ ".csv", "_job.yml", ".yml", "_fore.csv", "_stored.yml" ] {
+        std::fs::remove_file( format!( "{base}{suffix}" ) ).unwrap();
+    }
+}
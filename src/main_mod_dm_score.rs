@@ -0,0 +1,323 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::PathBuf;
+
+use csv::Writer;
+use polars::prelude::DataFrame;
+
+use crate::error::{HumptyError, Result};
+use crate::ingest::read_input;
+use crate::main_mod_dm_fit::{column_to_f64, TimeAxis, AxisUnit};
+
+////////////////
+///
+/// one forecast of one target: `quantiles` holds (level, value) pairs sorted by level, `horizon` counts steps
+/// past the forecast origin, the last observation (1 is the first step forecast, as the hub `ahead`).  `target`
+/// and `origin` are dates (YYYY-MM-DD), or time values for series without a date axis.
+#[derive(Debug, Clone)]
+pub struct ForecastRecord {
+    pub forecaster : String,
+    pub origin : String,
+    pub target : String,
+    pub horizon : i64,
+    pub quantiles : Vec<(f64, f64)>,
+}
+
+impl ForecastRecord {
+    /// the median when it is among the levels.
+    pub fn point( &self ) -> f64 {
+        self.quantiles.iter().find( |(q, _)| ( q - 0.5 ).abs() < 1e-9 ).map_or( f64::NAN, |(_, v)| *v )
+    }
+}
+
+/// pinball loss of the value `v` forecast as the `tau` quantile of the outcome `y`.
+pub fn pinball( tau : f64, v : f64, y : f64 ) -> f64 {
+    ( if y < v { tau - 1. } else { tau } ) * ( y - v )
+}
+
+/// weighted interval score, 2/n times the summed pinball losses of the n levels.  For levels forming K central
+/// intervals and the median this is the WIS of Bracher et al. (2021), for the median alone it is |y - m|.
+pub fn weighted_interval_score( quantiles : &[(f64, f64)], y : f64 ) -> f64 {
+    2. * quantiles.iter().map( |(q, v)| pinball( *q, *v, y ) ).sum::<f64>() / quantiles.len() as f64
+}
+
+/// CRPS = ∫ 2 pinball( τ, Q(τ), y ) dτ over (0, 1), with Q linear between the given levels and flat beyond them.
+pub fn crps_from_quantiles( quantiles : &[(f64, f64)], y : f64 ) -> f64 {
+    let grid = 1000;
+    let quantile_at = |tau : f64| {
+        let k = quantiles.partition_point( |(q, _)| *q < tau );
+        match ( k.checked_sub( 1 ).and_then( |j| quantiles.get( j ) ), quantiles.get( k ) ) {
+            ( Some( (q0, v0) ), Some( (q1, v1) ) ) if q1 > q0 => v0 + ( tau - q0 ) / ( q1 - q0 ) * ( v1 - v0 ),
+            ( _, Some( (_, v) ) ) | ( Some( (_, v) ), None ) => *v,
+            ( None, None ) => f64::NAN,
+        }
+    };
+    // midpoint rule on a uniform grid of levels.
+    ( 0..grid ).map( |k| {
+        let tau = ( k as f64 + 0.5 ) / grid as f64;
+        2. * pinball( tau, quantile_at( tau ), y )
+    }).sum::<f64>() / grid as f64
+}
+
+/// nominal level (1 - 2τ) of each central interval (τ, 1 - τ) among the levels and whether it covers `y`.
+pub fn interval_coverage( quantiles : &[(f64, f64)], y : f64 ) -> Vec<(f64, bool)> {
+    quantiles.iter().filter( |(q, _)| *q < 0.5 - 1e-9 ).filter_map( |(q, lo)| {
+        quantiles.iter().find( |(p, _)| ( p - ( 1. - q ) ).abs() < 1e-9 ).map( |(_, hi)| ( 1. - 2. * q, *lo <= y && y <= *hi ) )
+    }).collect()
+}
+
+/// scores of one forecast against its outcome.
+#[derive(Debug, Clone)]
+pub struct ScoredForecast {
+    pub record : ForecastRecord,
+    pub truth : f64,
+    pub squared_error : f64,
+    pub absolute_error : f64,
+    pub wis : f64,
+    pub crps : f64,
+    pub coverage : Vec<(f64, bool)>,
+}
+
+/// score every record whose target is in `truth`, the others are returned as a count.
+pub fn score_forecasts( records : &[ForecastRecord], truth : &BTreeMap<String, f64> ) -> (Vec<ScoredForecast>, usize) {
+    let mut missing = 0;
+    let scored = records.iter().filter_map( |r| {
+        let Some( y ) = truth.get( &r.target ).copied().filter( |y| y.is_finite() ) else {
+            missing += 1;
+            return None
+        };
+        let error = r.point() - y;
+        Some( ScoredForecast {
+            record: r.clone(),
+            truth: y,
+            squared_error: error * error,
+            absolute_error: error.abs(),
+            wis: weighted_interval_score( &r.quantiles, y ),
+            crps: crps_from_quantiles( &r.quantiles, y ),
+            coverage: interval_coverage( &r.quantiles, y ),
+        })
+    }).collect();
+    ( scored, missing )
+}
+
+/// mean scores of a group of forecasts, missing values are skipped.
+#[derive(Debug, Clone)]
+pub struct ScoreSummary {
+    pub n : usize,
+    pub mse : f64,
+    pub mae : f64,
+    pub wis : f64,
+    pub crps : f64,
+    /// fraction covered per nominal interval level, NaN when no forecast of the group has that interval.
+    pub coverage : Vec<(f64, f64)>,
+}
+
+fn finite_mean( values : impl Iterator<Item = f64> ) -> f64 {
+    let ( sum, n ) = values.filter( |v| v.is_finite() ).fold( ( 0., 0 ), |( s, n ), v| ( s + v, n + 1 ) );
+    if n == 0 { f64::NAN } else { sum / n as f64 }
+}
+
+/// the interval levels found in any scored forecast, ascending.
+pub fn coverage_levels( scored : &[ScoredForecast] ) -> Vec<f64> {
+    let mut levels : Vec<f64> = Vec::new();
+    for ( level, _ ) in scored.iter().flat_map( |s| s.coverage.iter() ) {
+        if ! levels.iter().any( |l| ( l - level ).abs() < 1e-9 ) {
+            levels.push( *level );
+        }
+    }
+    levels.sort_by( |a, b| a.total_cmp( b ) );
+    levels
+}
+
+pub fn summarize( scored : &[&ScoredForecast], levels : &[f64] ) -> ScoreSummary {
+    ScoreSummary {
+        n: scored.len(),
+        mse: finite_mean( scored.iter().map( |s| s.squared_error ) ),
+        mae: finite_mean( scored.iter().map( |s| s.absolute_error ) ),
+        wis: finite_mean( scored.iter().map( |s| s.wis ) ),
+        crps: finite_mean( scored.iter().map( |s| s.crps ) ),
+        coverage: levels.iter().map( |level| {
+            let hits = scored.iter().filter_map( |s| s.coverage.iter().find( |(l, _)| ( l - level ).abs() < 1e-9 ) );
+            ( *level, finite_mean( hits.map( |(_, covered)| if *covered { 1. } else { 0. } ) ) )
+        }).collect(),
+    }
+}
+
+/// group the scored forecasts by forecaster and a key, e.g. the horizon or the origin.
+pub fn summarize_by<K : Ord + Clone>( scored : &[ScoredForecast], key : impl Fn( &ScoredForecast ) -> K ) -> BTreeMap<(String, K), ScoreSummary> {
+    let levels = coverage_levels( scored );
+    let mut groups : BTreeMap<(String, K), Vec<&ScoredForecast>> = BTreeMap::new();
+    scored.iter().for_each( |s| groups.entry( ( s.record.forecaster.clone(), key( s ) ) ).or_default().push( s ) );
+    groups.into_iter().map( |(k, g)| ( k, summarize( &g, &levels ) ) ).collect()
+}
+
+fn coverage_column( level : f64 ) -> String {
+    format!( "coverage_{}", ( level * 1000. ).round() / 10. )
+}
+
+/// write the summaries with columns forecaster, `key_name`, n, mse, mae, wis, crps and coverage_<level %>.
+pub fn write_summaries<K : Ord + Clone + ToString>( path : &str, key_name : &str, summaries : &BTreeMap<(String, K), ScoreSummary> ) -> Result<()> {
+    let mut wtr = Writer::from_writer( File::create( path ).map_err( |e| HumptyError::io( path, e ))? );
+    let levels : Vec<f64> = summaries.values().next().map( |s| s.coverage.iter().map( |(l, _)| *l ).collect() ).unwrap_or_default();
+    let mut header : Vec<String> = [ "forecaster", key_name, "n", "mse", "mae", "wis", "crps" ].iter().map( |x| x.to_string() ).collect();
+    header.extend( levels.iter().map( |l| coverage_column( *l ) ) );
+    wtr.write_record( &header )?;
+    for ( (forecaster, key), s ) in summaries {
+        let mut row = vec![ forecaster.clone(), key.to_string(), s.n.to_string(), s.mse.to_string(), s.mae.to_string(), s.wis.to_string(), s.crps.to_string() ];
+        row.extend( s.coverage.iter().map( |(_, c)| c.to_string() ) );
+        wtr.write_record( &row )?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// one row per scored forecast.
+pub fn write_scores( path : &str, scored : &[ScoredForecast] ) -> Result<()> {
+    let mut wtr = Writer::from_writer( File::create( path ).map_err( |e| HumptyError::io( path, e ))? );
+    wtr.write_record( [ "forecaster", "forecast_date", "horizon", "target", "truth", "point", "squared_error", "absolute_error", "wis", "crps" ] )?;
+    for s in scored {
+        let r = &s.record;
+        wtr.write_record( [ r.forecaster.clone(), r.origin.clone(), r.horizon.to_string(), r.target.clone(), s.truth.to_string(),
+            r.point().to_string(), s.squared_error.to_string(), s.absolute_error.to_string(), s.wis.to_string(), s.crps.to_string() ] )?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// the values of a date (or time) column as keys: YYYY-MM-DD for dates, the number for plain times.
+fn time_keys( df : &DataFrame, column : &str ) -> Result<Vec<String>> {
+    let axis = TimeAxis::new( column.to_string(), AxisUnit::Day, None ).resolve( df )?;
+    Ok( axis.times( df )?.iter().map( |t| if t.is_finite() { axis.date( *t ) } else { String::new() } ).collect() )
+}
+
+fn numbers( df : &DataFrame, column : &str ) -> Result<Vec<f64>> {
+    let found = df.column( column ).map_err( |_| HumptyError::MissingColumn {
+        column: column.to_string(),
+        available: df.get_column_names().iter().map( |x| x.to_string() ).collect() })?;
+    Ok( column_to_f64( found )?.to_vec() )
+}
+
+/// the truth series keyed like the forecast targets: by `date_column`, or by row index when there is none.
+/// `diff` scores incidence forecasts against a cumulative series.
+pub fn read_truth( path : &str, date_column : Option<&str>, value_column : &str, diff : bool ) -> Result<BTreeMap<String, f64>> {
+    let ( df, _, _ ) = read_input( path, None )?;
+    let mut values = numbers( &df, value_column )?;
+    let keys = match date_column {
+        Some( column ) => time_keys( &df, column )?,
+        None => ( 0..df.height() ).map( |k| format!( "{}", k as f64 ) ).collect(),
+    };
+    if diff {
+        values = ( 0..values.len() ).map( |k| if k == 0 { f64::NAN } else { values[k] - values[k - 1] } ).collect();
+    }
+    Ok( keys.into_iter().zip( values ).filter( |(k, _)| ! k.is_empty() ).collect() )
+}
+
+/// forecaster, origin, target and horizon, the rows of one forecast share them.
+type ForecastKey = (String, String, String, i64);
+
+/// read the forecasts of a file, either forecast hub rows (`csv hub` or any hub forecaster) or quantile rows of
+/// `csv fore -q` (named `forecaster`, the origin is the last fitted point at `ahead` 0); both keep `ahead` as the horizon.
+pub fn read_forecasts( path : &str, forecaster : &str ) -> Result<Vec<ForecastRecord>> {
+    let ( df, _, _ ) = read_input( path, None )?;
+    let names : Vec<String> = df.get_column_names().iter().map( |x| x.to_string() ).collect();
+    let has = |c : &str| names.iter().any( |n| n == c );
+    let quantile = numbers( &df, "quantile" )?;
+    let value = numbers( &df, "value" )?;
+    let ahead = numbers( &df, "ahead" )?;
+
+    // one record per row with its single level, merged per forecast below.
+    let mut rows : Vec<ForecastRecord> = Vec::new();
+    let row = |forecaster : String, origin : String, target : String, k : usize| ForecastRecord {
+        forecaster, origin, target,
+        horizon: ahead[k].round() as i64,
+        quantiles: vec![ ( quantile[k], value[k] ) ],
+    };
+    if has( "target_end_date" ) && has( "forecast_date" ) {
+        let targets = time_keys( &df, "target_end_date" )?;
+        let origins = time_keys( &df, "forecast_date" )?;
+        let forecasters : Vec<String> = if has( "forecaster" ) {
+            df.column( "forecaster" )?.utf8()?.into_iter().map( |x| x.unwrap_or( forecaster ).trim().to_string() ).collect()
+        } else {
+            vec![ forecaster.to_string(); df.height() ]
+        };
+        for k in 0..df.height() {
+            rows.push( row( forecasters[k].clone(), origins[k].clone(), targets[k].clone(), k ) );
+        }
+    } else {
+        let targets = if has( "date" ) { time_keys( &df, "date" )? } else { numbers( &df, "time" )?.iter().map( |t| format!( "{t}" ) ).collect() };
+        let origin = ( 0..df.height() ).find( |k| ahead[*k].round() == 0. ).map( |k| targets[k].clone() ).ok_or_else(
+            || HumptyError::parse( path, "no row at the last fitted point (ahead = 0) to name the forecast origin" ))?;
+        for k in ( 0..df.height() ).filter( |k| ahead[*k].round() >= 1. ) {
+            rows.push( row( forecaster.to_string(), origin.clone(), targets[k].clone(), k ) );
+        }
+    }
+
+    let mut records : BTreeMap<ForecastKey, Vec<(f64, f64)>> = BTreeMap::new();
+    for r in rows {
+        records.entry( ( r.forecaster, r.origin, r.target, r.horizon ) ).or_default().extend( r.quantiles );
+    }
+    Ok( records.into_iter().map( |( (forecaster, origin, target, horizon), mut quantiles )| {
+        quantiles.sort_by( |a, b| a.0.total_cmp( &b.0 ) );
+        ForecastRecord { forecaster, origin, target, horizon, quantiles }
+    }).collect() )
+}
+
+/// `humpty score`: score the forecasts of every file against the truth and write `<output>_scores.csv`,
+/// `<output>_by_horizon.csv` and `<output>_by_origin.csv`.
+pub fn score_files( forecasts : &[String], forecaster : &str, truth : &BTreeMap<String, f64>, output : &str ) -> Result<()> {
+    let records = forecasts.iter().map( |f| read_forecasts( f, forecaster ) ).collect::<Result<Vec<_>>>()?.concat();
+    let ( scored, missing ) = score_forecasts( &records, truth );
+    if missing > 0 {
+        eprintln!( "WARNING {missing} of {} forecasts have no truth value and are not scored", records.len() );
+    }
+    if scored.is_empty() {
+        return Err( HumptyError::parse( "score", "no forecast target is in the truth series" ))
+    }
+    let mut stem = PathBuf::from( output );
+    stem.set_extension( "" );
+    let stem = stem.to_string_lossy().to_string();
+
+    let path = format!( "{stem}_scores.csv" );
+    write_scores( &path, &scored )?;
+    println!( "writing file {path}" );
+    let path = format!( "{stem}_by_horizon.csv" );
+    write_summaries( &path, "horizon", &summarize_by( &scored, |s| s.record.horizon ) )?;
+    println!( "writing file {path}" );
+    let path = format!( "{stem}_by_origin.csv" );
+    write_summaries( &path, "forecast_date", &summarize_by( &scored, |s| s.record.origin.clone() ) )?;
+    println!( "writing file {path}" );
+    Ok(())
+}
+
+#[test]
+fn test_interval_scores() {
+    let q = [ (0.1, 2.), (0.5, 5.), (0.9, 8.) ];
+    // inside the 80% interval: (2/3) (ρ_0.1 + ρ_0.5 + ρ_0.9) at y = 6.
+    let wis = weighted_interval_score( &q, 6. );
+    assert!( ( wis - 2. / 3. * ( 0.4 + 0.5 + 0.2 ) ).abs() < 1e-12 );
+    assert_eq!( interval_coverage( &q, 6. ), vec![ (0.8, true) ] );
+    assert_eq!( interval_coverage( &q, 9. ), vec![ (0.8, false) ] );
+    assert_eq!( weighted_interval_score( &[ (0.5, 5.) ], 3. ), 2. );
+    // a point forecast has CRPS |y - m|, a spread forecast centred on y less.
+    assert!( ( crps_from_quantiles( &[ (0.5, 5.) ], 3. ) - 2. ).abs() < 1e-9 );
+    assert!( crps_from_quantiles( &q, 5. ) < crps_from_quantiles( &[ (0.5, 8.) ], 5. ) );
+}
+
+#[test]
+fn test_hub_and_fore_horizons_agree() {
+    let base = std::env::temp_dir().join( format!( "humpty_score_{}", std::process::id() ) ).to_string_lossy().to_string();
+    let hub = format!( "{base}_hub.csv" );
+    std::fs::write( &hub, "ahead,geo_value,quantile,value,forecaster,forecast_date,data_source,signal,target_end_date,incidence_period\n\
+        1,ca,0.5,10,humpty,2020-03-10,jhu-csse,confirmed_incidence_num,2020-03-11,day\n\
+        2,ca,0.5,12,humpty,2020-03-10,jhu-csse,confirmed_incidence_num,2020-03-12,day\n" ).unwrap();
+    let fore = format!( "{base}_q.csv" );
+    std::fs::write( &fore, "time,date,ahead,quantile,value\n8,2020-03-09,-1,0.5,7\n9,2020-03-10,0,0.5,8\n10,2020-03-11,1,0.5,10\n11,2020-03-12,2,0.5,12\n" ).unwrap();
+    let from_hub = read_forecasts( &hub, "other" ).unwrap();
+    let from_fore = read_forecasts( &fore, "humpty" ).unwrap();
+    std::fs::remove_file( &hub ).unwrap();
+    std::fs::remove_file( &fore ).unwrap();
+    let keys = |records : &[ForecastRecord]| records.iter().map( |r| ( r.forecaster.clone(), r.origin.clone(), r.target.clone(), r.horizon ) ).collect::<Vec<_>>();
+    assert_eq!( keys( &from_hub ), keys( &from_fore ) );
+    assert_eq!( ( from_hub[0].origin.as_str(), from_hub[0].target.as_str(), from_hub[0].horizon ), ( "2020-03-10", "2020-03-11", 1 ) );
+    assert_eq!( from_fore[1].point(), 12. );
+}
@@ -7,7 +7,6 @@ use std::path::Path;
 use std::path::PathBuf;
 
 use csv::Writer;
-use libm::sqrt;
 use ndarray::s;
 use ndarray::ShapeBuilder;
 use polars::frame::row::Row;
@@ -19,13 +18,18 @@ use crate::models::ModelTanh::ModelTanh;
 use crate::viz_lib;
 use crate::viz_lib::*;
 use crate::viz_lib2;
+use crate::stats::{self, AlarmConfig};
 
+use crate::api::{self, MomentStat, Series, StatWindow};
+use crate::error::{HumptyError, Result};
+use crate::main_mod_dm_csv::{ensemble_forecast, ForecastEnsemble};
 use crate::main_mod_dm_fit::{
-    data_fit_load_metadata, data_fit_package, load_data, model_fit, reload_data,
+    data_fit_load_metadata, data_fit_package, fit_at, load_data, model_fit, read_fit_package,
+    reload_data,
 };
 
 // to calcualte the residual
-use ndarray::{array, aview1, Axis};
+use ndarray::{array, aview1, Array1, Axis};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug)]
@@ -40,6 +44,82 @@ impl partial_model {
     }
 }
 
+/// resolve the viz slice against `n` data points: (offset, limit, strides) with the limit clamped to the data.
+pub fn viz_slice(
+    offset: Option<i64>,
+    limit: Option<usize>,
+    strides: Option<usize>,
+    n: usize,
+) -> Result<(usize, usize, usize)> {
+    let eoffset = offset.unwrap_or(0_i64);
+    if eoffset < 0 || eoffset as usize >= n {
+        return Err(HumptyError::UnsupportedSlice(format!(
+            "offset {eoffset} is outside the {n} data points"
+        )));
+    }
+    let eoffset = eoffset as usize;
+    let elimit: usize = limit.unwrap_or(n).min(n - eoffset);
+    let estrides = strides.unwrap_or(1);
+    if estrides == 0 {
+        return Err(HumptyError::UnsupportedSlice(String::from(
+            "strides must be at least 1",
+        )));
+    }
+    Ok((eoffset, elimit, estrides))
+}
+
+/// times of rows eoffset.. eoffset+elimit (step estrides), rows past the data continue with the last time step.
+pub fn forecast_times(tfull: &Array1<f64>, eoffset: usize, elimit: usize, estrides: usize) -> Vec<f64> {
+    let n = tfull.len();
+    let step = if n > 1 { tfull[n - 1] - tfull[n - 2] } else { 1. };
+    (eoffset..(eoffset + elimit))
+        .step_by(estrides)
+        .map(|k| if k < n { tfull[k] } else { tfull[n - 1] + ((k - (n - 1)) as f64) * step })
+        .collect()
+}
+
+/// rows eoffset.. eoffset+elimit of the series taken every `estrides` rows, as (time, data).
+pub fn viz_rows(
+    tfull: &Array1<f64>,
+    dfull: &Array1<f64>,
+    eoffset: usize,
+    elimit: usize,
+    estrides: usize,
+) -> (Vec<f64>, Vec<f64>) {
+    (
+        tfull.slice(s![eoffset..(eoffset + elimit); estrides]).to_vec(),
+        dfull.slice(s![eoffset..(eoffset + elimit); estrides]).to_vec(),
+    )
+}
+
+/// positions in the viz rows `tall` of the last `pre` rows before the fitted support `tsplice`, of the rows inside
+/// it and of the first `post` rows after it.  The split goes by time, so the fit and the viz may use different strides.
+pub fn support_rows(
+    tall: &[f64],
+    tsplice: &[f64],
+    pre: usize,
+    post: usize,
+) -> Result<(Vec<usize>, Vec<usize>, Vec<usize>)> {
+    let (Some(first), Some(last)) = (tsplice.first(), tsplice.last()) else {
+        return Err(HumptyError::UnsupportedSlice(String::from("the model was fitted on no data")));
+    };
+    let before: Vec<usize> = (0..tall.len()).filter(|k| tall[*k] < *first).collect();
+    let ipre = before[before.len().saturating_sub(pre)..].to_vec();
+    let imod: Vec<usize> = (0..tall.len()).filter(|k| tall[*k] >= *first && tall[*k] <= *last).collect();
+    let ipost: Vec<usize> = (0..tall.len()).filter(|k| tall[*k] > *last).take(post).collect();
+    if imod.is_empty() {
+        return Err(HumptyError::UnsupportedSlice(String::from(
+            "the viz slice does not overlap the data the model was fitted on",
+        )));
+    }
+    Ok((ipre, imod, ipost))
+}
+
+/// is time `t` between the first and last time of the viz slice (the time axis need not be the row index).
+pub fn in_viz_window(tfull: &Array1<f64>, eoffset: usize, elimit: usize, t: f64) -> bool {
+    elimit > 0 && t >= tfull[eoffset] && t <= tfull[eoffset + elimit - 1]
+}
+
 pub fn basic_visualization(
     models: String,
     output: String,
@@ -50,19 +130,15 @@ pub fn basic_visualization(
     pmod: partial_model,
     title: Option<String>, 
     xlabel: Option<String>,
-    ylabel: Option<String>
-) {
+    ylabel: Option<String>,
+    ensemble: Option<ForecastEnsemble>,
+) -> Result<()> {
     // step 1: read the model file.
-    let mut path = PathBuf::from(models.as_str());
-    path.set_extension("yml");
-    let mut data_file =
-        File::open(path).expect("open model(s) file failed, please insure the extention is yml");
-    let mut reader = BufReader::new(data_file);
-    let VX: data_fit_package<ModelTanh> =
-        serde_yaml::from_reader(reader).expect("problem reading yaml file");
+    let VX: data_fit_package<ModelTanh> = read_fit_package(&models)?;
     let data_fit_package {
         load_metadata: lmd,
         fits: VV,
+        ..
     } = VX;
     // step 2: prepare output stubs.
     let mut path_output = PathBuf::from(output.as_str());
@@ -72,34 +148,56 @@ pub fn basic_visualization(
     // step 3: reload the data ( used in the model )
     // tsclice is are a range (local intrensic idex), tplice is extrinsic index (index in original data), dsplice is sliced data,
     // tfull is all time steps in datafile, dfull is all data in data file.
-    let ((tslice, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd);
+    let ((tslice, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd)?;
 
     // step 4: calculate the clipping slice or viz slice
-    let eoffset = offset.unwrap_or(0_i64) as usize;
-    let elimit: usize = limit.unwrap_or(dfull.shape()[0]);
-    let estrides = strides.unwrap_or(1);
+    let (eoffset, elimit, estrides) = viz_slice(offset, limit, strides, dfull.shape()[0])?;
     // restrict time/data to the viz slice
-    let tall: Vec<f64> = tfull
-        .slice(s![eoffset..(eoffset+elimit); estrides])
-        .to_vec();
-    let dall: Vec<f64> = dfull
-        .slice(s![eoffset..(eoffset+elimit); estrides])
-        .to_vec();
-
-    if (estrides > 1) {
-        panic!(" estrides Not yet supported ");
+    let (tall, dall) = viz_rows(&tfull, &dfull, eoffset, elimit, estrides);
+    // the model is drawn on the viz rows inside the fitted support, flanked by up to `before`/`after` rows.
+    let (ipre, imod, ipost) = support_rows(&tall, tsplice.as_slice(), pmod.before, pmod.after)?;
+    let etsplice: Vec<f64> = imod.iter().map(|k| tall[*k]).collect();
+
+    if let Some(ensemble) = ensemble {
+        // the ensemble is drawn on the same rows as a single model.
+        let t_viz: Vec<f64> = ipre.iter().chain(imod.iter()).chain(ipost.iter()).map(|k| tall[*k]).collect();
+        let fitted = Series::new(tsplice.as_slice().to_vec(), dsplice.as_slice().to_vec())?;
+        let ef = ensemble_forecast(&VV, &fitted, &t_viz, &ensemble)?;
+        let lo: Vec<f64> = ef.mean.iter().zip(ef.spread.iter()).map(|(m, s)| m - s).collect();
+        let hi: Vec<f64> = ef.mean.iter().zip(ef.spread.iter()).map(|(m, s)| m + s).collect();
+        let members: Vec<(String, Vec<f64>, f64)> = ef.members.iter().zip(ef.weights.iter()).enumerate()
+            .map(|(k, (m, w))| (format!("fit {k}"), m.clone(), *w))
+            .collect();
+        let title_string = title.clone().unwrap_or(format!(
+            "ensemble of {} fits {} ({:?} weights)",
+            members.len(),
+            models.as_str().replace(".yml", ""),
+            ensemble.weights
+        ));
+        viz_lib2::plot_ensemble(
+            &tall,
+            &dall,
+            &t_viz,
+            &ef.mean,
+            &lo,
+            &hi,
+            &members,
+            viz_lib2::PlotAction::PNG(
+                format!("{}", output_stem.to_string_lossy()).into(),
+                800,
+                600,
+                2.0,
+            ),
+            title_string,
+            xlabel.clone().unwrap_or(String::from("time ")),
+            ylabel.clone().unwrap_or(String::from("quantity")),
+        );
+        println!("writing file {}", output_stem.to_string_lossy());
+        return Ok(());
     }
-    // model (time) clipped to viz slice
-    let ETS: Vec<(usize, &f64)> = tsplice
-        .iter()
-        .enumerate()
-        .filter(|(k, g)| ((**g as usize) >= eoffset) && ((**g as usize) < (eoffset + elimit)))
-        .collect();
-    let etsplice: Vec<f64> = ETS.iter().map(|(a, b)| **b).collect();
-    let etslice: Vec<usize> = ETS.iter().map(|(a, b)| *a).collect();
 
     println!( " basic with {:?}", &title );
-    (0..min(top, VV.len())).into_par_iter().for_each(|k| {
+    (0..min(top, VV.len())).into_par_iter().try_for_each(|k| -> Result<()> {
         //step 4 clipping plane for model
         let model_fit {
             humps: humps,
@@ -107,26 +205,11 @@ pub fn basic_visualization(
             initial_model: initial_model,
             residual_total: residual_total,
             residual_per_point: residual_per_point,
-        } = &VV[k];
+            ..
+        } = fit_at(&VV, k)?;
         let mx = eval_M(&fitted_model, &etsplice);
-        //(* flanking prediction *)
-        let partial_model {
-            before: pre,
-            after: post,
-        } = pmod;
-
-        let tpre: Vec<f64> = tall
-            .iter()
-            .filter(|g| **g < *etsplice.first().unwrap())
-            .map(|v| *v)
-            .collect();
-        let tpre: Vec<f64> = tpre.iter().rev().take(pre).rev().map(|v| *v).collect(); //tpre[ 0.max(tpre.len() - pre).. ].to_vec();
-        let tpost: Vec<f64> = tall
-            .iter()
-            .filter(|g| **g > *etsplice.last().unwrap())
-            .map(|v| *v)
-            .take(post)
-            .collect();
+        let tpre: Vec<f64> = ipre.iter().map(|k| tall[*k]).collect();
+        let tpost: Vec<f64> = ipost.iter().map(|k| tall[*k]).collect();
 
         let mxall: Vec<f64> = eval_M(&fitted_model, &tall);
         let mxpre: Vec<f64> = eval_M(&fitted_model, &tpre);
@@ -139,7 +222,7 @@ pub fn basic_visualization(
 
         let lsm = residual_total;
         let (rsumsqxx, rsumsq_ppxx, residxx, resid1xx) =
-            fitted_model.residual_mat(&tslice, &dsplice);
+            fitted_model.residual_mat(&tsplice, &dsplice);
 
         let title_string = match( &title ){
             Some(t) => t.clone(),
@@ -148,7 +231,7 @@ pub fn basic_visualization(
                 models.as_str().replace(".yml", ""),
                 k,
                 residual_per_point
-            ) //format!("{}_fit_{}", output_stem.to_str().unwrap(), k).into(),
+            ) //format!("{}_fit_{}", output_stem.to_string_lossy(), k).into(),
         };
         let xlabel_string = match( &xlabel ){
             Some(s) => s.clone(),
@@ -168,7 +251,7 @@ pub fn basic_visualization(
             &tpost,
             &mxpost,
             viz_lib2::PlotAction::PNG(
-                format!("{}", output_stem.to_str().unwrap()).into(),
+                format!("{}", output_stem.to_string_lossy()).into(),
                 800,
                 600,
                 2.0,
@@ -177,8 +260,9 @@ pub fn basic_visualization(
             xlabel_string,
             ylabel_string,
         );
-        println!("writing file {}", output_stem.to_str().unwrap())
-    });
+        println!("writing file {}", output_stem.to_string_lossy());
+        Ok(())
+    })
 }
 
 pub fn vec_there_and_back(v: &Vec<f64>) -> Vec<f64> {
@@ -205,6 +289,8 @@ pub fn vec_envelope_to_region(vhi: &Vec<f64>, c: &Vec<f64>, vlo: &Vec<f64>) -> V
     val_there_and_back
 }
 
+/// the data, model and running residual quantile bands of fit `item`; with `alarms` the alarms of the change
+/// detectors on the residuals are drawn as vertical lines (see `api::residual_alarms`).
 pub fn residual_visualization(
     models: String,
     output: String,
@@ -215,45 +301,25 @@ pub fn residual_visualization(
     pmod: partial_model,
     title: Option<String>, 
     xlabel: Option<String>,
-    ylabel: Option<String>
-) {
-    let mut path = PathBuf::from(models.as_str()); // get the model file.
-    path.set_extension("yml");
-    let mut data_file =
-        File::open(path).expect("open model(s) file failed, please insure the extention is yml");
-    let mut reader = BufReader::new(data_file);
-    let VX: data_fit_package<ModelTanh> =
-        serde_yaml::from_reader(reader).expect("problem reading yaml file");
+    ylabel: Option<String>,
+    alarms: Option<AlarmConfig>,
+) -> Result<()> {
+    let VX: data_fit_package<ModelTanh> = read_fit_package(&models)?; // get the model file.
     let data_fit_package {
         load_metadata: lmd,
         fits: VV,
+        ..
     } = VX;
-    let ((tspan, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd.clone());
+    let ((tspan, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd.clone())?;
 
     // read the model file.
     // step 4: calculate the clipping slice or viz slice
-    let eoffset = offset.unwrap_or(0_i64) as usize;
-    let elimit: usize = limit.unwrap_or(dfull.shape()[0]);
-    let estrides = strides.unwrap_or(1);
+    let (eoffset, elimit, estrides) = viz_slice(offset, limit, strides, dfull.shape()[0])?;
     // All time/data in the viz slice
-    let tall: Vec<f64> = tfull
-        .slice(s![eoffset..(eoffset+elimit); estrides])
-        .to_vec();
-    let dall: Vec<f64> = dfull
-        .slice(s![eoffset..(eoffset+elimit); estrides])
-        .to_vec();
-
-    if (estrides > 1) {
-        panic!(" estrides Not yet supported ");
-    }
-    // model (time) clipped to viz slice.  tsplice is the model support (in global data index), now we further restrict that to viz_slice
-    let ETS: Vec<(usize, &f64)> = tsplice
-        .iter()
-        .enumerate()
-        .filter(|(k, g)| ((**g as usize) >= eoffset) && ((**g as usize) < (eoffset + elimit)))
-        .collect();
-    let etsplice: Vec<f64> = ETS.iter().map(|(a, b)| **b).collect();
-    let etslice: Vec<usize> = ETS.iter().map(|(a, b)| *a).collect();
+    let (tall, dall) = viz_rows(&tfull, &dfull, eoffset, elimit, estrides);
+    // the model is drawn on the viz rows inside the fitted support, flanked by up to `before`/`after` rows.
+    let (ipre, imod, ipost) = support_rows(&tall, tsplice.as_slice(), pmod.before, pmod.after)?;
+    let etsplice: Vec<f64> = imod.iter().map(|k| tall[*k]).collect();
 
     let mut path_output = PathBuf::from(output.as_str());
     path_output.set_extension("");
@@ -264,49 +330,20 @@ pub fn residual_visualization(
     // tfull is all time steps in datafile, dfull is all data in data file.
     let ((tslice ,tsplice),
           dsplice  , 
-         (tfull, dfull)) =  reload_data( lmd );
+         (tfull, dfull)) =  reload_data( lmd )?;
 
-    (item..(item + 1)).into_par_iter().for_each(|k| {
+    (item..(item + 1)).into_par_iter().try_for_each(|k| -> Result<()> {
         let model_fit {
             humps: humps,
             fitted_model: fitted_model,
             initial_model: initial_model,
             residual_total: residual_total,
             residual_per_point: residual_per_point,
-        } = &VV[k];
+            ..
+        } = fit_at(&VV, k)?;
         let mx = eval_M(&fitted_model, &etsplice);
-        //(* flanking prediction *)
-        let partial_model {
-            before: pre,
-            after: post,
-        } = pmod;
-
-        let ipre: Vec<usize> = tall
-            .iter()
-            .enumerate()
-            .filter(|(k, g)| **g < *etsplice.first().unwrap())
-            .map(|(k, v)| k)
-            .collect();
-        let ipre: Vec<usize> = ipre.iter().rev().take(pre).rev().map(|k| *k).collect();
-        let tpre: Vec<f64> = tall
-            .iter()
-            .filter(|g| **g < *etsplice.first().unwrap())
-            .map(|v| *v)
-            .collect();
-        let tpre: Vec<f64> = tpre.iter().rev().take(pre).rev().map(|v| *v).collect(); //tpre[ 0.max(tpre.len() - pre).. ].to_vec();
-        let tpost: Vec<f64> = tall
-            .iter()
-            .filter(|g| **g > *etsplice.last().unwrap())
-            .map(|v| *v)
-            .take(post)
-            .collect();
-        let ipost: Vec<usize> = tall
-            .iter()
-            .enumerate()
-            .filter(|(k, g)| **g > *etsplice.last().unwrap())
-            .map(|(k, g)| k)
-            .take(post)
-            .collect();
+        let tpre: Vec<f64> = ipre.iter().map(|k| tall[*k]).collect();
+        let tpost: Vec<f64> = ipost.iter().map(|k| tall[*k]).collect();
 
         let mxall: Vec<f64> = eval_M(&fitted_model, &tall);
         let mxpre: Vec<f64> = eval_M(&fitted_model, &tpre);
@@ -320,7 +357,7 @@ pub fn residual_visualization(
 
         let i_viz: Vec<usize> = ipre
             .iter()
-            .chain(etslice.iter().chain(ipost.iter()))
+            .chain(imod.iter().chain(ipost.iter()))
             .map(|k| *k)
             .collect();
         //let res_pre : Vec<f64> = ipre.iter().map(|k|{resid1xx[*k]}).collect();
@@ -329,14 +366,13 @@ pub fn residual_visualization(
         ///////////////
         /// form envelopes
         let res_viz: Vec<f64> = i_viz.iter().map(|k| resid1xx[*k]).collect();
-        //let dat_viz : Vec<f64> = i_viz.iter().map(|k|{dall[*k]}).collect();
         let t_viz: Vec<f64> = i_viz.iter().map(|k| tall[*k]).collect();
         let m_viz: Vec<f64> = eval_M(&fitted_model, &t_viz);
         // Here is the proceedure for visualizing the growing residual.
         let quantile_params = vec![0.0, 0.1, 0.25, 0.5, 0.75, 0.9, 1.0];
         let qpn = quantile_params.len();
         //let res_clip: Vec<f64> = viz_clip.map( |k| { resid1xx[k] } ).collect();
-        let quantiles_running = rolling_quantiles(res_viz, quantile_params); // clipped like viz_clip
+        let quantiles_running = stats::rolling_quantiles(&res_viz, &quantile_params); // clipped like viz_clip
                                                                              // now must subtract the moving model values from the qunatiles running.
 
         let time_vizslice_there_and_back: Vec<f64> = vec_there_and_back(&t_viz.to_vec());
@@ -355,7 +391,7 @@ pub fn residual_visualization(
             .collect();
         let title_string = match( &title ){
             Some(t) => t.clone(),
-            None => format!("{}_fit_{}", output_stem.to_str().unwrap(), k).into(),
+            None => format!("{}_fit_{}", output_stem.to_string_lossy(), k).into(),
         };
         let xlabel_string = match( &xlabel ){
             Some(s) => s.clone(),
@@ -365,6 +401,16 @@ pub fn residual_visualization(
             Some(s) => s.clone(),
             None => String::from("residual"),
         };
+        let alarm_lines: Vec<(String, f64)> = match &alarms {
+            Some(config) => {
+                let fitted = Series::new(tsplice.as_slice().to_vec(), dsplice.as_slice().to_vec())?;
+                api::residual_alarms(fitted_model, &fitted, &Series::new(tall.clone(), dall.clone())?, config)?
+                    .iter()
+                    .map(|a| (a.detector.name().to_string(), tall[a.index]))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
         viz_lib2::plot_d(
             &tall,
             &dall,
@@ -377,18 +423,20 @@ pub fn residual_visualization(
             &time_vizslice_there_and_back,
             &regions_all,
             viz_lib2::PlotAction::PNG(
-                format!("{}", output_stem.to_str().unwrap()).into(),
+                format!("{}", output_stem.to_string_lossy()).into(),
                 800,
                 600,
                 2.0,
             ),
-            String::from(format!("model fit {} \n LSM {} ", k, lsm)),
+            format!("model fit {} \n LSM {} ", k, lsm),
             xlabel_string,
             ylabel_string,
+            &alarm_lines,
         );
 
-        println!("writing file {}", output_stem.to_str().unwrap())
-    });
+        println!("writing file {}", output_stem.to_string_lossy());
+        Ok(())
+    })
 }
 
 /////////////////////////////////
@@ -405,91 +453,41 @@ pub fn disp_visualization(
     title: Option<String>, 
     xlabel: Option<String>,
     ylabel: Option<String>
-) {
-    let mut path = PathBuf::from(models.as_str()); // get the model file.
-    path.set_extension("yml");
-    let mut data_file =
-        File::open(path).expect("open model(s) file failed, please insure the extention is yml");
-    let mut reader = BufReader::new(data_file);
-    let VX: data_fit_package<ModelTanh> =
-        serde_yaml::from_reader(reader).expect("problem reading yaml file");
+) -> Result<()> {
+    let VX: data_fit_package<ModelTanh> = read_fit_package(&models)?; // get the model file.
     let data_fit_package {
         load_metadata: lmd,
         fits: VV,
+        ..
     } = VX;
-    let ((tspan, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd);
+    let ((tspan, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd)?;
 
     // read the model file.
     // step 4: calculate the clipping slice or viz slice
-    let eoffset = offset.unwrap_or(0_i64) as usize;
-    let elimit: usize = limit.unwrap_or(dfull.shape()[0]);
-    let estrides = strides.unwrap_or(1);
+    let (eoffset, elimit, estrides) = viz_slice(offset, limit, strides, dfull.shape()[0])?;
     // All time/data in the viz slice
-    let tall: Vec<f64> = tfull
-        .slice(s![eoffset..(eoffset+elimit); estrides])
-        .to_vec();
-    let dall: Vec<f64> = dfull
-        .slice(s![eoffset..(eoffset+elimit); estrides])
-        .to_vec();
-
-    if (estrides > 1) {
-        panic!(" estrides Not yet supported ");
-    }
-    // model (time) clipped to viz slice.  tsplice is the model support (in global data index), now we further restrict that to viz_slice
-    let ETS: Vec<(usize, &f64)> = tsplice
-        .iter()
-        .enumerate()
-        .filter(|(k, g)| ((**g as usize) >= eoffset) && ((**g as usize) < (eoffset + elimit)))
-        .collect();
-    let etsplice: Vec<f64> = ETS.iter().map(|(a, b)| **b).collect();
-    let etslice: Vec<usize> = ETS.iter().map(|(a, b)| *a).collect();
+    let (tall, dall) = viz_rows(&tfull, &dfull, eoffset, elimit, estrides);
+    // the model is drawn on the viz rows inside the fitted support, flanked by up to `before`/`after` rows.
+    let (ipre, imod, ipost) = support_rows(&tall, tsplice.as_slice(), pmod.before, pmod.after)?;
+    let etsplice: Vec<f64> = imod.iter().map(|k| tall[*k]).collect();
 
     let mut path_output = PathBuf::from(output.as_str());
     path_output.set_extension("");
     let output_stem = path_output.clone();
     path_output.set_extension("png");
 
-    (item..(item + 1)).into_par_iter().for_each(|k| {
+    (item..(item + 1)).into_par_iter().try_for_each(|k| -> Result<()> {
         let model_fit {
             humps: humps,
             fitted_model: fitted_model,
             initial_model: initial_model,
             residual_total: residual_total,
             residual_per_point: residual_per_point,
-        } = &VV[k];
+            ..
+        } = fit_at(&VV, k)?;
         let mx = eval_M(&fitted_model, &etsplice);
-        //(* flanking prediction *)
-        let partial_model {
-            before: pre,
-            after: post,
-        } = pmod;
-
-        let ipre: Vec<usize> = tall
-            .iter()
-            .enumerate()
-            .filter(|(k, g)| **g < *etsplice.first().unwrap())
-            .map(|(k, v)| k)
-            .collect();
-        let ipre: Vec<usize> = ipre.iter().rev().take(pre).rev().map(|k| *k).collect();
-        let tpre: Vec<f64> = tall
-            .iter()
-            .filter(|g| **g < *etsplice.first().unwrap())
-            .map(|v| *v)
-            .collect();
-        let tpre: Vec<f64> = tpre.iter().rev().take(pre).rev().map(|v| *v).collect(); //tpre[ 0.max(tpre.len() - pre).. ].to_vec();
-        let tpost: Vec<f64> = tall
-            .iter()
-            .filter(|g| **g > *etsplice.last().unwrap())
-            .map(|v| *v)
-            .take(post)
-            .collect();
-        let ipost: Vec<usize> = tall
-            .iter()
-            .enumerate()
-            .filter(|(k, g)| **g > *etsplice.last().unwrap())
-            .map(|(k, g)| k)
-            .take(post)
-            .collect();
+        let tpre: Vec<f64> = ipre.iter().map(|k| tall[*k]).collect();
+        let tpost: Vec<f64> = ipost.iter().map(|k| tall[*k]).collect();
 
         let mxall: Vec<f64> = eval_M(&fitted_model, &tall);
         let mxpre: Vec<f64> = eval_M(&fitted_model, &tpre);
@@ -503,7 +501,7 @@ pub fn disp_visualization(
 
         let i_viz: Vec<usize> = ipre
             .iter()
-            .chain(etslice.iter().chain(ipost.iter()))
+            .chain(imod.iter().chain(ipost.iter()))
             .map(|k| *k)
             .collect();
         //let res_pre : Vec<f64> = ipre.iter().map(|k|{resid1xx[*k]}).collect();
@@ -512,10 +510,10 @@ pub fn disp_visualization(
         ///////////////
         /// form envelopes
         let res_viz: Vec<f64> = i_viz.iter().map(|k| resid1xx[*k]).collect();
-        //let dat_viz : Vec<f64> = i_viz.iter().map(|k|{dall[*k]}).collect();
+        let t_viz: Vec<f64> = i_viz.iter().map(|k| tall[*k]).collect();
         let title_string = match( &title ){
             Some(t) => t.clone(),
-            None => String::from(format!("model fit {} \n LSM {} ", k, lsm)),
+            None => format!("model fit {} \n LSM {} ", k, lsm),
         };
         let xlabel_string = match( &xlabel ){
             Some(s) => s.clone(),
@@ -526,10 +524,10 @@ pub fn disp_visualization(
             None => String::from("displacements"),
         };
         viz_lib2::plot_e(
-            &tall,
+            &t_viz,
             &res_viz,
             viz_lib2::PlotAction::PNG(
-                format!("{}", output_stem.to_str().unwrap()).into(),
+                format!("{}", output_stem.to_string_lossy()).into(),
                 800,
                 600,
                 2.0,
@@ -538,8 +536,9 @@ pub fn disp_visualization(
             xlabel_string,
             ylabel_string,
         );
-        println!("writing file {}", output_stem.to_str().unwrap())
-    });
+        println!("writing file {}", output_stem.to_string_lossy());
+        Ok(())
+    })
 }
 
 /////////////////////////////////
@@ -555,92 +554,44 @@ pub fn skew_visualization(
     pmod: partial_model,
     title: Option<String>, 
     xlabel: Option<String>,
-    ylabel: Option<String>
-) {
-    let mut path = PathBuf::from(models.as_str()); // get the model file.
-    path.set_extension("yml");
-    let mut data_file =
-        File::open(path).expect("open model(s) file failed, please insure the extention is yml");
-    let mut reader = BufReader::new(data_file);
-    let VX: data_fit_package<ModelTanh> =
-        serde_yaml::from_reader(reader).expect("problem reading yaml file");
+    ylabel: Option<String>,
+    window: Option<StatWindow>,
+    stat: MomentStat,
+) -> Result<()> {
+    let VX: data_fit_package<ModelTanh> = read_fit_package(&models)?; // get the model file.
     let data_fit_package {
         load_metadata: lmd,
         fits: VV,
+        ..
     } = VX;
-    let ((tspan, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd);
+    let ((tspan, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd)?;
 
     // read the model file.
     // step 4: calculate the clipping slice or viz slice
-    let eoffset = offset.unwrap_or(0_i64) as usize;
-    let elimit: usize = limit.unwrap_or(dfull.shape()[0]);
-    let estrides = strides.unwrap_or(1);
+    let (eoffset, elimit, estrides) = viz_slice(offset, limit, strides, dfull.shape()[0])?;
     // All time/data in the viz slice
-    let tall: Vec<f64> = tfull
-        .slice(s![eoffset..(eoffset+elimit); estrides])
-        .to_vec();
-    let dall: Vec<f64> = dfull
-        .slice(s![eoffset..(eoffset+elimit); estrides])
-        .to_vec();
-
-    if (estrides > 1) {
-        panic!(" estrides Not yet supported ");
-    }
-    // model (time) clipped to viz slice.  tsplice is the model support (in global data index), now we further restrict that to viz_slice
-    let ETS: Vec<(usize, &f64)> = tsplice
-        .iter()
-        .enumerate()
-        .filter(|(k, g)| ((**g as usize) >= eoffset) && ((**g as usize) < (eoffset + elimit)))
-        .collect();
-    let etsplice: Vec<f64> = ETS.iter().map(|(a, b)| **b).collect();
-    let etslice: Vec<usize> = ETS.iter().map(|(a, b)| *a).collect();
+    let (tall, dall) = viz_rows(&tfull, &dfull, eoffset, elimit, estrides);
+    // the model is drawn on the viz rows inside the fitted support, flanked by up to `before`/`after` rows.
+    let (ipre, imod, ipost) = support_rows(&tall, tsplice.as_slice(), pmod.before, pmod.after)?;
+    let etsplice: Vec<f64> = imod.iter().map(|k| tall[*k]).collect();
 
     let mut path_output = PathBuf::from(output.as_str());
     path_output.set_extension("");
     let output_stem = path_output.clone();
     path_output.set_extension("png");
 
-    (item..(item + 1)).into_par_iter().for_each(|k| {
+    (item..(item + 1)).into_par_iter().try_for_each(|k| -> Result<()> {
         let model_fit {
             humps: humps,
             fitted_model: fitted_model,
             initial_model: initial_model,
             residual_total: residual_total,
             residual_per_point: residual_per_point,
-        } = &VV[k];
+            ..
+        } = fit_at(&VV, k)?;
         let mx = eval_M(&fitted_model, &etsplice);
-        //(* flanking prediction *)
-        let partial_model {
-            before: pre,
-            after: post,
-        } = pmod;
-
-        let ipre: Vec<usize> = tall
-            .iter()
-            .enumerate()
-            .filter(|(k, g)| **g < *etsplice.first().unwrap())
-            .map(|(k, v)| k)
-            .collect();
-        let ipre: Vec<usize> = ipre.iter().rev().take(pre).rev().map(|k| *k).collect();
-        let tpre: Vec<f64> = tall
-            .iter()
-            .filter(|g| **g < *etsplice.first().unwrap())
-            .map(|v| *v)
-            .collect();
-        let tpre: Vec<f64> = tpre.iter().rev().take(pre).rev().map(|v| *v).collect(); //tpre[ 0.max(tpre.len() - pre).. ].to_vec();
-        let tpost: Vec<f64> = tall
-            .iter()
-            .filter(|g| **g > *etsplice.last().unwrap())
-            .map(|v| *v)
-            .take(post)
-            .collect();
-        let ipost: Vec<usize> = tall
-            .iter()
-            .enumerate()
-            .filter(|(k, g)| **g > *etsplice.last().unwrap())
-            .map(|(k, g)| k)
-            .take(post)
-            .collect();
+        let tpre: Vec<f64> = ipre.iter().map(|k| tall[*k]).collect();
+        let tpost: Vec<f64> = ipost.iter().map(|k| tall[*k]).collect();
 
         let mxall: Vec<f64> = eval_M(&fitted_model, &tall);
         let mxpre: Vec<f64> = eval_M(&fitted_model, &tpre);
@@ -654,7 +605,7 @@ pub fn skew_visualization(
 
         let i_viz: Vec<usize> = ipre
             .iter()
-            .chain(etslice.iter().chain(ipost.iter()))
+            .chain(imod.iter().chain(ipost.iter()))
             .map(|k| *k)
             .collect();
         //let res_pre : Vec<f64> = ipre.iter().map(|k|{resid1xx[*k]}).collect();
@@ -663,29 +614,15 @@ pub fn skew_visualization(
         ///////////////
         /// form envelopes
         let res_viz: Vec<f64> = i_viz.iter().map(|k| resid1xx[*k]).collect();
-        //let dat_viz : Vec<f64> = i_viz.iter().map(|k|{dall[*k]}).collect();
-        ////
-        /// m_3( m ) = sum_j^m( r[j] - <r[:m]> )^3 / ((m-1) [r[:m])
-        let mu3: Vec<f64> = (1..)
-            .take(res_viz.len() + 1)
-            .map(|m| {
-                let avg_resid: f64 = res_viz.iter().take(m).sum::<f64>() / (m as f64);
-                let resid_disp: Vec<f64> = res_viz.iter().take(m).map(|x| *x - avg_resid).collect();
-                let resid_disp_sq: Vec<f64> = resid_disp.iter().map(|x| (*x) * (*x)).collect();
-                let resid_disp_cu: Vec<f64> =
-                    resid_disp.iter().map(|x| (*x) * (*x) * (*x)).collect();
-                let sv = sqrt(resid_disp_sq.iter().sum::<f64>() / (m as f64));
-                let mut dnom: f64 = sv * sv * sv * ((m - 1) as f64);
-                if dnom <= 0.0 {
-                    dnom = 1.0;
-                }
-                let m_3: f64 = resid_disp_cu.iter().sum::<f64>() / (dnom);
-                m_3
-            })
-            .collect();
+        let t_viz: Vec<f64> = i_viz.iter().map(|k| tall[*k]).collect();
+        // the expanding skew unless another window or statistic is asked for.
+        let values: Vec<f64> = match (window, stat) {
+            (None, MomentStat::Skew) => api::expanding_skew(&res_viz),
+            (window, stat) => api::rolling_moments(&res_viz, window.unwrap_or_default())?.stat(stat).clone(),
+        };
         let title_string = match( &title ){
             Some(t) => t.clone(),
-            None => String::from(format!("model fit {} \n LSM {} ", k, lsm)),
+            None => format!("model fit {} \n LSM {} ", k, lsm),
         };
         let xlabel_string = match( &xlabel ){
             Some(s) => s.clone(),
@@ -693,13 +630,16 @@ pub fn skew_visualization(
         };
         let ylabel_string = match( &ylabel ){
             Some(s) => s.clone(),
-            None => String::from("skew"),
+            None => match window {
+                Some(window) => format!("{stat:?} ({window:?})").to_lowercase(),
+                None => format!("{stat:?}").to_lowercase(),
+            },
         };      
         viz_lib2::plot_e(
-            &tall,
-            &mu3,
+            &t_viz,
+            &values,
             viz_lib2::PlotAction::PNG(
-                format!("{}", output_stem.to_str().unwrap() ).into(),
+                format!("{}", output_stem.to_string_lossy() ).into(),
                 800,
                 600,
                 2.0,
@@ -708,8 +648,9 @@ pub fn skew_visualization(
             xlabel_string,
             ylabel_string,
         );
-        println!("writing file {}", output_stem.to_str().unwrap())
-    });
+        println!("writing file {}", output_stem.to_string_lossy());
+        Ok(())
+    })
 }
 /* 
     let elimit = match( limit ){
@@ -737,21 +678,16 @@ pub fn intermediate_visualization(
     title: Option<String>, 
     xlabel: Option<String>,
     ylabel: Option<String>
-) {
-    let mut path = PathBuf::from(models.as_str());
-    path.set_extension("yml");
-    let mut data_file =
-        File::open(path).expect("open model(s) file failed, please insure the extention is yml");
-    let mut reader = BufReader::new(data_file);
-    let VX: data_fit_package<ModelTanh> =
-        serde_yaml::from_reader(reader).expect("problem reading yaml file");
+) -> Result<()> {
+    let VX: data_fit_package<ModelTanh> = read_fit_package(&models)?;
     let data_fit_package {
         load_metadata: lmd,
         fits: VV,
+        ..
     } = VX;
     //let V:  Vec<(f64, AffineAdditive<ModelTanh>, AffineAdditive<ModelTanh>)>
 
-    let ((tspan, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd);
+    let ((tspan, tsplice), dsplice, (tfull, dfull)) = reload_data(lmd)?;
 
     println!(" {}, {}, {}", models, output, item);
     println!(" {:?}\n{:?}\n", tspan, dsplice);
@@ -764,18 +700,7 @@ pub fn intermediate_visualization(
     let output_stem = path_output.clone();
     path_output.set_extension("png");
 
-    let elimit = match (limit) {
-        Some(x) => x,
-        None => dfull.shape()[0],
-    };
-    let estrides = match (strides) {
-        Some(x) => x,
-        None => 1,
-    };
-    let eoffset = match (offset) {
-        Some(x) => x as usize,
-        None => 0_usize,
-    };
+    let (eoffset, elimit, estrides) = viz_slice(offset, limit, strides, dfull.shape()[0])?;
 
     let k = item;
     {
@@ -785,15 +710,16 @@ pub fn intermediate_visualization(
             initial_model: initial_model,
             residual_total: residual_total,
             residual_per_point: residual_per_point,
-        } = &VV[k];
+            ..
+        } = fit_at(&VV, k)?;
 
-        let mx = eval_M(&fitted_model, &tspan.as_slice().to_vec());
+        let mx = eval_M(&fitted_model, &tsplice.as_slice().to_vec());
         let lsm = residual_total;
         let lsmpp = residual_per_point;
 
         let title_string = match( &title ){
             Some(t) => t.clone(),
-            None => String::from(format!("model fit {} \n LSM {} ", k, lsm)),
+            None => format!("model fit {} \n LSM {} ", k, lsm),
         };
         let xlabel_string = match( &xlabel ){
             Some(s) => s.clone(),
@@ -803,15 +729,16 @@ pub fn intermediate_visualization(
             Some(s) => s.clone(),
             None => String::from("quantity"),
         }; 
+        let (tall, dall) = viz_rows(&tfull, &dfull, eoffset, elimit, estrides);
         viz_lib::plot_model_with_markers(
-            &tfull.slice(s![eoffset..elimit; estrides]).to_vec(),
-            &dfull.slice(s![eoffset..elimit; estrides]).to_vec(),
+            &tall,
+            &dall,
             &tsplice.as_slice().to_vec(),
             &mx,
             fitted_model.clone(),
             0.20,
             PlotAction::PNG(
-                format!("{}", output_stem.to_str().unwrap()).into(),
+                format!("{}", output_stem.to_string_lossy()).into(),
                 800,
                 600,
                 2.0,
@@ -820,51 +747,23 @@ pub fn intermediate_visualization(
     xlabel_string,
             ylabel_string,
         );
-        println!("writing file {}", output_stem.to_str().unwrap())
+        println!("writing file {}", output_stem.to_string_lossy())
     }
+    Ok(())
 }
  
-//use polars::{df, prelude::{CsvReader, PolarsResult, DataFrame, SerReader}};
-
-use polars::prelude::QuantileInterpolOptions;
-use polars::{df, prelude::*};
 #[test]
-fn testit() {
-    let quantile_params = vec![0.0, 0.1, 0.5, 0.9, 1.0];
-    let data = vec![1., 2., 3., 43., 5., 6., 7., 78.];
-    rolling_quantiles(data, quantile_params);
+fn test_support_rows_strided() {
+    // viz every 3rd row, model fitted on rows 4..=10 at stride 1.
+    let tfull = Array1::from_iter((0..17).map(|k| k as f64));
+    let (tall, dall) = viz_rows(&tfull, &tfull, 0, 17, 3);
+    assert_eq!(tall, vec![0., 3., 6., 9., 12., 15.]);
+    assert_eq!(dall.len(), tall.len());
+    let tsplice: Vec<f64> = (4..=10).map(|k| k as f64).collect();
+    let (ipre, imod, ipost) = support_rows(&tall, &tsplice, 1, 5).unwrap();
+    assert_eq!((ipre, imod, ipost), (vec![1], vec![2, 3], vec![4, 5]));
+    assert!(support_rows(&tall, &[16.], 0, 0).is_err());
 }
-pub fn rolling_quantiles(data: Vec<f64>, quantile_params: Vec<f64>) -> Vec<Vec<f64>> {
-    // EDIT POINT .. TRYING TO TEST THE ORDER STATS OR quantiles.
-
-    let df: DataFrame = df!("stats" => data.as_slice()).unwrap();
-    print!("stats {:?}", df["stats"]);
-
-    let mut quantiles: Vec<Vec<f64>> = (1..df.height() + 1)
-        .into_iter()
-        .map(|row| {
-            let val: Vec<f64> = quantile_params
-                .iter()
-                .map(|cut| {
-                    let dstar = df.slice(0, row)["stats"]
-                        .quantile_as_series(*cut, QuantileInterpolOptions::Linear)
-                        .unwrap();
-                    dstar.f64().expect("issues").get(0).expect("issue ")
-                })
-                .collect();
-            val
-        })
-        .collect();
-
-    let qtraces: Vec<Vec<f64>> = (0..quantile_params.len())
-        .map(|k| (0..quantiles.len()).map(|v| quantiles[v][k]).collect())
-        .collect();
-    print!("fruit mids {:?}", df["stats"]);
-    print!(" quantile {:?}", quantiles);
-    print!(" qutraces {:?}", qtraces);
-    qtraces
-}
-
 //use polars::{df, prelude::{CsvReader, PolarsResult, DataFrame, SerReader}};
 /* 
 use polars::{prelude::*, df};
@@ -3,6 +3,7 @@ use serde::{Serialize, Deserialize};
 use rand_distr::{Geometric, Distribution};
 use varpro::prelude::SeparableModelBuilder;
 use super::{ParameterizedModel, VarProAdapter, ModelTanh};
+use crate::error::{HumptyError, Result};
 
 /// The idea here is to paste together other models implementing the paramterized model
 /// 
@@ -54,11 +55,12 @@ impl<M> ModelAdditive<M> where M : ParameterizedModel + Clone  + VarProAdapter{
 
     pub fn build_varpro_separable_model (&self,  
         tspan: nalgebra::Matrix<f64, nalgebra::Dyn, Const<1>, nalgebra::VecStorage<f64, nalgebra::Dyn, Const<1>>>, 
-        ) -> varpro::model::SeparableModel<f64>{
+        ) -> Result<varpro::model::SeparableModel<f64>>{
             let labels = self.separable_labels();
             let model = SeparableModelBuilder::<f64>::new(labels.as_slice() )
             .independent_variable(tspan);
-            let first_mod = self.components.get(0).unwrap();
+            let first_mod = self.components.get(0)
+                .ok_or_else( || HumptyError::Solver( String::from( "the model needs at least one hump" )))?;
             let model_first: varpro::model::builder::SeparableModelBuilderProxyWithDerivatives<f64> = 
             {
                 let suffix = format!("_{}", 0 );
@@ -85,8 +87,8 @@ impl<M> ModelAdditive<M> where M : ParameterizedModel + Clone  + VarProAdapter{
             let model_last = model_rest.invariant_function(|x|DVector::from_element(x.len(),1.))
                 .initial_parameters(self.get_separable_params())
                 .build()
-                .unwrap();
-            model_last 
+                .map_err( |e| HumptyError::Solver( format!( "cannot build separable model: {e}" )))?;
+            Ok( model_last )
 
     }
 
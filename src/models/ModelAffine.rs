@@ -4,41 +4,87 @@ use varpro::solvers::levmar::{LevMarProblemBuilder, LevMarSolver};
 use varpro::prelude::LeastSquaresProblem;
 
 use crate::models::{ParameterizedModel, ModelAdditive::ModelAdditive, ModelConstant::ModelConstant};
+use crate::error::{HumptyError, Result};
+use crate::transform::ValueTransform;
 
-use super::VarProAdapter;
+use super::{nan_if_null, VarProAdapter};
+
+/// Outcome of a single levmar solve.
+///     success :        the solver converged and the fitted parameters/residual are finite
+///     termination :    levmar termination reason (or the divergence that was detected afterwards)
+///     evaluations :    residual evaluations used by levmar (`iterations` in older packages)
+///     gradient_norm :  |J^T r| at the final parameters (NaN when it cannot be evaluated)
+#[derive(Debug, Serialize, Deserialize, Clone )]
+pub struct FitDiagnostics {
+    pub success : bool,
+    pub termination : String,
+    #[serde(alias = "iterations")]
+    pub evaluations : usize,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub gradient_norm : f64,
+}
+
+/// packages written before diagnostics were recorded carry no solver information.
+impl Default for FitDiagnostics {
+    fn default() -> Self {
+        Self { success: true, termination: String::from("unrecorded"), evaluations: 0, gradient_norm: f64::NAN }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone )]
 pub struct AffineAdditive<M> where M : ParameterizedModel + Clone  + VarProAdapter {
     pub tm : ModelAdditive<M>,  
-    pub km : ModelConstant
+    pub km : ModelConstant,
+    /// value transform the model was fitted under, `eval` maps back to data units through it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link : Option<ValueTransform>,
 }
 
 impl<M> AffineAdditive<M> where M : ParameterizedModel + Clone + VarProAdapter {
-    pub fn new(tm: ModelAdditive<M>, km: ModelConstant) -> Self { Self { tm, km } }
+    pub fn new(tm: ModelAdditive<M>, km: ModelConstant) -> Self { Self { tm, km, link: None } }
 
-    /// EDIT POINT - We are here.
+    /// the model value before the link, i.e. in the space it was fitted in.
+    pub fn eval_fitted( &self, t : f64 ) -> f64 {
+        self.tm.eval( t ) + self.km.eval( t )
+    }
+
+    /// fit the model to data with varpro/levmar starting from the current parameters.
+    /// The parameters are only updated when levmar terminates successfully, the returned
+    /// diagnostics record the termination reason either way.  An error is only returned when
+    /// the least squares problem itself cannot be formed (e.g. fewer points than parameters).
     pub fn curve_fit( &mut self , 
         tspan : &Matrix<f64, Dyn, Const<1>, nalgebra::VecStorage<f64, Dyn, Const<1>>>, 
         data : &Matrix<f64, Dyn, Const<1>, nalgebra::VecStorage<f64, Dyn, Const<1>>>, 
-    ) -> Result<bool, String>{
+    ) -> Result<FitDiagnostics> {
 
-        let model = self.tm.build_varpro_separable_model( tspan.clone() );
+        let model = self.tm.build_varpro_separable_model( tspan.clone() )?;
         //println!( " MODEL:  {:?}", model); 
         //println!(" forming problem"); 
         let problem = LevMarProblemBuilder::new(model)
             .observations(data.clone())
             .build()
-            .unwrap(); 
+            .map_err( |e| HumptyError::Solver( format!( "cannot build least squares problem: {e}" )))?; 
 
         //println!( " problem : {:?} ", &problem);  
         let (solved_problem, report) = LevMarSolver::new().minimize(problem);
 
-        if ! report.termination.was_successful(){
-            return Err( String::from("NevMarSolv -- Error"))
+        let gradient_norm = match ( solved_problem.residuals(), solved_problem.jacobian() ) {
+            ( Some(r), Some(j) ) => ( j.transpose() * r ).norm(),
+            _ => f64::NAN,
+        };
+        let mut diagnostics = FitDiagnostics {
+            success : report.termination.was_successful(),
+            termination : format!( "{:?}", report.termination ),
+            evaluations : report.number_of_evaluations,
+            gradient_norm,
+        };
+        if ! diagnostics.success {
+            return Ok( diagnostics )
         } 
         //println!( " \n\n ----- \n\n ");
         let alpha = solved_problem.params();
-        let coeff = solved_problem.linear_coefficients().unwrap();
+        let coeff = solved_problem.linear_coefficients()
+            .ok_or_else( || HumptyError::Solver( String::from( "no linear coefficients after a successful solve" )))?;
         //now we must splice these together for parameter adjustment.
         let mut RVV: Vec<f64> = Vec::new();
         let mut k1 = 0; 
@@ -57,9 +103,14 @@ impl<M> AffineAdditive<M> where M : ParameterizedModel + Clone + VarProAdapter {
             }
         );
         RVV.push( coeff[k1] );
+        if RVV.iter().any( |p| ! p.is_finite() ) {
+            diagnostics.success = false;
+            diagnostics.termination = format!( "Diverged (non-finite parameters after {})", diagnostics.termination );
+            return Ok( diagnostics )
+        }
         self.set_all_params( RVV.as_slice() );
 
-        Ok( true ) 
+        Ok( diagnostics )
     }   
 
 
@@ -128,10 +179,13 @@ where M : ParameterizedModel + Clone  + VarProAdapter{
          rv 
     }
     fn eval( &self, t : f64 ) -> f64{
-        self.tm.eval( t ) + self.km.eval(t )
+        match &self.link {
+            Some( link ) => link.inverse( self.eval_fitted( t ) ),
+            None => self.eval_fitted( t ),
+        }
     }
     fn get_copy( &self ) -> Self {
-        Self::new( self.tm.get_copy(), self.km.get_copy() )
+        Self { tm: self.tm.get_copy(), km: self.km.get_copy(), link: self.link.clone() }
     }
     fn mute( &mut self, rng : &rand::rngs::ThreadRng, mag : f64 , var : Option<&Self>){
         self.tm.mute( rng, mag , match var {Some(v) => Some(&v.tm), _ => None });
@@ -152,4 +206,9 @@ fn test_1(){
     let Y = [1.2, 3.3, 4.4, 5.5, 6.6 ];
     println!( "{:?}", &Y[(Y.len()-1)..(Y.len())]);
     println!( "{:?}", &Y[..(Y.len()-1)]);
-}
\ No newline at end of file
+}
+#[test]
+fn test_diagnostics_iterations_alias(){
+    let d : FitDiagnostics = serde_yaml::from_str( "success: true\ntermination: Converged\niterations: 7\ngradient_norm: 0.5" ).unwrap();
+    assert_eq!( d.evaluations, 7 );
+}
@@ -8,6 +8,11 @@ pub mod ModelTanh;
 pub mod ModelAdditive;
 pub mod ModelAffine; 
 
+/// JSON has no NaN and serde_json writes non-finite numbers as null, fields that may hold NaN read null back as NaN.
+pub fn nan_if_null<'de, D : serde::Deserializer<'de>>( d : D ) -> Result<f64, D::Error> {
+    Ok( <Option<f64> as serde::Deserialize>::deserialize( d )?.unwrap_or( f64::NAN ) )
+}
+
 pub trait ParameterizedModel{
     fn get_all_params(&self) -> Vec<f64>;
     fn set_all_params(&mut self, p: &[f64] );
@@ -0,0 +1,206 @@
+use chrono::{Datelike, Months, NaiveDate, Weekday};
+use polars::prelude::{DataFrame, DataType, IntoSeries, NamedFrom, Series};
+use serde::{Serialize, Deserialize};
+
+use crate::error::{HumptyError, Result};
+use crate::main_mod_dm_fit::{column_to_f64, TimeAxis, AxisUnit};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq )]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    Week,
+    Month,
+}
+
+impl std::str::FromStr for Period {
+    type Err = HumptyError;
+    fn from_str( s : &str ) -> Result<Self> {
+        match s {
+            "week" => Ok( Period::Week ),
+            "month" => Ok( Period::Month ),
+            _ => Err( HumptyError::parse( "resample period", format!( "'{s}' is not one of week, month" ))),
+        }
+    }
+}
+
+/// how the observations of one period are combined, missing values are skipped.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default )]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregate {
+    #[default]
+    Sum,
+    Last,
+    Mean,
+}
+
+impl std::str::FromStr for Aggregate {
+    type Err = HumptyError;
+    fn from_str( s : &str ) -> Result<Self> {
+        match s {
+            "sum" => Ok( Aggregate::Sum ),
+            "last" => Ok( Aggregate::Last ),
+            "mean" => Ok( Aggregate::Mean ),
+            _ => Err( HumptyError::parse( "resample aggregate", format!( "'{s}' is not one of sum, last, mean" ))),
+        }
+    }
+}
+
+////////////////
+///
+/// one step of the chain turning the loaded rows into the training series, applied in order:
+///
+/// ```yaml
+/// - { step: resample, period: week, anchor: wed, how: last }
+/// - { step: diff }
+/// ```
+///
+/// resample:  bins the rows by calendar period (weeks starting on the `anchor` weekday, or months starting on
+///            the `anchor` day of the month), each period is labelled by its first day.  Periods not fully covered
+///            by the data are dropped unless `partial` is set, so a trailing half week does not look like a drop.
+/// diff:      v[k] - v[k-1], the first row becomes missing.
+/// cumulate:  running sum, missing rows stay missing.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq )]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum SeriesStep {
+    Resample {
+        period : Period,
+        /// weekday (mon .. sun) for weeks, day of the month (1 .. 28) for months.
+        anchor : String,
+        #[serde(default)]
+        how : Aggregate,
+        #[serde(default)]
+        partial : bool,
+    },
+    Diff,
+    Cumulate,
+}
+
+impl SeriesStep {
+    /// a resample step, the anchor defaults to monday for weeks and to the first for months.
+    pub fn resample( period : Period, anchor : Option<String>, how : Aggregate, partial : bool ) -> Result<Self> {
+        let anchor = anchor.unwrap_or_else( || String::from( match period { Period::Week => "mon", Period::Month => "1" } ));
+        let step = SeriesStep::Resample { period, anchor, how, partial };
+        step.period_start( NaiveDate::default() )?;
+        Ok( step )
+    }
+
+    /// first day of the period holding `d` (resample only).
+    fn period_start( &self, d : NaiveDate ) -> Result<NaiveDate> {
+        let SeriesStep::Resample { period, anchor, .. } = self else { return Ok( d ) };
+        match period {
+            Period::Week => {
+                let w : Weekday = anchor.parse().map_err( |_| HumptyError::parse( "resample anchor", format!( "'{anchor}' is not a weekday (mon .. sun)" )))?;
+                let back = ( 7 + d.weekday().num_days_from_monday() - w.num_days_from_monday() ) % 7;
+                Ok( d - chrono::Duration::days( back as i64 ) )
+            },
+            Period::Month => {
+                let a : u32 = anchor.parse().ok().filter( |a| ( 1..=28 ).contains( a ) ).ok_or_else(
+                    || HumptyError::parse( "resample anchor", format!( "'{anchor}' is not a day of the month (1 .. 28)" )))?;
+                let this = d.with_day( a ).unwrap();
+                Ok( if d.day() >= a { this } else { this - Months::new( 1 ) } )
+            },
+        }
+    }
+
+    fn period_end( &self, start : NaiveDate ) -> NaiveDate {
+        match self {
+            SeriesStep::Resample { period: Period::Month, .. } => start + Months::new( 1 ),
+            _ => start + chrono::Duration::days( 7 ),
+        }
+    }
+
+    /// apply the step to a frame, `time_column` is needed to resample and must hold dates.
+    pub fn apply( &self, df : &DataFrame, value_column : &str, time_column : Option<&str> ) -> Result<DataFrame> {
+        let values = column_to_f64( df.column( value_column ).map_err( |_| HumptyError::MissingColumn {
+            column: value_column.to_string(),
+            available: df.get_column_names().iter().map( |x| x.to_string() ).collect() })? )?.to_vec();
+        let values = match self {
+            SeriesStep::Resample { .. } => {
+                let column = time_column.ok_or_else( || HumptyError::parse( "resample", "resampling needs a date column (--date-col)" ))?;
+                return self.resample_frame( df, &values, column, value_column )
+            },
+            SeriesStep::Diff => ( 0..values.len() ).map( |k| if k == 0 { f64::NAN } else { values[k] - values[k - 1] } ).collect(),
+            SeriesStep::Cumulate => {
+                let mut total = 0.;
+                values.iter().map( |v| if v.is_finite() { total += v; total } else { f64::NAN } ).collect::<Vec<f64>>()
+            },
+        };
+        let mut out = df.clone();
+        out.replace( value_column, Series::new( value_column, values ) )?;
+        Ok( out )
+    }
+
+    fn resample_frame( &self, df : &DataFrame, values : &[f64], time_column : &str, value_column : &str ) -> Result<DataFrame> {
+        let SeriesStep::Resample { how, partial, .. } = self else { unreachable!() };
+        let axis = TimeAxis::new( time_column.to_string(), AxisUnit::Day, None ).resolve( df )?;
+        if axis.numeric {
+            return Err( HumptyError::parse( "resample", format!( "time column {time_column} holds numbers, resampling needs dates" )))
+        }
+        let origin = chrono::NaiveDate::parse_from_str( &axis.date( 0. ), "%Y-%m-%d" ).map_err( |e| HumptyError::parse( "resample", e ))?;
+        let mut rows : Vec<(NaiveDate, f64)> = axis.times( df )?.iter().zip( values.iter() )
+            .filter( |(t, _)| t.is_finite() )
+            .map( |(t, v)| ( origin + chrono::Duration::days( t.floor() as i64 ), *v ) )
+            .collect();
+        rows.sort_by_key( |(d, _)| *d );
+        let ( Some( first ), Some( last ) ) = ( rows.first().map( |r| r.0 ), rows.last().map( |r| r.0 ) ) else {
+            return Err( HumptyError::parse( "resample", "no rows with a date" ))
+        };
+
+        let mut periods : Vec<(NaiveDate, Vec<f64>)> = Vec::new();
+        for (d, v) in rows {
+            let start = self.period_start( d )?;
+            match periods.last_mut() {
+                Some( (s, vs) ) if *s == start => vs.push( v ),
+                _ => periods.push( ( start, vec![ v ] ) ),
+            }
+        }
+        if ! partial {
+            periods.retain( |(s, _)| first <= *s && last >= self.period_end( *s ) - chrono::Duration::days( 1 ) );
+            if periods.is_empty() {
+                return Err( HumptyError::parse( "resample", "no period is fully covered by the data, use a partial resample" ))
+            }
+        }
+
+        let epoch = NaiveDate::from_ymd_opt( 1970, 1, 1 ).unwrap();
+        let days : Vec<i32> = periods.iter().map( |(s, _)| ( *s - epoch ).num_days() as i32 ).collect();
+        let totals : Vec<f64> = periods.iter().map( |(_, vs)| {
+            let present : Vec<f64> = vs.iter().copied().filter( |v| v.is_finite() ).collect();
+            match ( how, present.last() ) {
+                ( _, None ) => f64::NAN,
+                ( Aggregate::Sum, _ ) => present.iter().sum(),
+                ( Aggregate::Mean, _ ) => present.iter().sum::<f64>() / present.len() as f64,
+                ( Aggregate::Last, Some( v ) ) => *v,
+            }
+        }).collect();
+        let date = Series::new( time_column, days ).cast( &DataType::Date )?;
+        Ok( DataFrame::new( vec![ date.into_series(), Series::new( value_column, totals ) ])? )
+    }
+}
+
+/// apply a chain of steps in order.
+pub fn apply_steps( steps : &[SeriesStep], df : DataFrame, value_column : &str, time_column : Option<&str> ) -> Result<DataFrame> {
+    steps.iter().try_fold( df, |df, step| step.apply( &df, value_column, time_column ) )
+}
+
+#[test]
+fn test_resample_week_then_diff() {
+    // cumulative daily counts from a wednesday over 15 days: weeks wed..tue, the last (one day) week is partial.
+    let start = NaiveDate::from_ymd_opt( 2020, 1, 22 ).unwrap();
+    let epoch = NaiveDate::from_ymd_opt( 1970, 1, 1 ).unwrap();
+    let days : Vec<i32> = ( 0..15 ).map( |k| ( start - epoch ).num_days() as i32 + k ).collect();
+    let counts : Vec<f64> = ( 0..15 ).map( |k| ( k * k ) as f64 ).collect();
+    let df = DataFrame::new( vec![ Series::new( "date", days ).cast( &DataType::Date ).unwrap(), Series::new( "count", counts ) ]).unwrap();
+
+    let weekly = SeriesStep::resample( Period::Week, Some( String::from( "wed" ) ), Aggregate::Last, false ).unwrap();
+    let out = apply_steps( &[ weekly, SeriesStep::Diff ], df.clone(), "count", Some( "date" ) ).unwrap();
+    let values : Vec<f64> = out.column( "count" ).unwrap().f64().unwrap().into_no_null_iter().collect();
+    assert_eq!( values.len(), 2 );
+    assert!( values[0].is_nan() );
+    assert_eq!( values[1], 169. - 36. );
+
+    let sums = SeriesStep::resample( Period::Week, Some( String::from( "wed" ) ), Aggregate::Sum, true ).unwrap();
+    let out = apply_steps( &[ sums ], df, "count", Some( "date" ) ).unwrap();
+    let values : Vec<f64> = out.column( "count" ).unwrap().f64().unwrap().into_no_null_iter().collect();
+    assert_eq!( values, vec![ 91., 728., 196. ] );
+    assert!( SeriesStep::resample( Period::Month, Some( String::from( "31" ) ), Aggregate::Sum, false ).is_err() );
+}
@@ -0,0 +1,150 @@
+//! Running statistics and change detector alarms on the residuals of a fitted model, re-exported by `api`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::Series;
+use crate::error::{HumptyError, Result};
+use crate::models::ModelAffine::AffineAdditive;
+use crate::models::ModelTanh::ModelTanh;
+use crate::stats::{detect_changes, standardise, Alarm, AlarmConfig, MomentAccumulator};
+
+/// change detector alarms on the residuals of `series`, standardised by the mean and sd of the residuals on the
+/// `fitted` rows, so a new growth phase past (or inside) the fit shows as a shift; see `stats::detect_changes`.
+pub fn residual_alarms(
+    model: &AffineAdditive<ModelTanh>,
+    fitted: &Series,
+    series: &Series,
+    config: &AlarmConfig,
+) -> Result<Vec<Alarm>> {
+    let (_, _, _, reference) = model.residual(&fitted.time, &fitted.value);
+    let (_, _, _, residuals) = model.residual(&series.time, &series.value);
+    Ok(detect_changes(&standardise(&residuals, &reference)?, config))
+}
+
+/// m_3( m ) = sum_j^m( r[j] - <r[:m]> )^3 / ((m-1) sd(r[:m])^3)  for each prefix r[:m]
+pub fn expanding_skew(res: &[f64]) -> Vec<f64> {
+    let mut acc = MomentAccumulator::new();
+    res.iter()
+        .map(|r| {
+            acc.push(*r, 1.);
+            acc.expanding_skew()
+        })
+        .collect()
+}
+
+/// window of the running residual statistics at each point:
+///     expanding :       every point up to and including it (as `running_skew`)
+///     trailing(w) :     the last w points, so the early history does not dilute a new surge
+///     exponential(h) :  every point up to it weighted by 2^( -k / h ) for a point k steps back (half-life h)
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StatWindow {
+    #[default]
+    Expanding,
+    Trailing(usize),
+    Exponential(f64),
+}
+
+impl StatWindow {
+    /// a trailing window holds at least one point and a half-life is positive.
+    pub fn check(&self) -> Result<()> {
+        match *self {
+            StatWindow::Trailing(0) => Err(HumptyError::parse("residual window", "a trailing window needs at least one point")),
+            StatWindow::Exponential(h) if h.is_nan() || h <= 0. => {
+                Err(HumptyError::parse("residual window", format!("half-life {h} is not positive")))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// running mean, standard deviation, skewness m3 / m2^(3/2) and excess kurtosis m4 / m2^2 - 3 of the residuals
+/// (weighted population moments, missing residuals skipped); NaN while fewer than two residuals are in the window.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RollingMoments {
+    pub mean: Vec<f64>,
+    pub sd: Vec<f64>,
+    pub skew: Vec<f64>,
+    pub kurtosis: Vec<f64>,
+}
+
+/// one of the running statistics of `RollingMoments`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MomentStat {
+    Mean,
+    Sd,
+    #[default]
+    Skew,
+    Kurtosis,
+}
+
+impl std::str::FromStr for MomentStat {
+    type Err = HumptyError;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "mean" => Ok(MomentStat::Mean),
+            "sd" => Ok(MomentStat::Sd),
+            "skew" => Ok(MomentStat::Skew),
+            "kurtosis" => Ok(MomentStat::Kurtosis),
+            _ => Err(HumptyError::parse("residual statistic", format!("'{s}' is not one of mean, sd, skew, kurtosis"))),
+        }
+    }
+}
+
+impl RollingMoments {
+    pub fn stat(&self, stat: MomentStat) -> &Vec<f64> {
+        match stat {
+            MomentStat::Mean => &self.mean,
+            MomentStat::Sd => &self.sd,
+            MomentStat::Skew => &self.skew,
+            MomentStat::Kurtosis => &self.kurtosis,
+        }
+    }
+}
+
+pub fn rolling_moments(res: &[f64], window: StatWindow) -> Result<RollingMoments> {
+    window.check()?;
+    let (mut mean, mut sd, mut skew, mut kurtosis) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    let mut acc = MomentAccumulator::new();
+    for (m, r) in res.iter().enumerate() {
+        match window {
+            StatWindow::Expanding => {}
+            StatWindow::Trailing(width) => {
+                if m >= width {
+                    acc.remove(res[m - width], 1.);
+                }
+            }
+            StatWindow::Exponential(halflife) => acc.decay(2f64.powf(-1. / halflife)),
+        }
+        acc.push(*r, 1.);
+        let (x, w, s, k) = acc.moments();
+        mean.push(x);
+        sd.push(w);
+        skew.push(s);
+        kurtosis.push(k);
+    }
+    Ok(RollingMoments { mean, sd, skew, kurtosis })
+}
+
+#[test]
+fn test_rolling_moments_windows() {
+    // a calm alternating stretch followed by a one sided burst: the trailing window forgets the calm rows.
+    let mut res: Vec<f64> = (0..40).map(|k| if k % 2 == 0 { 1. } else { -1. }).collect();
+    res.extend([5., 0., 0., 0.]);
+    let n = res.len();
+    let expanding = rolling_moments(&res, StatWindow::Expanding).unwrap();
+    let trailing = rolling_moments(&res, StatWindow::Trailing(4)).unwrap();
+    assert!(expanding.mean[0].is_nan() && expanding.mean[1] == 0.);
+    assert!((trailing.mean[n - 1] - 1.25).abs() < 1e-12);
+    // 5,0,0,0: m2 = 75/16, m3 = 375/32, m4 = 7/3 m2^2 -> skew 2/sqrt(3), excess kurtosis 7/3 - 3.
+    assert!((trailing.skew[n - 1] - 2. / 3f64.sqrt()).abs() < 1e-12);
+    assert!(trailing.skew[n - 5].abs() < 1e-12 && (trailing.kurtosis[n - 5] + 2.).abs() < 1e-12);
+    assert!((trailing.kurtosis[n - 1] + 2. / 3.).abs() < 1e-12);
+    // a very long half-life approaches the expanding window.
+    let ewm = rolling_moments(&res, StatWindow::Exponential(1e9)).unwrap();
+    assert!((ewm.sd[n - 1] - expanding.sd[n - 1]).abs() < 1e-6);
+    assert!(rolling_moments(&res, StatWindow::Trailing(0)).is_err());
+    assert!(rolling_moments(&res, StatWindow::Exponential(0.)).is_err());
+    assert!(rolling_moments(&res, StatWindow::Exponential(f64::NAN)).is_err());
+}
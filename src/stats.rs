@@ -0,0 +1,660 @@
+use serde::{Serialize, Deserialize};
+
+use crate::error::{HumptyError, Result};
+
+////////////////
+///
+/// streaming residual statistics shared by `csv` and `viz`, each new row costs O(1) for the moments and
+/// O(log n) for the quantiles instead of a pass over the whole prefix.
+///
+/// weighted central moments of a stream, updated one value at a time (Pébay's pairwise update with one side a
+/// single point):
+///     weight :  W = sum w
+///     mean :    sum w x / W
+///     m_p :     sum w ( x - mean )^p  for p = 2, 3, 4
+/// a negative weight takes a value out again (trailing windows) and `decay` scales every weight seen so far
+/// (exponential windows).  Non-finite values are skipped, `count` is the number of values in the window.
+#[derive(Debug, Clone, Copy, Default, PartialEq )]
+pub struct MomentAccumulator {
+    pub count : usize,
+    pub weight : f64,
+    pub mean : f64,
+    pub m2 : f64,
+    pub m3 : f64,
+    pub m4 : f64,
+}
+
+impl MomentAccumulator {
+    pub fn new() -> Self { Self::default() }
+
+    fn update( &mut self, x : f64, w : f64 ) {
+        let wa = self.weight;
+        let n = wa + w;
+        if n <= 0. {
+            *self = Self::default();
+            return
+        }
+        let delta = x - self.mean;
+        let (d2, d3, d4) = ( delta * delta / n, delta * delta * delta / ( n * n ), delta.powi( 4 ) / ( n * n * n ) );
+        self.m4 += d4 * wa * w * ( wa * wa - wa * w + w * w ) + 6. * d2 * w * w * self.m2 / n - 4. * delta * w * self.m3 / n;
+        self.m3 += d3 * wa * w * ( wa - w ) - 3. * delta * w * self.m2 / n;
+        self.m2 += d2 * wa * w;
+        self.mean += delta * w / n;
+        self.weight = n;
+    }
+
+    /// add x with weight w.
+    pub fn push( &mut self, x : f64, w : f64 ) {
+        if x.is_finite() && w > 0. {
+            self.count += 1;
+            self.update( x, w );
+        }
+    }
+
+    /// take back a value added with `push( x, w )`.
+    pub fn remove( &mut self, x : f64, w : f64 ) {
+        if x.is_finite() && w > 0. && self.count > 0 {
+            self.count -= 1;
+            if self.count == 0 {
+                *self = Self::default();
+            } else {
+                self.update( x, -w );
+            }
+        }
+    }
+
+    /// multiply every weight seen so far by `factor`, the mean is unchanged.
+    pub fn decay( &mut self, factor : f64 ) {
+        self.weight *= factor;
+        self.m2 *= factor;
+        self.m3 *= factor;
+        self.m4 *= factor;
+    }
+
+    /// (mean, sd, skew, excess kurtosis) as weighted population moments; NaN with fewer than two values,
+    /// skew and kurtosis NaN when the values do not spread.
+    pub fn moments( &self ) -> (f64, f64, f64, f64) {
+        if self.count < 2 || self.weight <= 0. {
+            return (f64::NAN, f64::NAN, f64::NAN, f64::NAN)
+        }
+        let m2 = self.m2 / self.weight;
+        // a window emptied by removals leaves rounding noise rather than an exact zero.
+        if m2 <= f64::EPSILON * self.mean * self.mean || m2 <= 0. {
+            return (self.mean, 0., f64::NAN, f64::NAN)
+        }
+        let ( m3, m4 ) = ( self.m3 / self.weight, self.m4 / self.weight );
+        (self.mean, m2.sqrt(), m3 / m2.powf( 1.5 ), m4 / ( m2 * m2 ) - 3.)
+    }
+
+    /// m_3 / ( (m-1) sd^3 ) with unit weights, the sum of cubed deviations over (m-1) population sd^3 used by
+    /// the skew plots and csv since the start (0 while the values do not spread).
+    pub fn expanding_skew( &self ) -> f64 {
+        let m = self.count as f64;
+        let sv = if self.weight > 0. { ( self.m2.max( 0. ) / self.weight ).sqrt() } else { 0. };
+        let dnom = sv * sv * sv * ( m - 1. );
+        self.m3 / if dnom > 0. { dnom } else { 1. }
+    }
+}
+
+/// counts of inserted values by rank in a Fenwick tree, so the k-th smallest value is found in O(log n).
+/// The values that can be inserted are fixed up front and ranked once.
+#[derive(Debug, Clone )]
+pub struct OrderStatistics {
+    sorted : Vec<f64>,
+    tree : Vec<usize>,
+    count : usize,
+}
+
+impl OrderStatistics {
+    /// an empty structure able to hold the finite values among `values`.
+    pub fn new( values : &[f64] ) -> Self {
+        let mut sorted : Vec<f64> = values.iter().copied().filter( |v| v.is_finite() ).collect();
+        sorted.sort_by( |a, b| a.total_cmp( b ) );
+        let tree = vec![ 0; sorted.len() + 1 ];
+        Self { sorted, tree, count: 0 }
+    }
+
+    pub fn len( &self ) -> usize { self.count }
+
+    pub fn is_empty( &self ) -> bool { self.count == 0 }
+
+    fn add( &mut self, x : f64, delta : isize ) -> bool {
+        if ! x.is_finite() {
+            return false
+        }
+        // equal values share a rank, the tree counts how many of them are in.
+        let rank = self.sorted.partition_point( |v| *v < x );
+        if rank >= self.sorted.len() || self.sorted[rank] != x {
+            return false
+        }
+        let mut i = rank + 1;
+        while i < self.tree.len() {
+            self.tree[i] = ( self.tree[i] as isize + delta ) as usize;
+            i += i & i.wrapping_neg();
+        }
+        true
+    }
+
+    /// add x, values that were not given to `new` (or are not finite) are ignored.
+    pub fn insert( &mut self, x : f64 ) {
+        if self.add( x, 1 ) {
+            self.count += 1;
+        }
+    }
+
+    /// take out one copy of x.
+    pub fn remove( &mut self, x : f64 ) {
+        if self.count > 0 && self.add( x, -1 ) {
+            self.count -= 1;
+        }
+    }
+
+    /// the k-th smallest value in, from 0.
+    pub fn kth( &self, k : usize ) -> f64 {
+        if k >= self.count {
+            return f64::NAN
+        }
+        let (mut pos, mut rest) = (0, k + 1);
+        let mut step = ( self.tree.len() - 1 ).next_power_of_two();
+        while step > 0 {
+            if pos + step < self.tree.len() && self.tree[pos + step] < rest {
+                pos += step;
+                rest -= self.tree[pos];
+            }
+            step /= 2;
+        }
+        self.sorted[pos]
+    }
+
+    /// the q quantile interpolated linearly between the order statistics at ( count - 1 ) q.
+    pub fn quantile( &self, q : f64 ) -> f64 {
+        if self.count == 0 {
+            return f64::NAN
+        }
+        let at = ( self.count - 1 ) as f64 * q.clamp( 0., 1. );
+        let ( lo, hi ) = ( at.floor() as usize, at.ceil() as usize );
+        let ( a, b ) = ( self.kth( lo ), self.kth( hi ) );
+        a + ( b - a ) * ( at - lo as f64 )
+    }
+}
+
+/// the quantiles `levels` of every prefix data[..=k], one trace per level (missing values skipped).
+pub fn rolling_quantiles( data : &[f64], levels : &[f64] ) -> Vec<Vec<f64>> {
+    let mut order = OrderStatistics::new( data );
+    let mut traces = vec![ Vec::with_capacity( data.len() ); levels.len() ];
+    for x in data {
+        order.insert( *x );
+        traces.iter_mut().zip( levels ).for_each( |(trace, q)| trace.push( order.quantile( *q ) ) );
+    }
+    traces
+}
+
+/// P( X > x ) for a standard normal X.
+pub fn normal_sf( x : f64 ) -> f64 {
+    0.5 * libm::erfc( x / std::f64::consts::SQRT_2 )
+}
+
+/// P( X > x ) for X chi-squared with k degrees of freedom, the regularized upper incomplete gamma Q( k/2, x/2 )
+/// (series below a + 1, continued fraction above).
+pub fn chi2_sf( x : f64, k : f64 ) -> f64 {
+    if x.is_nan() || k <= 0. {
+        return f64::NAN
+    }
+    if x <= 0. {
+        return 1.
+    }
+    let ( a, x ) = ( k / 2., x / 2. );
+    let front = ( a * x.ln() - x - libm::lgamma( a ) ).exp();
+    if x < a + 1. {
+        let (mut term, mut sum, mut ap) = ( 1. / a, 1. / a, a );
+        for _ in 0..1000 {
+            ap += 1.;
+            term *= x / ap;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break
+            }
+        }
+        ( 1. - front * sum ).max( 0. )
+    } else {
+        // modified Lentz evaluation of the continued fraction.
+        let tiny = 1e-300;
+        let mut b = x + 1. - a;
+        let (mut c, mut d) = ( 1. / tiny, 1. / b );
+        let mut h = d;
+        for i in 1..1000 {
+            let an = -( i as f64 ) * ( i as f64 - a );
+            b += 2.;
+            d = an * d + b;
+            d = if d.abs() < tiny { 1. / tiny } else { 1. / d };
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            let step = d * c;
+            h *= step;
+            if ( step - 1. ).abs() < 1e-15 {
+                break
+            }
+        }
+        front * h
+    }
+}
+
+/// one formal test of the residuals, the fit passes it when p_value >= the significance level
+/// (NaN when there are too few residuals for it).
+#[derive(Debug, Serialize, Deserialize, Clone )]
+pub struct DiagnosticTest {
+    pub name : String,
+    /// what the test looks for, e.g. "autocorrelation".
+    pub checks : String,
+    pub statistic : f64,
+    pub p_value : f64,
+}
+
+impl DiagnosticTest {
+    fn new( name : &str, checks : &str, statistic : f64, p_value : f64 ) -> Self {
+        Self { name: String::from( name ), checks: String::from( checks ), statistic, p_value }
+    }
+
+    /// None when the test could not be computed.
+    pub fn passes( &self, alpha : f64 ) -> Option<bool> {
+        if self.p_value.is_nan() { None } else { Some( self.p_value >= alpha ) }
+    }
+}
+
+////////////////
+///
+/// formal tests of the residuals r = data - model, each with "the residuals are white noise" as its null:
+///     ljung_box :      Q = n (n+2) sum_{k=1}^{h} ρ_k^2 / (n-k)  ~ χ²(h), autocorrelation up to lag h
+///     durbin_watson :  d = sum (r_t - r_{t-1})^2 / sum r_t^2, about 2 without lag one autocorrelation,
+///                      p from the large sample limit (d - 2) sqrt(n) / 2 ~ N(0,1), two sided
+///     runs :           the number of runs of residuals of one sign against its mean under random ordering
+///                      (Wald-Wolfowitz), two sided; few runs mean the model misses a structure in time
+///     jarque_bera :    JB = n/6 ( S^2 + K^2/4 ) ~ χ²(2) for skewness S and excess kurtosis K, normality
+///     breusch_pagan :  n R^2 of r^2 regressed on the model value ~ χ²(1) (Koenker's studentized form),
+///                      a residual spread growing with the level
+/// Missing residuals are dropped first, `model` holds the model value at each residual.
+pub fn residual_diagnostics( residuals : &[f64], model : &[f64], lags : usize ) -> Vec<DiagnosticTest> {
+    let ( r, m ) : ( Vec<f64>, Vec<f64> ) = residuals.iter().zip( model ).filter( |(r, m)| r.is_finite() && m.is_finite() ).map( |(r, m)| ( *r, *m ) ).unzip();
+    let n = r.len();
+    let nf = n as f64;
+    let mean = r.iter().sum::<f64>() / nf;
+    let ss : f64 = r.iter().map( |x| ( x - mean ).powi( 2 ) ).sum();
+    let spread = n > 2 && ss > 0.;
+
+    let ljung_box = if spread && lags > 0 && n > lags + 1 {
+        let q = nf * ( nf + 2. ) * (1..=lags).map( |k| {
+            let rho = (k..n).map( |t| ( r[t] - mean ) * ( r[t - k] - mean ) ).sum::<f64>() / ss;
+            rho * rho / ( nf - k as f64 )
+        }).sum::<f64>();
+        DiagnosticTest::new( &format!( "ljung_box({lags})" ), "autocorrelation", q, chi2_sf( q, lags as f64 ) )
+    } else {
+        DiagnosticTest::new( &format!( "ljung_box({lags})" ), "autocorrelation", f64::NAN, f64::NAN )
+    };
+
+    let sq : f64 = r.iter().map( |x| x * x ).sum();
+    let durbin_watson = if n > 2 && sq > 0. {
+        let d = r.windows( 2 ).map( |w| ( w[1] - w[0] ).powi( 2 ) ).sum::<f64>() / sq;
+        DiagnosticTest::new( "durbin_watson", "lag one autocorrelation", d, 2. * normal_sf( ( ( d - 2. ) * nf.sqrt() / 2. ).abs() ) )
+    } else {
+        DiagnosticTest::new( "durbin_watson", "lag one autocorrelation", f64::NAN, f64::NAN )
+    };
+
+    let signs : Vec<bool> = r.iter().filter( |x| **x != mean ).map( |x| *x > mean ).collect();
+    let ( n1, n2 ) = ( signs.iter().filter( |s| **s ).count() as f64, signs.iter().filter( |s| ! **s ).count() as f64 );
+    let runs = if n1 > 0. && n2 > 0. && n1 + n2 > 2. {
+        let count = 1 + signs.windows( 2 ).filter( |w| w[0] != w[1] ).count();
+        let total = n1 + n2;
+        let expected = 2. * n1 * n2 / total + 1.;
+        let variance = 2. * n1 * n2 * ( 2. * n1 * n2 - total ) / ( total * total * ( total - 1. ) );
+        let p = if variance > 0. { 2. * normal_sf( ( count as f64 - expected ).abs() / variance.sqrt() ) } else { f64::NAN };
+        DiagnosticTest::new( "runs", "structure in the signs", count as f64, p )
+    } else {
+        DiagnosticTest::new( "runs", "structure in the signs", f64::NAN, f64::NAN )
+    };
+
+    let jarque_bera = if spread {
+        let m2 = ss / nf;
+        let skew = r.iter().map( |x| ( x - mean ).powi( 3 ) ).sum::<f64>() / nf / m2.powf( 1.5 );
+        let kurtosis = r.iter().map( |x| ( x - mean ).powi( 4 ) ).sum::<f64>() / nf / ( m2 * m2 ) - 3.;
+        let jb = nf / 6. * ( skew * skew + kurtosis * kurtosis / 4. );
+        DiagnosticTest::new( "jarque_bera", "normality", jb, chi2_sf( jb, 2. ) )
+    } else {
+        DiagnosticTest::new( "jarque_bera", "normality", f64::NAN, f64::NAN )
+    };
+
+    let e : Vec<f64> = r.iter().map( |x| x * x ).collect();
+    let ( e_mean, m_mean ) = ( e.iter().sum::<f64>() / nf, m.iter().sum::<f64>() / nf );
+    let sxy : f64 = e.iter().zip( &m ).map( |(e, m)| ( e - e_mean ) * ( m - m_mean ) ).sum();
+    let sxx : f64 = m.iter().map( |m| ( m - m_mean ).powi( 2 ) ).sum();
+    let syy : f64 = e.iter().map( |e| ( e - e_mean ).powi( 2 ) ).sum();
+    let breusch_pagan = if n > 2 && sxx > 0. && syy > 0. {
+        let lm = nf * sxy * sxy / ( sxx * syy );
+        DiagnosticTest::new( "breusch_pagan", "heteroscedasticity", lm, chi2_sf( lm, 1. ) )
+    } else {
+        DiagnosticTest::new( "breusch_pagan", "heteroscedasticity", f64::NAN, f64::NAN )
+    };
+
+    vec![ ljung_box, durbin_watson, runs, jarque_bera, breusch_pagan ]
+}
+
+/// the Ljung-Box lag used when none is given, min( 10, n / 5 ).
+pub fn default_lags( n : usize ) -> usize {
+    ( n / 5 ).clamp( 1, 10 )
+}
+
+/// sequential change detectors run on the standardised residuals, see `detect_changes`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord )]
+#[serde(rename_all = "snake_case")]
+pub enum Detector {
+    Cusum,
+    PageHinkley,
+    Bocpd,
+}
+
+impl std::str::FromStr for Detector {
+    type Err = HumptyError;
+    fn from_str( s : &str ) -> Result<Self> {
+        match s {
+            "cusum" => Ok( Detector::Cusum ),
+            "page_hinkley" | "ph" => Ok( Detector::PageHinkley ),
+            "bocpd" => Ok( Detector::Bocpd ),
+            _ => Err( HumptyError::parse( "change detector", format!( "'{s}' is not one of cusum, page_hinkley, bocpd" ))),
+        }
+    }
+}
+
+impl Detector {
+    pub fn name( &self ) -> &'static str {
+        match self {
+            Detector::Cusum => "cusum",
+            Detector::PageHinkley => "page_hinkley",
+            Detector::Bocpd => "bocpd",
+        }
+    }
+}
+
+fn all_detectors() -> Vec<Detector> { vec![ Detector::Cusum, Detector::PageHinkley, Detector::Bocpd ] }
+fn default_drift() -> f64 { 0.5 }
+fn default_threshold() -> f64 { 5. }
+fn default_run_length() -> f64 { 250. }
+fn default_probability() -> f64 { 0.5 }
+
+/// settings of the change detectors, drift and threshold in residual standard deviations:
+///     cusum :         S+ = max( 0, S+ + z - μ - drift ), S- likewise for μ - z, alarm when one exceeds threshold;
+///                     μ is 0 until the first alarm, then the running mean of the segment since the last onset
+///     page_hinkley :  the cumulative deviation of z from its running mean less drift against its minimum,
+///                     both directions, alarm when the gap exceeds threshold
+///     bocpd :         Bayesian online change-point detection (Adams & MacKay) with a constant hazard
+///                     1 / run_length and a normal mean for each run, alarm when the posterior probability of
+///                     a change in the last `BOCPD_WINDOW` rows first exceeds `probability`
+/// every detector starts afresh after its alarm.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq )]
+pub struct AlarmConfig {
+    #[serde(default = "all_detectors")]
+    pub detectors : Vec<Detector>,
+    #[serde(default = "default_drift")]
+    pub drift : f64,
+    #[serde(default = "default_threshold")]
+    pub threshold : f64,
+    #[serde(default = "default_run_length")]
+    pub run_length : f64,
+    #[serde(default = "default_probability")]
+    pub probability : f64,
+}
+
+impl Default for AlarmConfig {
+    fn default() -> Self {
+        Self { detectors: all_detectors(), drift: default_drift(), threshold: default_threshold(), run_length: default_run_length(), probability: default_probability() }
+    }
+}
+
+/// rows back in which bocpd looks for a change.
+pub const BOCPD_WINDOW : usize = 10;
+
+/// runs of bocpd longer than this are merged into the next longest, bounding the work per row.
+const BOCPD_MAX_RUN : usize = 1000;
+
+/// one alarm, `index` and `onset` are positions in the residual stream:
+///     onset :      where the detector places the start of the change
+///     magnitude :  mean z from onset to the alarm less the mean z of the rows before it (since the previous
+///                  onset of the same detector), the size of the shift in residual standard deviations
+///     statistic :  the detector value at the alarm, signed by the direction for cusum and page_hinkley,
+///                  the change probability for bocpd
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq )]
+pub struct Alarm {
+    pub detector : Detector,
+    pub index : usize,
+    pub onset : usize,
+    pub magnitude : f64,
+    pub statistic : f64,
+}
+
+/// ( r - mean ) / sd with the mean and sd of the finite `reference` residuals (those of the fitted rows).
+pub fn standardise( residuals : &[f64], reference : &[f64] ) -> Result<Vec<f64>> {
+    let mut acc = MomentAccumulator::new();
+    reference.iter().for_each( |r| acc.push( *r, 1. ) );
+    let ( mean, sd, _, _ ) = acc.moments();
+    if sd.is_nan() || sd <= 0. {
+        return Err( HumptyError::parse( "residual alarms", "the residuals of the fitted rows do not spread, nothing to standardise by" ))
+    }
+    Ok( residuals.iter().map( |r| ( r - mean ) / sd ).collect() )
+}
+
+fn finite_mean( z : &[f64] ) -> f64 {
+    let (n, sum) = z.iter().filter( |x| x.is_finite() ).fold( (0, 0.), |(n, s), x| ( n + 1, s + x ) );
+    if n > 0 { sum / n as f64 } else { 0. }
+}
+
+fn alarm( detector : Detector, z : &[f64], previous : usize, onset : usize, index : usize, statistic : f64 ) -> Alarm {
+    let onset = onset.min( index );
+    Alarm { detector, index, onset, magnitude: finite_mean( &z[onset..=index] ) - finite_mean( &z[previous.min( onset )..onset] ), statistic }
+}
+
+fn cusum( z : &[f64], drift : f64, threshold : f64 ) -> Vec<Alarm> {
+    let mut alarms = Vec::new();
+    let ( mut hi, mut lo, mut onset_hi, mut onset_lo, mut previous ) = ( 0., 0., 0, 0, 0 );
+    // the level watched, 0 for the fitted residuals, after an alarm the running mean of the new segment.
+    let mut segment : Option<MomentAccumulator> = None;
+    for (t, x) in z.iter().enumerate() {
+        if ! x.is_finite() {
+            continue
+        }
+        let target = match segment.as_mut() {
+            Some( acc ) => { acc.push( *x, 1. ); acc.mean },
+            None => 0.,
+        };
+        hi = f64::max( 0., hi + x - target - drift );
+        lo = f64::max( 0., lo - x + target - drift );
+        if hi == 0. { onset_hi = t + 1; }
+        if lo == 0. { onset_lo = t + 1; }
+        if hi > threshold || lo > threshold {
+            let ( onset, statistic ) = if hi >= lo { ( onset_hi, hi ) } else { ( onset_lo, -lo ) };
+            let a = alarm( Detector::Cusum, z, previous, onset, t, statistic );
+            previous = a.onset;
+            let mut acc = MomentAccumulator::new();
+            z[a.onset..=t].iter().for_each( |x| acc.push( *x, 1. ) );
+            segment = Some( acc );
+            alarms.push( a );
+            ( hi, lo, onset_hi, onset_lo ) = ( 0., 0., t + 1, t + 1 );
+        }
+    }
+    alarms
+}
+
+fn page_hinkley( z : &[f64], drift : f64, threshold : f64 ) -> Vec<Alarm> {
+    let mut alarms = Vec::new();
+    let mut previous = 0;
+    let mut mean = MomentAccumulator::new();
+    // cumulative deviations up and down, their minima and where those were reached.
+    let ( mut up, mut down, mut min_up, mut min_down, mut onset_up, mut onset_down ) = ( 0., 0., 0., 0., 0, 0 );
+    for (t, x) in z.iter().enumerate() {
+        if ! x.is_finite() {
+            continue
+        }
+        mean.push( *x, 1. );
+        up += x - mean.mean - drift;
+        down += mean.mean - x - drift;
+        if up < min_up { ( min_up, onset_up ) = ( up, t + 1 ); }
+        if down < min_down { ( min_down, onset_down ) = ( down, t + 1 ); }
+        let ( gap_up, gap_down ) = ( up - min_up, down - min_down );
+        if gap_up > threshold || gap_down > threshold {
+            let ( onset, statistic ) = if gap_up >= gap_down { ( onset_up, gap_up ) } else { ( onset_down, -gap_down ) };
+            let a = alarm( Detector::PageHinkley, z, previous, onset, t, statistic );
+            previous = a.onset;
+            alarms.push( a );
+            mean = MomentAccumulator::new();
+            ( up, down, min_up, min_down, onset_up, onset_down ) = ( 0., 0., 0., 0., t + 1, t + 1 );
+        }
+    }
+    alarms
+}
+
+/// z ~ N( μ, 1 ) within a run, μ ~ N( 0, 1 ) when a run starts.
+fn bocpd( z : &[f64], run_length : f64, probability : f64 ) -> Vec<Alarm> {
+    let hazard = 1. / run_length.max( 1. );
+    let density = |x : f64, mean : f64, var : f64| ( -( x - mean ).powi( 2 ) / ( 2. * var ) ).exp() / ( 2. * std::f64::consts::PI * var ).sqrt();
+    let mut alarms = Vec::new();
+    let ( mut previous, mut armed, mut since_start ) = ( 0, true, 0 );
+    // ( probability, values in the run, their sum ) for the runs still possible.
+    let mut runs : Vec<(f64, usize, f64)> = Vec::new();
+    for (t, x) in z.iter().enumerate() {
+        if ! x.is_finite() {
+            continue
+        }
+        since_start += 1;
+        let change = hazard * density( *x, 0., 2. );
+        let mut next : Vec<(f64, usize, f64)> = Vec::with_capacity( runs.len() + 1 );
+        next.push( ( if runs.is_empty() { 1. } else { change }, 1, *x ) );
+        next.extend( runs.iter().map( |(p, n, sum)| {
+            let precision = 1. + *n as f64;
+            ( p * ( 1. - hazard ) * density( *x, sum / precision, 1. + 1. / precision ), n + 1, sum + x )
+        }));
+        if next.len() > BOCPD_MAX_RUN {
+            // merge the longest run into the one before it.
+            let (p, _, _) = next.pop().expect("more than BOCPD_MAX_RUN runs");
+            next.last_mut().expect("more than BOCPD_MAX_RUN runs").0 += p;
+        }
+        let total : f64 = next.iter().map( |r| r.0 ).sum();
+        if total.is_nan() || total <= 0. {
+            // no run explains x, start over from it.
+            next = vec![ ( 1., 1, *x ) ];
+        } else {
+            next.iter_mut().for_each( |r| r.0 /= total );
+        }
+        runs = next;
+        let recent = runs.iter().take_while( |r| r.1 <= BOCPD_WINDOW ).collect::<Vec<_>>();
+        let p_change : f64 = recent.iter().map( |r| r.0 ).sum();
+        // runs starting with the series are not changes.
+        if p_change > probability && armed && since_start > BOCPD_WINDOW {
+            let n = recent.iter().max_by( |a, b| a.0.total_cmp( &b.0 ) ).map_or( 1, |r| r.1 );
+            let onset = z[..=t].iter().enumerate().rev().filter( |(_, x)| x.is_finite() ).nth( n - 1 ).map_or( t, |(k, _)| k );
+            let a = alarm( Detector::Bocpd, z, previous, onset, t, p_change );
+            previous = a.onset;
+            alarms.push( a );
+            armed = false;
+        } else if p_change <= probability {
+            armed = true;
+        }
+    }
+    alarms
+}
+
+/// run the configured detectors over the standardised residuals z, alarms ordered by position.
+pub fn detect_changes( z : &[f64], config : &AlarmConfig ) -> Vec<Alarm> {
+    let mut detectors = config.detectors.clone();
+    detectors.sort();
+    detectors.dedup();
+    let mut alarms : Vec<Alarm> = detectors.iter().flat_map( |d| match d {
+        Detector::Cusum => cusum( z, config.drift, config.threshold ),
+        Detector::PageHinkley => page_hinkley( z, config.drift, config.threshold ),
+        Detector::Bocpd => bocpd( z, config.run_length, config.probability ),
+    }).collect();
+    alarms.sort_by_key( |a| ( a.index, a.detector ) );
+    alarms
+}
+
+#[test]
+fn test_streaming_matches_direct() {
+    let data : Vec<f64> = (0..200).map( |k| ( k as f64 * 0.7 ).sin() * 10. + if k % 17 == 0 { 40. } else { 0. } + k as f64 * 0.05 ).collect();
+    let direct = |xs : &[f64]| {
+        let n = xs.len() as f64;
+        let mean = xs.iter().sum::<f64>() / n;
+        let m = |p : i32| xs.iter().map( |x| ( x - mean ).powi( p ) ).sum::<f64>() / n;
+        (mean, m( 2 ).sqrt(), m( 3 ) / m( 2 ).powf( 1.5 ), m( 4 ) / ( m( 2 ) * m( 2 ) ) - 3.)
+    };
+    // a trailing window of 30, pushed and removed through the whole series.
+    let mut acc = MomentAccumulator::new();
+    for (k, x) in data.iter().enumerate() {
+        acc.push( *x, 1. );
+        if k >= 30 {
+            acc.remove( data[k - 30], 1. );
+        }
+        if k >= 1 {
+            let (a, b) = ( acc.moments(), direct( &data[k.saturating_sub( 29 )..=k] ) );
+            assert!( ( a.0 - b.0 ).abs() < 1e-9 && ( a.1 - b.1 ).abs() < 1e-9 );
+            assert!( ( a.2 - b.2 ).abs() < 1e-8 && ( a.3 - b.3 ).abs() < 1e-8 );
+        }
+    }
+    // quantiles of every prefix against a sort, interpolated as polars' linear quantile.
+    let levels = [ 0., 0.1, 0.5, 0.9, 1. ];
+    let traces = rolling_quantiles( &data, &levels );
+    for k in [ 0, 1, 6, 99, 199 ] {
+        let mut prefix = data[..=k].to_vec();
+        prefix.sort_by( |a, b| a.total_cmp( b ) );
+        for (j, q) in levels.iter().enumerate() {
+            let at = k as f64 * q;
+            let (lo, hi) = ( prefix[at.floor() as usize], prefix[at.ceil() as usize] );
+            assert!( ( traces[j][k] - ( lo + ( hi - lo ) * ( at - at.floor() ) ) ).abs() < 1e-12 );
+        }
+    }
+    let mut order = OrderStatistics::new( &[ 3., 1., 3., 2. ] );
+    [ 3., 1., 3., 2. ].iter().for_each( |x| order.insert( *x ) );
+    order.remove( 3. );
+    assert_eq!( ( order.len(), order.kth( 0 ), order.kth( 1 ), order.kth( 2 ) ), ( 3, 1., 2., 3. ) );
+}
+
+#[test]
+fn test_residual_diagnostics() {
+    use rand::SeedableRng;
+    use rand_distr::{Distribution, Normal};
+    assert!( ( chi2_sf( 3.841458820694124, 1. ) - 0.05 ).abs() < 1e-9 );
+    assert!( ( chi2_sf( 18.307038053275146, 10. ) - 0.05 ).abs() < 1e-9 );
+    assert!( ( chi2_sf( 7., 2. ) - ( -3.5f64 ).exp() ).abs() < 1e-12 );
+    let mut rng = rand::rngs::StdRng::seed_from_u64( 7 );
+    let normal = Normal::new( 0., 1. ).unwrap();
+    let model : Vec<f64> = (0..300).map( |t| 100. + t as f64 ).collect();
+    let noise : Vec<f64> = (0..300).map( |_| normal.sample( &mut rng ) ).collect();
+    let alpha = 0.01;
+    let white = residual_diagnostics( &noise, &model, default_lags( 300 ) );
+    assert!( white.iter().all( |d| d.passes( alpha ) == Some( true ) ), "{white:?}" );
+    // a missed hump leaves a slow wave: autocorrelated, long runs; a spread growing with the model level.
+    let wave : Vec<f64> = noise.iter().enumerate().map( |(t, e)| 5. * ( t as f64 / 30. ).sin() + e ).collect();
+    let failed : Vec<String> = residual_diagnostics( &wave, &model, 10 ).iter().filter( |d| d.passes( alpha ) == Some( false ) ).map( |d| d.name.clone() ).collect();
+    assert!( [ "ljung_box(10)", "durbin_watson", "runs" ].iter().all( |n| failed.contains( &n.to_string() ) ), "{failed:?}" );
+    let growing : Vec<f64> = noise.iter().zip( &model ).map( |(e, m)| e * ( m - 90. ) ).collect();
+    assert_eq!( residual_diagnostics( &growing, &model, 10 )[4].passes( alpha ), Some( false ) );
+    assert!( residual_diagnostics( &[ 1. ], &[ 1. ], 10 ).iter().all( |d| d.passes( alpha ).is_none() ) );
+}
+
+#[test]
+fn test_change_detectors() {
+    use rand::SeedableRng;
+    use rand_distr::{Distribution, Normal};
+    let mut rng = rand::rngs::StdRng::seed_from_u64( 11 );
+    let normal = Normal::new( 0., 1. ).unwrap();
+    // calm for 200 rows, then a new growth phase pushes the residuals up by 2 sd from row 200.
+    let z : Vec<f64> = (0..300).map( |t| normal.sample( &mut rng ) + if t >= 200 { 2. } else { 0. } ).collect();
+    let alarms = detect_changes( &z, &AlarmConfig::default() );
+    for d in all_detectors() {
+        let shift = alarms.iter().find( |a| a.detector == d && a.index >= 200 ).unwrap_or_else( || panic!( "no {d:?} alarm in {alarms:?}" ));
+        assert!( shift.index < 215 && shift.onset >= 190 && shift.onset <= shift.index, "{shift:?}" );
+        assert!( shift.magnitude > 1. && shift.statistic > 0., "{shift:?}" );
+        // one alarm for the change, the detector settles on the new level.
+        assert!( alarms.iter().all( |a| a.detector != d || a.index <= shift.index || a.index < 200 ), "{alarms:?}" );
+    }
+    let calm : Vec<f64> = z[..200].to_vec();
+    assert!( detect_changes( &calm, &AlarmConfig::default() ).len() <= 1 );
+    assert!( standardise( &[ 1., 2. ], &[ 3., 3. ] ).is_err() );
+}
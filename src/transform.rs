@@ -0,0 +1,115 @@
+use serde::{Serialize, Deserialize};
+
+use crate::error::{HumptyError, Result};
+use crate::models::nan_if_null;
+
+fn unresolved() -> f64 { f64::NAN }
+
+////////////////
+///
+/// value transform applied to the training series before fitting, the model is fitted in the transformed
+/// space and carries the transform as its `link`, so evaluating it gives data units again:
+///     unit_range :  z = ( y - min ) / ( max - min )
+///     z_score :     z = ( y - mean ) / sd
+///     log1p :       z = ln( 1 + y )
+/// min/max and mean/sd are taken from the training slice when they are not given.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq )]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ValueTransform {
+    UnitRange {
+        #[serde(default = "unresolved", deserialize_with = "nan_if_null")]
+        min : f64,
+        #[serde(default = "unresolved", deserialize_with = "nan_if_null")]
+        max : f64,
+    },
+    ZScore {
+        #[serde(default = "unresolved", deserialize_with = "nan_if_null")]
+        mean : f64,
+        #[serde(default = "unresolved", deserialize_with = "nan_if_null")]
+        sd : f64,
+    },
+    Log1p,
+}
+
+impl std::str::FromStr for ValueTransform {
+    type Err = HumptyError;
+    fn from_str( s : &str ) -> Result<Self> {
+        match s {
+            "unit" | "unit_range" => Ok( ValueTransform::UnitRange { min: f64::NAN, max: f64::NAN } ),
+            "zscore" | "z_score" => Ok( ValueTransform::ZScore { mean: f64::NAN, sd: f64::NAN } ),
+            "log1p" => Ok( ValueTransform::Log1p ),
+            _ => Err( HumptyError::parse( "value transform", format!( "'{s}' is not one of unit, zscore, log1p" ))),
+        }
+    }
+}
+
+impl ValueTransform {
+    /// fill in the parameters that were not given from the training values (missing values are skipped).
+    pub fn resolve( &self, values : &[f64] ) -> Result<Self> {
+        let present : Vec<f64> = values.iter().copied().filter( |v| v.is_finite() ).collect();
+        if present.is_empty() {
+            return Err( HumptyError::parse( "value transform", "no values to take the parameters from" ))
+        }
+        let n = present.len() as f64;
+        Ok( match self {
+            ValueTransform::UnitRange { min, max } => ValueTransform::UnitRange {
+                min: if min.is_finite() { *min } else { present.iter().copied().fold( f64::INFINITY, f64::min ) },
+                max: if max.is_finite() { *max } else { present.iter().copied().fold( f64::NEG_INFINITY, f64::max ) },
+            },
+            ValueTransform::ZScore { mean, sd } => {
+                let m = if mean.is_finite() { *mean } else { present.iter().sum::<f64>() / n };
+                ValueTransform::ZScore {
+                    mean: m,
+                    sd: if sd.is_finite() { *sd } else { ( present.iter().map( |v| ( v - m ) * ( v - m ) ).sum::<f64>() / n ).sqrt() },
+                }
+            },
+            ValueTransform::Log1p => ValueTransform::Log1p,
+        })
+    }
+
+    /// y = shift + scale * z for the affine transforms, so κ scales by `scale` and the offset maps to shift + scale * offset.
+    pub fn affine( &self ) -> Option<(f64, f64)> {
+        let guard = |s : f64| if s != 0. && s.is_finite() { s } else { 1. };
+        match self {
+            ValueTransform::UnitRange { min, max } => Some( ( *min, guard( max - min ) ) ),
+            ValueTransform::ZScore { mean, sd } => Some( ( *mean, guard( *sd ) ) ),
+            ValueTransform::Log1p => None,
+        }
+    }
+
+    /// data units -> model space.
+    pub fn forward( &self, y : f64 ) -> f64 {
+        match self.affine() {
+            Some( ( shift, scale ) ) => ( y - shift ) / scale,
+            None => y.ln_1p(),
+        }
+    }
+
+    /// model space -> data units.
+    pub fn inverse( &self, z : f64 ) -> f64 {
+        match self.affine() {
+            Some( ( shift, scale ) ) => shift + scale * z,
+            None => z.exp_m1(),
+        }
+    }
+
+    /// transform the training values, values outside the domain (log1p of y <= -1) are an error.
+    pub fn apply( &self, values : &[f64] ) -> Result<Vec<f64>> {
+        let z : Vec<f64> = values.iter().map( |y| self.forward( *y ) ).collect();
+        match z.iter().position( |v| ! v.is_finite() ) {
+            Some( k ) => Err( HumptyError::parse( "value transform", format!( "{:?} is undefined for the value {} at observation {k}", self, values[k] ))),
+            None => Ok( z ),
+        }
+    }
+}
+
+#[test]
+fn test_value_transform_round_trip() {
+    let y = [ 3., 10., 1000., 0. ];
+    for kind in [ "unit", "zscore", "log1p" ] {
+        let t = kind.parse::<ValueTransform>().unwrap().resolve( &y ).unwrap();
+        let z = t.apply( &y ).unwrap();
+        y.iter().zip( z.iter() ).for_each( |(a, b)| assert!( ( t.inverse( *b ) - a ).abs() < 1e-9 ) );
+    }
+    assert!( ValueTransform::Log1p.apply( &[ -2. ] ).is_err() );
+}
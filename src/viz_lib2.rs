@@ -158,6 +158,20 @@ pub fn add_vert_line_(mut layout : Layout , x : f64, color : NamedColor, width :
 pub fn add_vert_line( mut layout : Layout , x : f64 ) -> Layout {
     add_vert_line_( layout, x, NamedColor::Pink, 1.0 )
 }
+pub fn add_horiz_line_(mut layout : Layout , y : f64, color : NamedColor, width : f64 ) -> Layout {
+    layout.add_shape(
+        Shape::new()
+            .x_ref("paper")
+            .y_ref("y")
+            .shape_type(ShapeType::Line)
+            .x0(0)
+            .y0(y)
+            .x1(1)
+            .y1(y)
+            .line(ShapeLine::new().color(color).width(width).dash(DashType::Dash)),
+    );
+    layout
+}
 
 pub fn add_vert_shaded_region_( mut layout : Layout , x1 : f64, x2 : f64, color :NamedColor, width :f64 , opacity : f64 )-> Layout {
     layout.add_shape(
@@ -312,6 +326,31 @@ pub fn plot_c(
     view.finalize( act );
 }
 
+/// data, an ensemble mean with a band of one spread either side, and the members drawn more opaque the more weight
+/// they carry; `members` holds ( name, values at `tm`, weight ).
+pub fn plot_ensemble( 
+    t : &Vec<f64>,       d: &Vec<f64>, 
+    tm: &Vec<f64>,       mean : &Vec<f64>, 
+    lo: &Vec<f64>,       hi : &Vec<f64>,
+    members : &[(String, Vec<f64>, f64)],
+    act : PlotAction, title : String, x_label : String, y_label : String   ) {
+    let mut view = viz_graph::new();
+    let layout = Layout::new()
+        .title(Title::new( title.as_str() ))
+        .x_axis(Axis::new().title(Title::new( x_label.as_str())))
+        .y_axis(Axis::new().title(Title::new( y_label.as_str())));
+    view.plot.set_layout(layout);
+    view.plot = add_shaded_region_between_two_curves_( view.plot, tm, hi, lo, Rgba::new(255, 192, 203,0.4), Rgba::new(255, 192, 203,0.6), String::from("mean ± spread"), true );
+    view.plot = plot_data( view.plot, t, d , &view.style); 
+    let wmax = members.iter().map( |(_, _, w)| *w ).fold( 0., f64::max );
+    for (name, m, w) in members {
+        let opacity = if wmax > 0. { 0.15 + 0.75 * w / wmax } else { 0.15 };
+        view.plot = plot_trace_( view.plot, tm, m, Mode::Lines, 1, format!( "{name} (w {w:.3})" ), NamedColor::Gray, opacity );
+    }
+    view.plot = plot_model( view.plot, tm, mean, "ensemble mean", &view.style );
+    view.finalize( act );
+}
+
 pub fn plot_c_histo( t : &Vec<f64>, d: &Vec<f64>, tm: &Vec<f64>, m : &Vec<f64>, act : PlotAction, title : String, x_label : String, y_label : String   ) {
     let mut view = viz_graph::new();
     let disp: Vec<f64> = d.iter().zip( m ).map( |(a,b)| a - b ).collect();
@@ -340,12 +379,17 @@ pub fn plot_d(  t : &Vec<f64>,
                 act : PlotAction, 
                 title : String, 
                 x_label : String, 
-                y_label : String   ) {
+                y_label : String,
+                alarms : &[(String, f64)] ) {
     let mut view = viz_graph::new();
-    let layout = Layout::new()
+    let mut layout = Layout::new()
                 .title(Title::new( title.as_str() ))
                 .x_axis(Axis::new().title(Title::new( x_label.as_str())))
                 .y_axis(Axis::new().title(Title::new( y_label.as_str())));
+    for (name, x) in alarms {
+        layout = add_vert_line_( layout, *x, NamedColor::Red, 1.0 );
+        layout.add_annotation( Annotation::new().x_ref("x").y_ref("paper").x(*x).y(1.0).text( name.as_str() ).show_arrow(false) );
+    }
     view.plot.set_layout(layout);
      
     let regions = dtb.iter()
@@ -389,6 +433,32 @@ pub fn plot_e(  t : &Vec<f64>,
     view.finalize( act );
 }
 
+/////////////////////
+/// one line per named series (e.g. a score per horizon for each forecaster), with an optional dashed reference level.
+/// 
+pub fn plot_lines(  lines : &[(String, Vec<f64>, Vec<f64>)], 
+                reference : Option<f64>, 
+                act : PlotAction, 
+                title : String, 
+                x_label : String, 
+                y_label : String   ) {
+    let colors = [ NamedColor::Blue, NamedColor::OrangeRed, NamedColor::Green, NamedColor::Purple, NamedColor::Brown, NamedColor::Teal ];
+    let mut view = viz_graph::new();
+    let mut layout = Layout::new()
+                .title(Title::new( title.as_str() ))
+                .x_axis(Axis::new().title(Title::new( x_label.as_str())))
+                .y_axis(Axis::new().title(Title::new( y_label.as_str())));
+    if let Some( y ) = reference {
+        layout = add_horiz_line_( layout, y, NamedColor::Gray, 1.0 );
+    }
+    view.plot.set_layout(layout);
+
+    for (k, (name, t, d)) in lines.iter().enumerate() {
+        view.plot = plot_trace_( view.plot, t, d, Mode::LinesMarkers, 6, name.clone(), colors[k % colors.len()], 1.0 );
+    }
+    view.finalize( act );
+}
+
 // This plot is designed to show bands around the model.
 pub fn plot_d_histo(  t : &Vec<f64>, 
                 d : &Vec<f64>, 